@@ -4,23 +4,47 @@ use std::{io::{self, Error}, path::PathBuf};
 pub struct CloneArgs {
     pub url: String,
     pub target_dir: PathBuf,
+    /// `-b/--branch`: the remote branch (or tag) to check out instead of the remote's HEAD.
+    pub branch: Option<String>,
+    /// `-n/--no-checkout`: leave the working tree and index empty.
+    pub no_checkout: bool,
 }
 
 pub fn parse_args(args: &[String]) -> io::Result<CloneArgs> {
-    if args.is_empty() {
-        return Err(Error::new(io::ErrorKind::InvalidInput, "Usage: git clone <url> [directory]"));
+    let mut branch = None;
+    let mut no_checkout = false;
+    let mut positional = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-n" | "--no-checkout" => no_checkout = true,
+            "-b" | "--branch" => {
+                let name = iter.next().ok_or_else(|| Error::new(io::ErrorKind::InvalidInput, format!("option `{}' requires a value", arg)))?;
+                branch = Some(name.clone());
+            }
+            x if x.starts_with("--branch=") => branch = Some(x["--branch=".len()..].to_string()),
+            x if x.starts_with('-') => {
+                return Err(Error::new(io::ErrorKind::InvalidInput, format!("unknown option: {}", x)));
+            }
+            other => positional.push(other.to_string()),
+        }
+    }
+
+    if positional.is_empty() || positional.len() > 2 {
+        return Err(Error::new(io::ErrorKind::InvalidInput, "Usage: git clone [-n] [-b <branch>] <url> [directory]"));
     }
 
-    let url = args[0].clone();
-    let target_dir = if args.len() >= 2 {
-        PathBuf::from(&args[1])
+    let url = positional[0].clone();
+    let target_dir = if positional.len() == 2 {
+        PathBuf::from(&positional[1])
     } else {
-        let trimmed = url.trim_end_matches(".git");
+        let trimmed = url.trim_end_matches('/').trim_end_matches(".git");
         let name = trimmed
             .rsplit('/')
             .find(|s| !s.is_empty())
             .unwrap_or("repo");
         PathBuf::from(name)
     };
-    Ok(CloneArgs { url, target_dir })
-}
\ No newline at end of file
+    Ok(CloneArgs { url, target_dir, branch, no_checkout })
+}
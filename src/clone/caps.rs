@@ -31,8 +31,10 @@ impl Capabilities {
 }
 
 pub fn parse_capabilities(cap_strings: Vec<&str>) -> Capabilities {
-    let mut caps = Capabilities::default();
-    caps.all_caps = Vec::with_capacity(cap_strings.len());
+    let mut caps = Capabilities {
+        all_caps: Vec::with_capacity(cap_strings.len()),
+        ..Default::default()
+    };
 
     for cap in cap_strings {
         caps.all_caps.push(cap.to_string());
@@ -2,4 +2,5 @@ pub mod args;
 pub mod refs;
 pub mod packet_line;
 pub mod caps;
-pub mod transport;
\ No newline at end of file
+pub mod transport;
+pub mod pack;
\ No newline at end of file
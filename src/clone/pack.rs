@@ -0,0 +1,243 @@
+use std::{collections::HashMap, io::{self, Read}};
+
+use flate2::bufread::ZlibDecoder;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    clone::packet_line::pkt_line_unpacker::PackHeader,
+    hash::GitHash,
+    objects::{parser::object_header_parser::parse_object_header, read_object, write_object, ObjectType},
+    utils::streamer::BufferedStreamCursor,
+};
+
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+const CHECKSUM_LEN: usize = 20;
+
+/// How an entry of a pack stores its object.
+enum EntryKind {
+    Whole(ObjectType),
+    /// A delta against the entry at this pack offset.
+    OfsDelta(usize),
+    /// A delta against the object with this id, in the pack or already on disk.
+    RefDelta(GitHash),
+}
+
+struct PackEntry {
+    offset: usize,
+    kind: EntryKind,
+    /// The inflated object, or delta instructions.
+    data: Vec<u8>,
+}
+
+/// Writes every object of a pack as a loose object, resolving deltas, like
+/// `git unpack-objects`. Returns how many objects were written.
+pub fn unpack_objects(pack: &[u8]) -> io::Result<usize> {
+    if pack.len() < PackHeader::SIZE + CHECKSUM_LEN {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "pack is truncated"));
+    }
+    let (body, checksum) = pack.split_at(pack.len() - CHECKSUM_LEN);
+    if Sha1::digest(body).as_slice() != checksum {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "pack checksum mismatch"));
+    }
+
+    let header = PackHeader::from_cursor(&mut BufferedStreamCursor::with_chunk_size(body, PackHeader::SIZE))?;
+    if header.version != 2 && header.version != 3 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("pack version {} unsupported", header.version)));
+    }
+
+    let mut entries = Vec::with_capacity(header.num_objects as usize);
+    let mut offset = PackHeader::SIZE;
+    for _ in 0..header.num_objects {
+        let (entry, next) = read_entry(body, offset)?;
+        entries.push(entry);
+        offset = next;
+    }
+    if offset != body.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "pack has trailing garbage"));
+    }
+
+    resolve_and_write(&entries)?;
+    Ok(entries.len())
+}
+
+/// Parses the entry at `offset`, returning it and the offset of the next one.
+fn read_entry(pack: &[u8], offset: usize) -> io::Result<(PackEntry, usize)> {
+    let header = parse_object_header(&mut BufferedStreamCursor::with_chunk_size(&pack[offset..], 16))?;
+    let mut pos = offset + header.header_size;
+
+    let kind = match header.object_type {
+        ObjectType::Unknown(OFS_DELTA) => {
+            let (distance, len) = read_base_distance(&pack[pos..])?;
+            pos += len;
+            let base = offset.checked_sub(distance).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "delta base offset out of bounds"))?;
+            EntryKind::OfsDelta(base)
+        }
+        ObjectType::Unknown(REF_DELTA) => {
+            let raw = pack.get(pos..pos + CHECKSUM_LEN).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated delta base"))?;
+            pos += CHECKSUM_LEN;
+            EntryKind::RefDelta(GitHash::from_raw_bytes(raw))
+        }
+        ObjectType::Unknown(code) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown object type {} in pack", code)));
+        }
+        object_type => EntryKind::Whole(object_type),
+    };
+
+    let mut decoder = ZlibDecoder::new(&pack[pos..]);
+    let mut data = Vec::with_capacity(header.size as usize);
+    decoder.read_to_end(&mut data)?;
+    if data.len() as u64 != header.size {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("object at offset {} has the wrong size", offset)));
+    }
+    pos += decoder.total_in() as usize;
+
+    Ok((PackEntry { offset, kind, data }, pos))
+}
+
+/// The distance back to the base of an offset delta, in git's encoding where
+/// each continuation byte also adds one, and the number of bytes it took.
+fn read_base_distance(bytes: &[u8]) -> io::Result<(usize, usize)> {
+    let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, "truncated delta offset");
+    let mut byte = *bytes.first().ok_or_else(truncated)?;
+    let mut distance = (byte & 0x7f) as usize;
+    let mut len = 1;
+    while byte & 0x80 != 0 {
+        byte = *bytes.get(len).ok_or_else(truncated)?;
+        distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+        len += 1;
+    }
+    Ok((distance, len))
+}
+
+/// Rebuilds every object and writes it. Entries are resolved in rounds since
+/// a ref delta may name a base that comes later in the pack, or, in a thin
+/// pack, one that is only in the repository.
+fn resolve_and_write(entries: &[PackEntry]) -> io::Result<()> {
+    let by_offset: HashMap<usize, usize> = entries.iter().enumerate().map(|(i, e)| (e.offset, i)).collect();
+    let mut objects: Vec<Option<(ObjectType, Vec<u8>)>> = vec![None; entries.len()];
+    let mut by_hash: HashMap<GitHash, usize> = HashMap::new();
+    let mut external: HashMap<GitHash, (ObjectType, Vec<u8>)> = HashMap::new();
+
+    let mut pending: Vec<usize> = (0..entries.len()).collect();
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut still_pending = Vec::new();
+        for i in pending {
+            let base = match &entries[i].kind {
+                EntryKind::Whole(object_type) => Some((*object_type, None)),
+                EntryKind::OfsDelta(offset) => {
+                    let b = *by_offset.get(offset).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "delta base is not an entry of the pack"))?;
+                    objects[b].as_ref().map(|(t, data)| (*t, Some(data.as_slice())))
+                }
+                EntryKind::RefDelta(hash) => by_hash
+                    .get(hash)
+                    .and_then(|&b| objects[b].as_ref())
+                    .or_else(|| external.get(hash))
+                    .map(|(t, data)| (*t, Some(data.as_slice()))),
+            };
+            let Some((object_type, base)) = base else {
+                still_pending.push(i);
+                continue;
+            };
+
+            let data = match base {
+                Some(base) => apply_delta(base, &entries[i].data)?,
+                None => entries[i].data.clone(),
+            };
+            by_hash.insert(write_object(object_type, &data)?, i);
+            objects[i] = Some((object_type, data));
+        }
+
+        if still_pending.len() == before {
+            load_missing_bases(entries, &still_pending, &mut external)?;
+        }
+        pending = still_pending;
+    }
+    Ok(())
+}
+
+/// Reads the bases of stuck ref deltas from the repository; fails if none is there.
+fn load_missing_bases(entries: &[PackEntry], pending: &[usize], external: &mut HashMap<GitHash, (ObjectType, Vec<u8>)>) -> io::Result<()> {
+    let mut loaded = false;
+    for &i in pending {
+        if let EntryKind::RefDelta(hash) = &entries[i].kind {
+            if !external.contains_key(hash) {
+                if let Ok(object) = read_object(&hash.to_hex()) {
+                    external.insert(hash.clone(), object);
+                    loaded = true;
+                }
+            }
+        }
+    }
+    if loaded {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "pack has deltas whose base is missing"))
+    }
+}
+
+/// Rebuilds an object from its base and git's delta instructions: copies of
+/// base ranges and literal inserts, after the base and result sizes.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let corrupt = || io::Error::new(io::ErrorKind::InvalidData, "corrupt delta");
+    let mut pos = 0;
+    let read_size = |pos: &mut usize| -> io::Result<usize> {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*pos).ok_or_else(corrupt)?;
+            *pos += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(size);
+            }
+        }
+    };
+
+    let base_size = read_size(&mut pos)?;
+    let result_size = read_size(&mut pos)?;
+    if base_size != base.len() {
+        return Err(corrupt());
+    }
+
+    let mut result = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // which offset and size bytes follow is given by the low bits
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for bit in 0..4 {
+                if op & (1 << bit) != 0 {
+                    offset |= (*delta.get(pos).ok_or_else(corrupt)? as usize) << (8 * bit);
+                    pos += 1;
+                }
+            }
+            for bit in 0..3 {
+                if op & (0x10 << bit) != 0 {
+                    size |= (*delta.get(pos).ok_or_else(corrupt)? as usize) << (8 * bit);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let chunk = offset.checked_add(size).and_then(|end| base.get(offset..end)).ok_or_else(corrupt)?;
+            result.extend_from_slice(chunk);
+        } else if op != 0 {
+            let chunk = delta.get(pos..pos + op as usize).ok_or_else(corrupt)?;
+            result.extend_from_slice(chunk);
+            pos += op as usize;
+        } else {
+            return Err(corrupt());
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(corrupt());
+    }
+    Ok(result)
+}
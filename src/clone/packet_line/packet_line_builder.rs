@@ -12,6 +12,12 @@ pub struct UploadPackV2RequestBuilder {
 
 }
 
+impl Default for UploadPackV2RequestBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UploadPackV2RequestBuilder {
     pub fn new() -> Self {
         Self {
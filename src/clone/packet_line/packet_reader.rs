@@ -14,7 +14,7 @@ impl<R: BufRead> PacketReader<R> {
         Self { 
             inner: StreamingPeekableIter::new(reader, &[]), 
             bytes_read: 0,
-            expected_len: expected_len
+            expected_len
          }   
     }
 
@@ -1,70 +1,62 @@
-use crate::{constants::GIT_OBJECTS_DIR, objects::parser::object_header_parser::parse_object_header, utils::{file_utils::generate_temp_filename, streamer::{BufferedStreamCursor, TeeWriter}}};
-use std::{fs::{self, File}, io::{self, Read, Result, Write}, path::Path};
+use crate::{clone::pack::unpack_objects, utils::streamer::BufferedStreamCursor};
+use std::io::{self, Read, Result, Write};
+use git_packetline::{PacketLineRef, StreamingPeekableIter};
 use reqwest::blocking::Response;
-use sha1::{Digest, Sha1};
 
-pub fn unpack_pkt_res(res: Response, repo_root: &Path) -> Result<()> {
+/// Reads the `packfile` section of a protocol v2 fetch response and writes
+/// its objects to the repository. Band 1 carries the pack, band 2 progress
+/// (shown on stderr) and band 3 a fatal error. Returns the number of objects.
+pub fn unpack_pkt_res(res: Response) -> Result<usize> {
     if !res.status().is_success() {
-        return Err(io::Error::new(io::ErrorKind::Other, format!("unable to unpack response, response status is: {}", res.status())))
+        return Err(io::Error::other(format!("unable to unpack response, response status is: {}", res.status())))
     }
 
-    let mut cursor = BufferedStreamCursor::with_chunk_size(res, 128);
-    print_lines_until_pack(&mut cursor)?;
-    let pack_dir = repo_root.join(GIT_OBJECTS_DIR).join("pack");
-    fs::create_dir_all(&pack_dir)?;
-    let temp_path = pack_dir.join(generate_temp_filename(None));
-    let mut pack_file = File::create(&temp_path)?;
-    let mut hasher = Sha1::new();
-    let mut tree_writer = TeeWriter::new(&mut pack_file, &mut hasher);
-    let pack_header = parse_pack_header(&mut cursor, &mut tree_writer)?;
-    println!("Pack Header numer of objects: {:?}, Version: {}", pack_header.num_objects, pack_header.version);
-    cursor.drain_consumed();
-    persist_objects(&mut cursor, &mut tree_writer, &pack_header)?;
-    tree_writer.flush()?;
-    Ok(())
-}
+    let mut lines = StreamingPeekableIter::new(res, &[PacketLineRef::Flush]);
+    let mut in_packfile = false;
+    let mut pack = Vec::new();
+    let mut progress = RemoteProgress { at_line_start: true };
+    while let Some(line) = lines.read_line() {
+        let line = line?.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let PacketLineRef::Data(data) = line else { continue };
+        if !in_packfile {
+            // sections before the pack, like acknowledgments, are skipped
+            in_packfile = data.strip_suffix(b"\n").unwrap_or(data) == b"packfile";
+            continue;
+        }
+        match data.split_first() {
+            Some((1, bytes)) => pack.extend_from_slice(bytes),
+            Some((2, message)) => progress.write(message)?,
+            Some((3, message)) => {
+                return Err(io::Error::other(format!("remote error: {}", String::from_utf8_lossy(message).trim_end())));
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "bad sideband packet in packfile section")),
+        }
+    }
 
-pub fn persist_objects<R: Read, W: Write>(cursor: &mut BufferedStreamCursor<R>, tee: &mut TeeWriter<W, Sha1>, pack_header: &PackHeader) -> io::Result<()> {
-    for _ in 0..pack_header.num_objects {
-        let object_header = parse_object_header(cursor)?;
-        println!("Parsed object: {:?}", object_header);
+    if !in_packfile {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "server response has no packfile section"));
     }
-    Ok(())
+    unpack_objects(&pack)
 }
 
-pub fn print_lines_until_pack<R: Read>(cursor: &mut BufferedStreamCursor<R>) -> io::Result<()> {
-    let pack_magic = b"PACK";
-
-    loop {
-        let len_bytes = cursor.read(4)?;
-        if len_bytes == b"0000" {
-            continue; 
-        }
-
-        let hex_str = std::str::from_utf8(len_bytes)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid hex in pkt-line"))?;
-        let total_len = usize::from_str_radix(hex_str, 16)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid hex value"))?;
-
-        if total_len < 4 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid pkt-line length"));
-        }
-
-        let content_len = total_len - 4;
-        let content = cursor.peek(content_len)?;
+/// Shows the server's progress messages on stderr, each line prefixed with
+/// `remote: ` like git does. Messages may end mid-line.
+struct RemoteProgress {
+    at_line_start: bool,
+}
 
-        if let Some(pos) = content.windows(4).position(|w| w == pack_magic) {
-            let before = &content[..pos];
-            print!("{}", String::from_utf8_lossy(before));
-            cursor.advance(pos);
-            break; 
-        } else {
-            print!("{}", String::from_utf8_lossy(content));
-            cursor.advance(content_len);
+impl RemoteProgress {
+    fn write(&mut self, message: &[u8]) -> Result<()> {
+        let mut stderr = io::stderr().lock();
+        for segment in message.split_inclusive(|&c| c == b'\n' || c == b'\r') {
+            if self.at_line_start {
+                stderr.write_all(b"remote: ")?;
+            }
+            stderr.write_all(segment)?;
+            self.at_line_start = segment.ends_with(b"\n") || segment.ends_with(b"\r");
         }
+        Ok(())
     }
-    
-    Ok(())
 }
 
 pub struct PackHeader {
@@ -116,14 +108,3 @@ impl PackHeader {
         buf
     }
 }
-
-
-pub fn parse_pack_header<R: Read, W: Write>(
-    cursor: &mut BufferedStreamCursor<R>,
-    tee: &mut TeeWriter<W, Sha1>,) -> io::Result<PackHeader> {
-    let header = PackHeader::from_cursor(cursor)?;
-    tee.write_all(&header.to_bytes())?;
-    println!("{:02X?}", &header.to_bytes());
-
-    Ok(header)
-}
@@ -23,13 +23,13 @@ pub trait UploadPackNegotiator: Send + Sync {
 }
 
 pub fn negogiate_want(ref_ads: &RefAdvertisement, url: &str) -> Result<Response> {
-    let git_url = GitUrl::parse(&url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let git_url = GitUrl::parse(url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
     let negotiator = get_negotiator(&git_url.scheme).ok_or_else(|| {io::Error::new(io::ErrorKind::Unsupported, format!("unsupported scheme: {}", git_url.scheme))})?;
-    let res = negotiator.negogiate(&url, &ref_ads)?;
+    let res = negotiator.negogiate(url, ref_ads)?;
 
     if !res.status().is_success() {
         eprintln!("Fetch failed with status: {}", res.status());
-        return Err(io::Error::new(io::ErrorKind::Other, format!("Git server responsed with failure status: {}", res.status())))
+        return Err(io::Error::other(format!("Git server responsed with failure status: {}", res.status())))
     }
 
     Ok(res)
@@ -8,7 +8,7 @@ pub fn parse_ref_advertisement(bytes: &[u8]) -> std::io::Result<RefAdvertisement
     let cursor =    BufReader::new(Cursor::new(bytes));
     let mut reader = PacketReader::new(cursor, bytes.len()); 
 
-    _ = match reader.read_line()? {
+    match reader.read_line()? {
         Some(PacketLineRef::Data(data)) => {
             let line = std::str::from_utf8(data).unwrap_or("").trim_end();
             if !line.starts_with("# service=") {
@@ -41,7 +41,7 @@ pub fn parse_ref_advertisement(bytes: &[u8]) -> std::io::Result<RefAdvertisement
                     head = Some(hash.clone());
                 }
 
-                refs.push(AdvertisedRef { hash: hash, name: name });
+                refs.push(AdvertisedRef { hash, name });
                 if !right.is_empty() {
                     let caps = right.split_whitespace().collect();
                     capabilities = parse_capabilities(caps);
@@ -69,6 +69,12 @@ pub fn parse_ref_advertisement(bytes: &[u8]) -> std::io::Result<RefAdvertisement
     })
 }
 
+/// The name a clone gives the remote it was cloned from.
+pub const REMOTE_NAME: &str = "origin";
+
+/// Marks the advertised line giving the object an annotated tag points at.
+const PEELED_SUFFIX: &str = "^{}";
+
 pub struct RefAdvertisement {
     pub refs: Vec<AdvertisedRef>,
     pub capabilities: Capabilities,
@@ -82,17 +88,64 @@ pub struct AdvertisedRef {
 
 impl RefAdvertisement {
 
+    /// Writes the advertised refs to `packed-refs` as a clone keeps them:
+    /// branches as `refs/remotes/origin/*` and tags as they are, with the
+    /// commit a tag peels to on the line after it.
     pub fn write_packed_refs(&self, repo_root: &Path) -> io::Result<()> {
-        let mut content = String::from("# pack-refs with: peeled fully-peeled sorted\n");
-
+        let mut refs: Vec<(String, &str, Option<&str>)> = Vec::new();
         for r in &self.refs {
-            content.push_str(&format!("{} {}\n", r.hash, r.name));
+            if let Some(branch) = r.name.strip_prefix("refs/heads/") {
+                refs.push((format!("refs/remotes/{}/{}", REMOTE_NAME, branch), &r.hash, None));
+            } else if r.name.starts_with("refs/tags/") && !r.name.ends_with(PEELED_SUFFIX) {
+                let peeled = self.find(&format!("{}{}", r.name, PEELED_SUFFIX)).map(|p| p.hash.as_str());
+                refs.push((r.name.clone(), &r.hash, peeled));
+            }
+        }
+        refs.sort();
+
+        let mut content = String::from("# pack-refs with: peeled fully-peeled sorted\n");
+        for (name, hash, peeled) in refs {
+            content.push_str(&format!("{} {}\n", hash, name));
+            if let Some(peeled) = peeled {
+                content.push_str(&format!("^{}\n", peeled));
+            }
         }
 
         let path = repo_root.join(".git/packed-refs");
         fs::write(path, content)
     }
 
+    pub fn find(&self, name: &str) -> Option<&AdvertisedRef> {
+        self.refs.iter().find(|r| r.name == name)
+    }
+
+    /// The branch the remote's HEAD points at: its `symref` capability, or
+    /// else a branch at the same commit, preferring `main` and `master`.
+    pub fn head_branch(&self) -> Option<&str> {
+        if let Some((_, to)) = self.capabilities.symref.as_ref().filter(|(from, _)| from == "HEAD") {
+            return Some(to);
+        }
+        let head = self.head.as_deref()?;
+        let candidates = || self.refs.iter().filter(|r| r.hash == head && r.name.starts_with("refs/heads/"));
+        candidates()
+            .find(|r| r.name == "refs/heads/main" || r.name == "refs/heads/master")
+            .or_else(|| candidates().next())
+            .map(|r| r.name.as_str())
+    }
+
+    /// The objects a clone asks for: the tips of all branches and tags.
+    pub fn wanted_hashes(&self) -> Vec<&str> {
+        let mut wants: Vec<&str> = self
+            .refs
+            .iter()
+            .filter(|r| (r.name.starts_with("refs/heads/") || r.name.starts_with("refs/tags/")) && !r.name.ends_with(PEELED_SUFFIX))
+            .map(|r| r.hash.as_str())
+            .collect();
+        wants.sort();
+        wants.dedup();
+        wants
+    }
+
     pub fn print_debug(&self) {
         println!("--- RefAdvertisement ---");
 
@@ -13,18 +13,17 @@ pub fn fetch_refs(url: &str) -> Result<Vec<u8>, std::io::Error> {
         .get(&service_url)
         .header(USER_AGENT, GIT_AGENT)
         .send()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(std::io::Error::other)?;
 
     if !response.status().is_success() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        return Err(std::io::Error::other(
             format!("Request failed with status {}", response.status()),
         ));
     }
 
     let bytes = response
         .bytes()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        .map_err(std::io::Error::other)?;
 
     Ok(bytes.to_vec())
 }
@@ -34,11 +33,15 @@ pub struct HttpNegotiator;
 impl UploadPackNegotiator for HttpNegotiator {
     fn negogiate(&self, base_url: &str, ref_adv: &RefAdvertisement) -> std::io::Result<Response> {
         let client = Client::new();
-        let url = clean_url(&base_url);
-        let head_hash = ref_adv.head.as_ref().ok_or_else(|| {io::Error::new(io::ErrorKind::InvalidData, "No HEAD advertised in refs")})?;
+        let url = clean_url(base_url);
+        let wants = ref_adv.wanted_hashes();
+        if wants.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "No refs advertised to fetch"));
+        }
 
-        let body = UploadPackV2RequestBuilder::new()
-            .want(&head_hash)
+        let body = wants
+            .into_iter()
+            .fold(UploadPackV2RequestBuilder::new(), |builder, hash| builder.want(hash))
             .agent(GIT_AGENT)
             .fetch_option("thin-pack")
             .fetch_option("ofs-delta")
@@ -53,7 +56,7 @@ impl UploadPackNegotiator for HttpNegotiator {
             .header("git-protocol", "version=2")
             .body(body.clone())
             .send()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("request failed: {}", e)))?;
+            .map_err(|e| io::Error::other(format!("request failed: {}", e)))?;
 
         Ok(res) 
     }
@@ -61,11 +64,11 @@ impl UploadPackNegotiator for HttpNegotiator {
 
 fn clean_url(url: &str) -> String {
 
-    return if url.ends_with(GIT_UPLOAD_PACK) {
+    if url.ends_with(GIT_UPLOAD_PACK) {
         url.to_string()
     } 
     else {
-        format!("{}/{}", url.trim_end_matches('/'), GIT_UPLOAD_PACK).to_string()};
+        format!("{}/{}", url.trim_end_matches('/'), GIT_UPLOAD_PACK).to_string()}
     }
-const GIT_UPLOAD_PACK: &'static str = "git-upload-pack";
-pub const GIT_AGENT: &'static str = "git/2.42.0";
\ No newline at end of file
+const GIT_UPLOAD_PACK: &str = "git-upload-pack";
+pub const GIT_AGENT: &str = "git/2.42.0";
\ No newline at end of file
@@ -1,93 +1,269 @@
-use std::{fs, io, path::{Path, PathBuf}};
+use std::{collections::HashSet, fmt, io, path::Path};
 
-use crate::{constants::{GIT_DIR, INDEX_PATH}, index::{index::{read_index, write_index}, index_entry::IndexEntry}, objects::{write_object_from_path, FileMode}};
+use crate::{commands::parse_error::CommandParseError, config::git_config::GitConfig, constants::INDEX_PATH, ignore::matcher::IgnoreMatcher, index::{index::{load_index, Index, IndexLock}, index_entry::IndexEntry}, objects::{hash_object, write_object_from_path, FileMode, ObjectType}, pathspec::PathSpec, utils::parallel::{parallel_map, worker_count}, worktree::{file_state::{check_entry, FileState}, walker::WorktreeWalker}};
 
 
 pub fn run(args: &[String]) -> io::Result<()> {
+    add(&parse_command(args)?)
+}
+
+/// Stages what `cmd` asks for and writes the index.
+pub fn add(cmd: &AddCommand) -> io::Result<()> {
     let index_path = Path::new(INDEX_PATH);
-    let mut entries = if index_path.exists() {
-        match read_index(index_path) {
-            Ok(data) => data,
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof || e.kind() == io::ErrorKind::InvalidData => Vec::new(),
-            Err(e) => return Err(e),
+    // held from the read to the write, so no other update is lost
+    let lock = IndexLock::acquire(index_path)?;
+    let mut entries = load_index(index_path)?;
+    let config = GitConfig::load()?;
+
+    let (changes, ignored, unmatched) = collect_changes(cmd, &entries, &config)?;
+    if let Some(pathspec) = unmatched {
+        // exiting skips destructors, so the lock is released first
+        drop(lock);
+        // like git, a pathspec matching nothing is fatal
+        eprintln!("fatal: pathspec '{}' did not match any files", pathspec);
+        std::process::exit(128);
+    }
+
+    if cmd.dry_run || cmd.verbose {
+        for change in &changes {
+            println!("{}", change);
         }
-    } else {
-        Vec::new()
-    };
-
-    let target_paths = if args.is_empty() || (args.len() == 1 && args[0] == ".") {
-        collect_all_files(".")?
-    } else {
-        args.iter().map(PathBuf::from).collect()
-    };
-
-    for path in target_paths {
-        if path.starts_with(GIT_DIR) || !path.is_file() {
-            continue;
+    }
+
+    if cmd.dry_run {
+        return report_ignored(&ignored);
+    }
+
+    // blobs are hashed and compressed on the worker pool; the index itself is
+    // only updated from this thread, in one merge
+    let workers = worker_count(&config, changes.len());
+    let new_entries = parallel_map(&changes, workers, |change| match change {
+        Change::Add(path) => build_entry(path, cmd.intent_to_add).map(Some),
+        Change::Remove(_) => Ok(None),
+    });
+
+    let mut added = Vec::new();
+    let mut removed = HashSet::new();
+    for (change, entry) in changes.iter().zip(new_entries) {
+        match entry? {
+            Some(entry) => added.push(entry),
+            None => {
+                removed.insert(change.path());
+            }
         }
+    }
+    entries.retain(|e| !removed.contains(e.path.as_str()));
+    // replaces the existing entries, collapsing any conflict stages
+    entries.add_all(added);
 
-        let hash = write_object_from_path(crate::objects::ObjectType::Blob, &path)?; 
+    lock.commit(&entries)?;
+    report_ignored(&ignored)
+}
 
-        let mode = FileMode::from_path(&path)?;
-        let cwd = std::env::current_dir()?;
-        let rel_path = normalize_git_path(&path, &cwd).unwrap_or_else(|err| panic!("normalize git path failed: {}", err));
+fn build_entry(path: &str, intent_to_add: bool) -> io::Result<IndexEntry> {
+    let mode = FileMode::from_path(Path::new(path))?;
+    if intent_to_add {
+        let (hash, _) = hash_object(ObjectType::Blob, b"");
+        return Ok(IndexEntry {
+            intent_to_add: true,
+            ..IndexEntry::new(mode, path.to_string(), hash)
+        });
+    }
 
-        let entry = IndexEntry {
-            mode,
-            path: rel_path,
-            hash,
-        };
+    let hash = write_object_from_path(ObjectType::Blob, Path::new(path))?;
+    Ok(IndexEntry::new(mode, path.to_string(), hash))
+}
 
-        // Replace existing entry or insert new one
-        if let Some(existing) = entries.iter_mut().find(|e| e.path == entry.path) {
-            *existing = entry;
-        } else {
-            entries.push(entry);
+fn report_ignored(ignored: &[String]) -> io::Result<()> {
+    if ignored.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("The following paths are ignored by one of your .gitignore files:");
+    for path in ignored {
+        eprintln!("{}", path);
+    }
+    eprintln!("hint: Use -f if you really want to add them.");
+    Err(io::Error::new(io::ErrorKind::InvalidInput, "some paths are ignored"))
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<AddCommand> {
+    let mut mode = AddMode::Default;
+    let mut dry_run = false;
+    let mut verbose = false;
+    let mut intent_to_add = false;
+    let mut force = false;
+    let mut paths = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-u" | "--update" => mode = AddMode::Update,
+            "-A" | "--all" => mode = AddMode::All,
+            "-n" | "--dry-run" => dry_run = true,
+            "-v" | "--verbose" => verbose = true,
+            "-N" | "--intent-to-add" => intent_to_add = true,
+            "-f" | "--force" => force = true,
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => paths.push(other.to_string()),
         }
     }
 
-    write_index(index_path, &entries)
+    if mode == AddMode::Update && intent_to_add {
+        return Err(CommandParseError::InvalidArgument("-u and --intent-to-add are mutually exclusive".to_string()).into());
+    }
+
+    Ok(AddCommand {
+        mode,
+        paths,
+        dry_run,
+        verbose,
+        intent_to_add,
+        force,
+    })
 }
 
-fn collect_all_files(root: &str) -> io::Result<Vec<PathBuf>> {
-    let mut result = Vec::new();
-    for entry in fs::read_dir(root)? {
-        let path = entry?.path();
-        if path.is_dir() {
-            if !path.ends_with(GIT_DIR) {
-                result.extend(collect_all_files(&path.to_string_lossy())?);
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AddMode {
+    /// Stage new, modified and deleted files matching the given paths.
+    Default,
+    /// `-u`: only refresh paths already in the index, including deletions.
+    Update,
+    /// `-A`: like the default mode, but over the whole tree when no paths are given.
+    All,
+}
+
+pub struct AddCommand {
+    mode: AddMode,
+    paths: Vec<String>,
+    dry_run: bool,
+    verbose: bool,
+    intent_to_add: bool,
+    /// `-f`: also add files matched by ignore rules.
+    force: bool,
+}
+
+impl AddCommand {
+    /// `add -u` over the whole tree, as `commit -a` does before committing.
+    pub fn update_tracked() -> Self {
+        AddCommand { mode: AddMode::Update, paths: Vec::new(), dry_run: false, verbose: false, intent_to_add: false, force: false }
+    }
+}
+
+enum Change {
+    Add(String),
+    Remove(String),
+}
+
+impl Change {
+    fn path(&self) -> &str {
+        match self {
+            Change::Add(path) | Change::Remove(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Add(path) => write!(f, "add '{}'", path),
+            Change::Remove(path) => write!(f, "remove '{}'", path),
+        }
+    }
+}
+
+/// Works out which index updates the command implies, without touching the index.
+/// Also returns the explicitly named paths that were skipped because they are
+/// ignored, and the first pathspec that matched nothing, if any.
+fn collect_changes(cmd: &AddCommand, entries: &Index, config: &GitConfig) -> io::Result<(Vec<Change>, Vec<String>, Option<String>)> {
+    // no paths means the whole working tree
+    let pathspec = PathSpec::parse(&cmd.paths)?;
+    let mut matched = vec![false; pathspec.items().len()];
+
+    let mut changes = Vec::new();
+    let mut seen = HashSet::new();
+    let mut ignored = Vec::new();
+    let mut ignored_patterns = Vec::new();
+
+    let ignore = if cmd.force { None } else { Some(IgnoreMatcher::load()?) };
+    let mut walker = WorktreeWalker::new(ignore);
+
+    // untracked files; tracked ones are refreshed from the index below
+    if cmd.mode != AddMode::Update {
+        // literal paths naming ignored, untracked files are reported rather than silently skipped
+        for (i, item) in pathspec.items().iter().enumerate() {
+            if item.magic.exclude || item.is_glob() || item.pattern.is_empty() {
+                continue;
+            }
+            if entries.iter().any(|e| item.matches(&e.path)) {
+                continue;
+            }
+            if let Some(ignore) = walker.ignore_matcher() {
+                let is_dir = Path::new(&item.pattern).is_dir();
+                if ignore.check_path(&item.pattern, is_dir)?.is_some_and(|m| m.is_ignored()) {
+                    matched[i] = true;
+                    ignored.push(item.original.clone());
+                    ignored_patterns.push(item.pattern.clone());
+                }
+            }
+        }
+
+        for base in pathspec.base_dirs() {
+            for path in walker.files_under(&base)? {
+                if ignored_patterns.contains(&path) || entries.contains(&path) {
+                    continue;
+                }
+                if !mark_matched(&pathspec, &path, &mut matched) {
+                    continue;
+                }
+                if seen.insert(path.clone()) {
+                    changes.push(Change::Add(path));
+                }
             }
-        } else {
-            result.push(path);
         }
     }
 
-    Ok(result)
+    let mut tracked = Vec::new();
+    for entry in entries {
+        if !mark_matched(&pathspec, &entry.path, &mut matched) {
+            continue;
+        }
+
+        if !seen.insert(entry.path.clone()) || cmd.intent_to_add {
+            continue;
+        }
+        tracked.push(entry);
+    }
+
+    let states = parallel_map(&tracked, worker_count(config, tracked.len()), |entry| check_entry(entry));
+    for (entry, state) in tracked.into_iter().zip(states) {
+        match state? {
+            FileState::Deleted => changes.push(Change::Remove(entry.path.clone())),
+            FileState::Modified => changes.push(Change::Add(entry.path.clone())),
+            FileState::Unchanged => {}
+        }
+    }
+
+    let unmatched = pathspec.items().iter().zip(&matched).find(|(item, matched)| !**matched && !item.magic.exclude).map(|(item, _)| item.original.clone());
+
+    // untracked and tracked changes are reported together, in path order
+    changes.sort_by(|a, b| a.path().as_bytes().cmp(b.path().as_bytes()));
+    Ok((changes, ignored, unmatched))
 }
 
-pub fn normalize_git_path(full_path: &Path, repo_root: &Path) -> Result<String, String> {
-    // Step 1: Try to canonicalize the input path
-    let abs_path = full_path.canonicalize()
-        .map_err(|e| format!("Failed to canonicalize path '{}': {}", full_path.display(), e))?;
-
-    let repo_root = repo_root.canonicalize()
-        .map_err(|e| format!("Failed to canonicalize repo root '{}': {}", repo_root.display(), e))?;
-
-    // Step 2: Try to strip the repo root from the full path
-    let rel_path = abs_path.strip_prefix(&repo_root)
-        .map_err(|e| format!(
-            "Failed to strip prefix:\n  full path: '{}'\n  repo root: '{}'\n  error: {}",
-            abs_path.display(),
-            repo_root.display(),
-            e
-        ))?;
-
-    // Step 3: Normalize to forward slashes
-    let normalized = rel_path
-        .components()
-        .map(|c| c.as_os_str().to_string_lossy())
-        .collect::<Vec<_>>()
-        .join("/");
-
-    Ok(normalized)
-}
\ No newline at end of file
+/// Records which pathspec items matched `path` and reports whether it is in scope.
+fn mark_matched(pathspec: &PathSpec, path: &str, matched: &mut [bool]) -> bool {
+    match pathspec.matching_items(path) {
+        Some(items) => {
+            for i in items {
+                matched[i] = true;
+            }
+            true
+        }
+        None => false,
+    }
+}
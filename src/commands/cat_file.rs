@@ -4,7 +4,7 @@ use crate::objects::{read_object, ObjectType};
 pub fn run(args: &[String]) -> io::Result<()> {
     if args.len() == 3 && args[1] == "-p" {
         let hash = &args[2];
-        return cat_file_print(hash);
+        cat_file_print(hash)
     } else {
         eprintln!("Usage: git-rust cat-file -p <hash>");
         std::process::exit(1);
@@ -0,0 +1,114 @@
+use std::{io::{self, Read, Write}, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, ignore::matcher::{IgnoreMatch, IgnoreMatcher}, index::index::{load_index, Index}, utils::path_utils::to_repo_path};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let mut paths = cmd.paths.clone();
+    if cmd.stdin {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        let separator = if cmd.nul_terminated { '\0' } else { '\n' };
+        paths.extend(input.split(separator).filter(|p| !p.is_empty()).map(str::to_string));
+    }
+
+    if paths.is_empty() {
+        return Err(CommandParseError::MissingArgument("no path specified".to_string()).into());
+    }
+
+    let tracked = if cmd.no_index { Index::default() } else { load_index(Path::new(INDEX_PATH))? };
+    let mut matcher = IgnoreMatcher::load()?;
+    let mut out = io::stdout().lock();
+    let mut any_ignored = false;
+
+    for path in &paths {
+        let repo_path = to_repo_path(path)?;
+        let found = if tracked.contains(&repo_path) {
+            // tracked files are never considered ignored
+            None
+        } else {
+            matcher.check_path(&repo_path, Path::new(&repo_path).is_dir())?
+        };
+
+        let ignored = found.as_ref().is_some_and(IgnoreMatch::is_ignored);
+        any_ignored |= ignored;
+
+        if cmd.quiet {
+            continue;
+        }
+
+        if cmd.verbose {
+            match &found {
+                Some(m) => write_verbose(&mut out, &cmd, &m.source, &m.line.to_string(), &m.pattern, path)?,
+                None if cmd.non_matching => write_verbose(&mut out, &cmd, "", "", "", path)?,
+                None => {}
+            }
+        } else if ignored {
+            write!(out, "{}{}", path, cmd.terminator())?;
+        }
+    }
+    out.flush()?;
+
+    if !any_ignored {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn write_verbose<W: Write>(out: &mut W, cmd: &CheckIgnoreCommand, source: &str, line: &str, pattern: &str, path: &str) -> io::Result<()> {
+    if cmd.nul_terminated {
+        write!(out, "{}\0{}\0{}\0{}\0", source, line, pattern, path)
+    } else {
+        writeln!(out, "{}:{}:{}\t{}", source, line, pattern, path)
+    }
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<CheckIgnoreCommand> {
+    let mut cmd = CheckIgnoreCommand::default();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => cmd.paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-v" | "--verbose" => cmd.verbose = true,
+            "-n" | "--non-matching" => cmd.non_matching = true,
+            "-q" | "--quiet" => cmd.quiet = true,
+            "-z" => cmd.nul_terminated = true,
+            "--stdin" => cmd.stdin = true,
+            "--no-index" => cmd.no_index = true,
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => cmd.paths.push(other.to_string()),
+        }
+    }
+
+    if cmd.non_matching && !cmd.verbose {
+        return Err(CommandParseError::InvalidArgument("--non-matching is only valid with --verbose".to_string()).into());
+    }
+    if cmd.quiet && cmd.verbose {
+        return Err(CommandParseError::InvalidArgument("cannot have both --quiet and --verbose".to_string()).into());
+    }
+
+    Ok(cmd)
+}
+
+#[derive(Default)]
+pub struct CheckIgnoreCommand {
+    paths: Vec<String>,
+    verbose: bool,
+    non_matching: bool,
+    quiet: bool,
+    nul_terminated: bool,
+    stdin: bool,
+    no_index: bool,
+}
+
+impl CheckIgnoreCommand {
+    fn terminator(&self) -> char {
+        if self.nul_terminated { '\0' } else { '\n' }
+    }
+}
@@ -0,0 +1,306 @@
+use std::{io, path::Path};
+
+use crate::{
+    commands::parse_error::CommandParseError,
+    config::git_config::GitConfig,
+    constants::INDEX_PATH,
+    diff::changes::{diff_tree_to_worktree, drop_unchanged_worktree},
+    hash::GitHash,
+    index::{index::{entries_from_tree, load_index, write_index}, index_entry::IndexEntry},
+    objects::{commit_object::{peel_to_commit, read_commit}, ident::{ident, Role}, peel_to_tree, read_object},
+    pathspec::PathSpec,
+    refs::{head_commit, is_valid_branch_name, read_head, reflog::append_reflog, resolve_ref, resolve_revision, write_head, write_ref, Head},
+    worktree::{checkout::{checkout_tree, write_worktree_entry}, file_state::{check_entry, FileState}},
+};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    match &cmd.paths {
+        Some(paths) => checkout_paths(cmd.target.as_deref(), paths),
+        None => switch(&cmd),
+    }
+}
+
+pub struct CheckoutCommand {
+    /// The branch or commit to switch to, or the tree-ish to take paths from.
+    target: Option<String>,
+    /// `-b`/`-B`: the branch to create at the target, and whether to reset an existing one.
+    new_branch: Option<(String, bool)>,
+    force: bool,
+    quiet: bool,
+    detach: bool,
+    /// Set when checking out paths rather than switching.
+    paths: Option<Vec<String>>,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<CheckoutCommand> {
+    let mut new_branch = None;
+    let mut force = false;
+    let mut quiet = false;
+    let mut detach = false;
+    let mut positional = Vec::new();
+    let mut after_dashes = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                after_dashes = Some(iter.by_ref().cloned().collect::<Vec<_>>());
+            }
+            "-f" | "--force" => force = true,
+            "-q" | "--quiet" => quiet = true,
+            "--detach" => detach = true,
+            "-b" | "-B" => {
+                let name = iter.next().ok_or_else(|| CommandParseError::MissingArgument(format!("switch `{}' requires a value", &arg[1..])))?;
+                new_branch = Some((name.clone(), arg == "-B"));
+            }
+            x if x.starts_with('-') && x.len() > 1 => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => positional.push(other.to_string()),
+        }
+    }
+
+    let (target, paths) = match after_dashes {
+        Some(paths) => {
+            if positional.len() > 1 {
+                return Err(CommandParseError::InvalidArgument("only one reference expected".to_string()).into());
+            }
+            (positional.pop(), (!paths.is_empty()).then_some(paths))
+        }
+        // without `--`, the first argument is a revision if it names one
+        None if positional.is_empty() => (None, None),
+        None if resolve_revision(&positional[0])?.is_some() => {
+            let target = positional.remove(0);
+            (Some(target), (!positional.is_empty()).then_some(positional))
+        }
+        None if new_branch.is_some() || detach => {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("'{}' is not a commit", positional[0])));
+        }
+        None => (None, Some(positional)),
+    };
+
+    if paths.is_some() && (new_branch.is_some() || detach) {
+        return Err(CommandParseError::InvalidArgument("switching branches and checking out paths cannot be combined".to_string()).into());
+    }
+    if detach && new_branch.is_some() {
+        return Err(CommandParseError::InvalidArgument("'--detach' cannot be used with '-b/-B'".to_string()).into());
+    }
+    if let Some((name, _)) = &new_branch {
+        if !is_valid_branch_name(name) {
+            return Err(CommandParseError::InvalidArgument(format!("'{}' is not a valid branch name", name)).into());
+        }
+    }
+
+    Ok(CheckoutCommand { target, new_branch, force, quiet, detach, paths })
+}
+
+/// Moves HEAD, the index and the working tree to a branch or commit.
+fn switch(cmd: &CheckoutCommand) -> io::Result<()> {
+    let old_head = read_head()?;
+    let old_commit = head_commit()?;
+
+    let (commit, branch) = match &cmd.target {
+        None if cmd.new_branch.is_none() && !cmd.detach => {
+            // like git, a bare `checkout` only reports the local changes
+            if !cmd.quiet {
+                show_local_changes(old_commit.as_ref())?;
+            }
+            return Ok(());
+        }
+        // on an unborn branch, -b only renames the branch HEAD will start
+        None if old_commit.is_none() && cmd.new_branch.is_some() => return switch_unborn(cmd),
+        None => (old_commit.clone().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "You are on a branch yet to be born"))?, None),
+        Some(name) => {
+            let commit = resolve_revision(name)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("pathspec '{}' did not match any file(s) known to git", name)))?;
+            let branch_ref = format!("refs/heads/{}", name);
+            let is_branch = name != "HEAD" && resolve_ref(&branch_ref)?.is_some();
+            (commit, is_branch.then_some(branch_ref))
+        }
+    };
+    // a tag stands for its commit; fails early if the target isn't a commit
+    let commit = peel_to_commit(&commit)?;
+    read_commit(&commit)?;
+
+    let mut created = Created::No;
+    let new_head = match (&cmd.new_branch, branch) {
+        (Some((name, reset)), _) => {
+            let branch_ref = format!("refs/heads/{}", name);
+            created = match resolve_ref(&branch_ref)? {
+                Some(_) if !reset => {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("a branch named '{}' already exists", name)));
+                }
+                Some(_) => Created::Reset,
+                None => Created::New,
+            };
+            Head::Branch(branch_ref)
+        }
+        (None, Some(branch_ref)) if !cmd.detach => Head::Branch(branch_ref),
+        _ => Head::Detached(commit.clone()),
+    };
+
+    let old_tree = old_commit.as_ref().map(|c| read_commit(c).map(|c| c.tree)).transpose()?;
+    checkout_tree(old_tree.as_ref(), &read_commit(&commit)?.tree, cmd.force)?;
+
+    let old_branch_commit = match &new_head {
+        Head::Branch(branch_ref) if created != Created::No => resolve_ref(branch_ref)?,
+        _ => None,
+    };
+    if let (Some(_), Head::Branch(branch_ref)) = (&cmd.new_branch, &new_head) {
+        write_ref(branch_ref, &commit)?;
+    }
+    write_head(&new_head)?;
+    log_switch(cmd, &old_head, old_commit.as_ref(), &new_head, old_branch_commit.as_ref(), &commit, created)?;
+
+    if cmd.quiet {
+        return Ok(());
+    }
+    if !cmd.force {
+        show_local_changes(Some(&commit))?;
+    }
+    report_switch(&old_head, old_commit.as_ref(), &new_head, &commit, created)
+}
+
+/// `checkout -b <name>` with no commits yet: points HEAD at the new unborn
+/// branch, leaving the index and working tree alone.
+fn switch_unborn(cmd: &CheckoutCommand) -> io::Result<()> {
+    let Some((name, _)) = &cmd.new_branch else { return Ok(()) };
+    write_head(&Head::Branch(format!("refs/heads/{}", name)))?;
+    if !cmd.quiet {
+        eprintln!("Switched to a new branch '{}'", name);
+    }
+    Ok(())
+}
+
+/// Records the switch in HEAD's reflog, which `@{-<n>}` reads back, and a
+/// branch created or reset by `-b`/`-B` in the branch's.
+fn log_switch(cmd: &CheckoutCommand, old_head: &Head, old_commit: Option<&GitHash>, new_head: &Head, old_branch_commit: Option<&GitHash>, commit: &GitHash, created: Created) -> io::Result<()> {
+    // like a missing reflog, a missing identity shouldn't stop the checkout
+    let Ok(who) = ident(&GitConfig::load()?, Role::Committer) else { return Ok(()) };
+    let start = cmd.target.as_deref().unwrap_or("HEAD");
+
+    if let Head::Branch(branch_ref) = new_head {
+        match created {
+            Created::New => append_reflog(branch_ref, None, commit, &who, &format!("branch: Created from {}", start))?,
+            Created::Reset => append_reflog(branch_ref, old_branch_commit, commit, &who, &format!("branch: Reset to {}", start))?,
+            Created::No => {}
+        }
+    }
+
+    let from = match old_head {
+        Head::Branch(_) => old_head.branch_name().unwrap_or_default().to_string(),
+        Head::Detached(hash) => hash.to_hex(),
+    };
+    let to = match (&cmd.new_branch, new_head) {
+        (Some((name, _)), _) => name.as_str(),
+        _ => start,
+    };
+    append_reflog("HEAD", old_commit, commit, &who, &format!("checkout: moving from {} to {}", from, to))
+}
+
+/// Prints the paths that differ between a commit and the working tree, as
+/// `git checkout` does after switching.
+fn show_local_changes(commit: Option<&GitHash>) -> io::Result<()> {
+    let config = GitConfig::load()?;
+    let index = load_index(Path::new(INDEX_PATH))?;
+    let tree = commit.map(|c| read_commit(c).map(|c| c.tree)).transpose()?;
+    let changes = drop_unchanged_worktree(diff_tree_to_worktree(tree.as_ref(), &index, &PathSpec::default(), &config)?)?;
+    for change in changes {
+        println!("{}\t{}", change.status.code(), change.path);
+    }
+    Ok(())
+}
+
+/// Whether `-b`/`-B` made a new branch or reset an existing one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Created {
+    No,
+    New,
+    Reset,
+}
+
+fn report_switch(old_head: &Head, old_commit: Option<&GitHash>, new_head: &Head, commit: &GitHash, created: Created) -> io::Result<()> {
+    if let (Head::Detached(_), Some(old)) = (old_head, old_commit) {
+        if old != commit {
+            eprintln!("Previous HEAD position was {} {}", old.abbrev(7), commit_subject(old)?);
+        }
+    }
+
+    let branch = new_head.branch_name().unwrap_or_default();
+    match (new_head, created) {
+        (Head::Detached(_), _) => eprintln!("HEAD is now at {} {}", commit.abbrev(7), commit_subject(commit)?),
+        (_, Created::New) => eprintln!("Switched to a new branch '{}'", branch),
+        (_, Created::Reset) => eprintln!("Switched to and reset branch '{}'", branch),
+        _ if old_head == new_head => eprintln!("Already on '{}'", branch),
+        _ => eprintln!("Switched to branch '{}'", branch),
+    }
+    Ok(())
+}
+
+/// The first line of a commit's message.
+fn commit_subject(commit: &GitHash) -> io::Result<String> {
+    let (_, content) = read_object(&commit.to_hex())?;
+    let text = String::from_utf8_lossy(&content);
+    let message = text.split_once("\n\n").map_or("", |(_, message)| message);
+    Ok(message.lines().next().unwrap_or_default().to_string())
+}
+
+/// Overwrites the matching files with their version from a tree-ish, which
+/// is also staged, or else from the index.
+fn checkout_paths(tree_ish: Option<&str>, paths: &[String]) -> io::Result<()> {
+    let pathspec = PathSpec::parse(paths)?;
+    let mut index = load_index(Path::new(INDEX_PATH))?;
+    let mut matched = vec![false; pathspec.items().len()];
+
+    let source: Vec<IndexEntry> = match tree_ish {
+        Some(name) => {
+            let commit = resolve_revision(name)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("invalid reference: {}", name)))?;
+            entries_from_tree(&peel_to_tree(&commit.to_hex())?, "")?.into_entries()
+        }
+        None => index.iter().cloned().collect(),
+    };
+
+    let mut selected = Vec::new();
+    for entry in source {
+        let Some(items) = pathspec.matching_items(&entry.path) else { continue };
+        for i in items {
+            matched[i] = true;
+        }
+        selected.push(entry);
+    }
+
+    let unmatched: Vec<&str> = pathspec.items().iter().zip(&matched).filter(|(item, m)| !**m && !item.magic.exclude).map(|(item, _)| item.original.as_str()).collect();
+    for path in &unmatched {
+        eprintln!("error: pathspec '{}' did not match any file(s) known to git", path);
+    }
+    if !unmatched.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "pathspec did not match"));
+    }
+    if tree_ish.is_none() {
+        let mut unmerged: Vec<&str> = selected.iter().filter(|e| e.is_unmerged()).map(|e| e.path.as_str()).collect();
+        unmerged.dedup();
+        for path in &unmerged {
+            eprintln!("error: path '{}' is unmerged", path);
+        }
+        if !unmerged.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unmerged paths"));
+        }
+    }
+
+    for entry in selected {
+        if check_entry(&entry)? != FileState::Unchanged {
+            write_worktree_entry(&entry)?;
+        }
+        if tree_ish.is_some() {
+            // a file replaces whatever was tracked as a directory at its
+            // path or as a file at one of its parents
+            let under: Vec<String> = index.entries_under(&entry.path).iter().map(|e| e.path.clone()).collect();
+            for path in under.iter().map(String::as_str).chain(entry.path.match_indices('/').map(|(pos, _)| &entry.path[..pos])) {
+                index.remove(path);
+            }
+            index.add(entry);
+        }
+    }
+    write_index(Path::new(INDEX_PATH), &index)
+}
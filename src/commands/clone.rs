@@ -1,27 +1,112 @@
-use std::{fs, io::{self}, path::Path};
+use std::{env, fs, io::{self}, path::Path};
 
-use crate::{clone::{args::parse_args, packet_line::{pkt_line_unpacker::unpack_pkt_res, pkt_negotiator::negogiate_want}, refs::parse_ref_advertisement, transport::http::fetch_refs}, commands::init::init_git_dir,  utils::signal::{register_signal_handler, EventName}};
+use crate::{clone::{args::{parse_args, CloneArgs}, packet_line::{pkt_line_unpacker::unpack_pkt_res, pkt_negotiator::negogiate_want}, refs::{parse_ref_advertisement, RefAdvertisement, REMOTE_NAME}, transport::http::fetch_refs}, commands::init::init_git_dir, constants::{GIT_CONFIG_PATH, GIT_DIR}, hash::GitHash, objects::commit_object::read_commit, refs::{write_head, write_ref, Head}, utils::signal::{register_signal_handler, EventName}, worktree::checkout::checkout_tree};
 
 
 pub fn run(args: &[String]) -> io::Result<()> {
-    let clone_args = parse_args(&args)?;
+    let clone_args = parse_args(args)?;
     validate_target_dir_empty_or_missing(&clone_args.target_dir)?;
-    let target_dir = clone_args.target_dir.clone();
-    println!("Running git-clone with args: {},{}", clone_args.url, clone_args.target_dir.display());
+    eprintln!("Cloning into '{}'...", clone_args.target_dir.display());
+    fs::create_dir_all(&clone_args.target_dir)?;
+    // the clone runs from inside the new repository, so cleanup needs an absolute path
+    let target_dir = fs::canonicalize(&clone_args.target_dir)?;
+    let cleanup_dir = target_dir.clone();
     register_signal_handler(EventName::CtrlC, move || {
-        setup_interrupt_cleanup(&target_dir);
+        setup_interrupt_cleanup(&cleanup_dir);
     });
+
+    run_with_cleanup(|| clone_into(&clone_args, &target_dir), &target_dir)
+}
+
+fn clone_into(clone_args: &CloneArgs, target_dir: &Path) -> io::Result<()> {
     let refs_bytes = fetch_refs(&clone_args.url)?;
     let refs = parse_ref_advertisement(&refs_bytes)?;
+    init_git_dir(target_dir)?;
+    env::set_current_dir(target_dir)?;
+
+    if refs.wanted_hashes().is_empty() {
+        if let Some(branch) = &clone_args.branch {
+            return Err(remote_branch_not_found(branch));
+        }
+        write_config(&clone_args.url, None)?;
+        eprintln!("warning: You appear to have cloned an empty repository.");
+        return Ok(());
+    }
+
+    let checkout = checkout_target(&refs, clone_args.branch.as_deref())?;
     let res = negogiate_want(&refs, &clone_args.url)?;
-    if !clone_args.target_dir.exists() {
-        fs::create_dir_all(&clone_args.target_dir)?;
+    unpack_pkt_res(res)?;
+    refs.write_packed_refs(target_dir)?;
+
+    let branch = match &checkout {
+        CheckoutTarget::Branch(name, _) => Some(name.as_str()),
+        CheckoutTarget::Tag(_) => None,
+    };
+    write_config(&clone_args.url, branch)?;
+    if let Some(head_branch) = refs.head_branch().and_then(|b| b.strip_prefix("refs/heads/")) {
+        let remote_head = Path::new(GIT_DIR).join(format!("refs/remotes/{}/HEAD", REMOTE_NAME));
+        fs::create_dir_all(remote_head.parent().unwrap_or(Path::new(GIT_DIR)))?;
+        fs::write(remote_head, format!("ref: refs/remotes/{}/{}\n", REMOTE_NAME, head_branch))?;
     }
-    init_git_dir(&clone_args.target_dir)?;
-    run_with_cleanup(|| refs.write_packed_refs(&clone_args.target_dir), &clone_args.target_dir)?;
-    run_with_cleanup(|| unpack_pkt_res(res, &clone_args.target_dir), &clone_args.target_dir)?;
 
-    Ok(())
+    let commit = match checkout {
+        CheckoutTarget::Branch(name, commit) => {
+            let branch_ref = format!("refs/heads/{}", name);
+            write_ref(&branch_ref, &commit)?;
+            write_head(&Head::Branch(branch_ref))?;
+            commit
+        }
+        CheckoutTarget::Tag(commit) => {
+            write_head(&Head::Detached(commit.clone()))?;
+            commit
+        }
+    };
+
+    if clone_args.no_checkout {
+        return Ok(());
+    }
+    checkout_tree(None, &read_commit(&commit)?.tree, false)
+}
+
+/// What a clone leaves HEAD on: a local branch made from a remote branch, or
+/// the commit of a tag given to `--branch`.
+enum CheckoutTarget {
+    Branch(String, GitHash),
+    Tag(GitHash),
+}
+
+fn checkout_target(refs: &RefAdvertisement, branch: Option<&str>) -> io::Result<CheckoutTarget> {
+    let Some(name) = branch else {
+        let head = refs.head_branch().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "remote HEAD refers to nonexistent ref, unable to checkout"))?;
+        let r = refs.find(head).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "remote HEAD refers to nonexistent ref, unable to checkout"))?;
+        let name = head.strip_prefix("refs/heads/").unwrap_or(head);
+        return Ok(CheckoutTarget::Branch(name.to_string(), GitHash::from_hex(&r.hash)?));
+    };
+
+    if let Some(r) = refs.find(&format!("refs/heads/{}", name)) {
+        return Ok(CheckoutTarget::Branch(name.to_string(), GitHash::from_hex(&r.hash)?));
+    }
+    // an annotated tag is followed by the commit it points at
+    let tag = format!("refs/tags/{}", name);
+    match refs.find(&format!("{}^{{}}", tag)).or_else(|| refs.find(&tag)) {
+        Some(r) => Ok(CheckoutTarget::Tag(GitHash::from_hex(&r.hash)?)),
+        None => Err(remote_branch_not_found(name)),
+    }
+}
+
+fn remote_branch_not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("Remote branch {} not found in upstream {}", name, REMOTE_NAME))
+}
+
+/// Writes the new repository's config: the remote it came from and the
+/// upstream of the checked out branch.
+fn write_config(url: &str, branch: Option<&str>) -> io::Result<()> {
+    let mut config = String::from("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = false\n\tlogallrefupdates = true\n");
+    config.push_str(&format!("[remote \"{0}\"]\n\turl = {1}\n\tfetch = +refs/heads/*:refs/remotes/{0}/*\n", REMOTE_NAME, url));
+    if let Some(branch) = branch {
+        config.push_str(&format!("[branch \"{}\"]\n\tremote = {}\n\tmerge = refs/heads/{}\n", branch, REMOTE_NAME, branch));
+    }
+    fs::write(GIT_CONFIG_PATH, config)
 }
 
 fn setup_interrupt_cleanup(dir: &Path) {
@@ -0,0 +1,385 @@
+use std::{fs, io::{self, Read, Write}, path::Path};
+
+use crate::{
+    commands::{add::{add, AddCommand}, parse_error::CommandParseError, status::write_long, write_tree::write_index_tree},
+    config::git_config::GitConfig,
+    constants::{COMMIT_EDITMSG_PATH, INDEX_PATH},
+    diff::{changes::{diff_trees, tree_files}, output::{write_changes, DiffOptions}, rename::{detect_renames, Detect, RenameOptions}},
+    hash::GitHash,
+    index::index::load_index,
+    objects::{
+        commit_object::{process_commit, read_commit, Commit, ParsedCommit},
+        hash_object,
+        ident::{date_overridden, format_date, ident, ident_date, parse_date, parse_name_email, Role},
+        ObjectType, Person,
+    },
+    pathspec::PathSpec,
+    refs::{head_commit, read_head, reflog::update_head},
+    utils::editor::launch_editor,
+    worktree::status::{collect_status, UntrackedMode},
+};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    commit(&cmd)
+}
+
+#[derive(Default)]
+pub struct CommitCommand {
+    /// `-m`: each one is a paragraph of the message.
+    messages: Vec<String>,
+    /// `-F`: read the message from a file, or stdin for `-`.
+    file: Option<String>,
+    /// `-a`: stage modified and deleted tracked files first.
+    all: bool,
+    /// `--amend`: replace the commit at HEAD instead of adding a child.
+    amend: bool,
+    allow_empty: bool,
+    /// `--author`: `Name <email>` to record instead of the configured author.
+    author: Option<String>,
+    /// `--date`: the author date to record.
+    date: Option<String>,
+    /// `-e`/`--no-edit`: whether to open the editor, when not the default.
+    edit: Option<bool>,
+    /// `--cleanup`: how to tidy the message, overriding `commit.cleanup`.
+    cleanup: Option<String>,
+    /// `-t`: the initial message, overriding `commit.template`.
+    template: Option<String>,
+    quiet: bool,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<CommitCommand> {
+    let mut cmd = CommitCommand::default();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next().cloned().ok_or_else(|| CommandParseError::MissingArgument(format!("option `{}' requires a value", name)))
+        };
+        match arg.as_str() {
+            "-m" | "--message" => cmd.messages.push(value("message")?),
+            "-F" | "--file" => cmd.file = Some(value("file")?),
+            "--author" => cmd.author = Some(value("author")?),
+            "--date" => cmd.date = Some(value("date")?),
+            "-t" | "--template" => cmd.template = Some(value("template")?),
+            "-e" | "--edit" => cmd.edit = Some(true),
+            "--no-edit" => cmd.edit = Some(false),
+            "-a" | "--all" => cmd.all = true,
+            "--amend" => cmd.amend = true,
+            "--allow-empty" => cmd.allow_empty = true,
+            "-q" | "--quiet" => cmd.quiet = true,
+            x if x.starts_with("--message=") => cmd.messages.push(x["--message=".len()..].to_string()),
+            x if x.starts_with("-m") => cmd.messages.push(x[2..].to_string()),
+            x if x.starts_with("--file=") => cmd.file = Some(x["--file=".len()..].to_string()),
+            x if x.starts_with("-F") => cmd.file = Some(x[2..].to_string()),
+            x if x.starts_with("--author=") => cmd.author = Some(x["--author=".len()..].to_string()),
+            x if x.starts_with("--date=") => cmd.date = Some(x["--date=".len()..].to_string()),
+            x if x.starts_with("--cleanup=") => cmd.cleanup = Some(x["--cleanup=".len()..].to_string()),
+            x if x.starts_with("--template=") => cmd.template = Some(x["--template=".len()..].to_string()),
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => {
+                return Err(CommandParseError::InvalidArgument(format!("committing only some paths is not supported: '{}'", other)).into());
+            }
+        }
+    }
+
+    if !cmd.messages.is_empty() && cmd.file.is_some() {
+        return Err(CommandParseError::InvalidArgument("options '-m' and '-F' cannot be used together".to_string()).into());
+    }
+    Ok(cmd)
+}
+
+/// Records the index as a new commit on top of HEAD, or in place of it with
+/// `--amend`, and moves the current branch (or a detached HEAD) to it.
+fn commit(cmd: &CommitCommand) -> io::Result<()> {
+    let config = GitConfig::load()?;
+    if cmd.all {
+        add(&AddCommand::update_tracked())?;
+    }
+
+    let index = load_index(Path::new(INDEX_PATH))?;
+    if index.has_unmerged() {
+        eprintln!("error: Committing is not possible because you have unmerged files.");
+        eprintln!("hint: Fix them up in the work tree, and then use 'git add/rm <file>'");
+        eprintln!("hint: as appropriate to mark resolution and make a commit.");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Exiting because of an unresolved conflict."));
+    }
+
+    let head = head_commit()?;
+    let amended = match (cmd.amend, &head) {
+        (true, None) => return Err(io::Error::new(io::ErrorKind::NotFound, "You have nothing to amend.")),
+        (true, Some(hash)) => Some(read_commit(hash)?),
+        (false, _) => None,
+    };
+    let parents = match &amended {
+        Some(old) => old.parents.clone(),
+        None => head.into_iter().collect(),
+    };
+    let message = read_message(cmd, amended.as_ref())?;
+    let template = match (&message, &cmd.template) {
+        (None, Some(path)) => Some(read_template(Path::new(path))?),
+        (None, None) => config.get_path("commit.template").map(|path| read_template(&path)).transpose()?,
+        (Some(_), _) => None,
+    };
+    let use_editor = cmd.edit.unwrap_or(cmd.messages.is_empty() && cmd.file.is_none());
+    let cleanup = Cleanup::resolve(cmd.cleanup.as_deref().or(config.get("commit.cleanup")), use_editor)?;
+
+    let tree = write_index_tree(&index)?;
+    let parent_tree = parents.first().map(|p| read_commit(p).map(|c| c.tree)).transpose()?;
+    let (empty_tree, _) = hash_object(ObjectType::Tree, b"");
+    if *parent_tree.as_ref().unwrap_or(&empty_tree) == tree && !cmd.allow_empty {
+        if cmd.amend {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "You asked to amend the most recent commit, but doing so would make\nit empty. You can repeat your command with --allow-empty, or you can\nremove the commit entirely with \"git reset HEAD^\".",
+            ));
+        }
+        // like git, the status explains why there is nothing to commit
+        let status = collect_status(&PathSpec::default(), UntrackedMode::Normal, &config)?;
+        let mut out = io::stdout().lock();
+        write_long(&mut out, &status, false)?;
+        out.flush()?;
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to commit"));
+    }
+
+    let committer = ident(&config, Role::Committer)?;
+    let author = commit_author(cmd, &config, amended.as_ref())?;
+    let date_shown = cmd.amend || cmd.date.is_some() || date_overridden(Role::Author);
+
+    // the message goes through COMMIT_EDITMSG whether or not it is edited
+    let mut content = message.as_deref().or(template.as_deref()).map_or_else(String::new, |m| cleanup.prepare(m));
+    if use_editor {
+        content.push('\n');
+        write_help(&mut content, cleanup, &author, &committer, date_shown, &config)?;
+    }
+    fs::write(COMMIT_EDITMSG_PATH, &content)?;
+    if use_editor {
+        launch_editor(Path::new(COMMIT_EDITMSG_PATH), &config)?;
+        content = fs::read_to_string(COMMIT_EDITMSG_PATH)?;
+    }
+
+    let message = cleanup.apply(&content);
+    if cleanup != Cleanup::Verbatim && template.is_some_and(|t| !cleanup.is_empty(&t) && cleanup.apply(&t) == message) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Aborting commit; you did not edit the message."));
+    }
+    if cleanup.is_empty(&message) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Aborting commit due to empty commit message."));
+    }
+    let first_line = message.lines().next().unwrap_or_default().to_string();
+    let is_root = parents.is_empty();
+    let hash = process_commit(&Commit { tree: tree.clone(), parents, message, committer: committer.clone(), author: author.clone() })?;
+
+    let kind = match (cmd.amend, is_root) {
+        (true, _) => " (amend)",
+        (false, true) => " (initial)",
+        (false, false) => "",
+    };
+    update_head(&hash, &committer, &format!("commit{}: {}", kind, first_line))?;
+
+    if cmd.quiet {
+        return Ok(());
+    }
+    print_summary(&config, &hash, is_root, &author, &committer, date_shown, parent_tree.as_ref(), &tree)
+}
+
+/// The message from `-m`, `-F` or, when amending, the old commit. `None`
+/// leaves it to the template and the editor.
+fn read_message(cmd: &CommitCommand, amended: Option<&ParsedCommit>) -> io::Result<Option<String>> {
+    if !cmd.messages.is_empty() {
+        return Ok(Some(cmd.messages.join("\n\n")));
+    }
+    match (&cmd.file, amended) {
+        (Some(file), _) if file == "-" => {
+            let mut message = String::new();
+            io::stdin().read_to_string(&mut message)?;
+            Ok(Some(message))
+        }
+        (Some(file), _) => fs::read_to_string(file)
+            .map(Some)
+            .map_err(|e| io::Error::new(e.kind(), format!("could not read log file '{}': {}", file, e))),
+        (None, Some(old)) => Ok(Some(old.message.clone())),
+        (None, None) => Ok(None),
+    }
+}
+
+fn read_template(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("could not read '{}': {}", path.display(), e)))
+}
+
+const CUT_LINE: &str = "# ------------------------ >8 ------------------------";
+
+/// How the message is tidied before it is recorded, from `--cleanup` or
+/// `commit.cleanup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cleanup {
+    /// Whitespace, plus lines starting with `#` are dropped.
+    Strip,
+    /// Trailing whitespace and surplus blank lines are dropped.
+    Whitespace,
+    /// The message is left exactly as given.
+    Verbatim,
+    /// Like `Whitespace`, after dropping everything below the cut line.
+    Scissors,
+}
+
+impl Cleanup {
+    /// Picks the mode like git: `default` strips comments only from an
+    /// edited message, and `scissors` needs the editor to mean anything.
+    fn resolve(value: Option<&str>, use_editor: bool) -> io::Result<Self> {
+        let edited = |mode| if use_editor { mode } else { Cleanup::Whitespace };
+        match value.unwrap_or("default") {
+            "default" => Ok(edited(Cleanup::Strip)),
+            "strip" => Ok(Cleanup::Strip),
+            "whitespace" => Ok(Cleanup::Whitespace),
+            "verbatim" => Ok(Cleanup::Verbatim),
+            "scissors" => Ok(edited(Cleanup::Scissors)),
+            other => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid cleanup mode {}", other))),
+        }
+    }
+
+    /// The message as it is put in front of the user.
+    fn prepare(self, message: &str) -> String {
+        match self {
+            Cleanup::Verbatim if !message.is_empty() && !message.ends_with('\n') => format!("{}\n", message),
+            Cleanup::Verbatim => message.to_string(),
+            _ => strip_space(message, false),
+        }
+    }
+
+    fn apply(self, message: &str) -> String {
+        match self {
+            Cleanup::Strip => strip_space(message, true),
+            Cleanup::Whitespace => strip_space(message, false),
+            Cleanup::Verbatim => message.to_string(),
+            Cleanup::Scissors => {
+                let end = message.lines().take_while(|l| *l != CUT_LINE).map(|l| l.len() + 1).sum::<usize>();
+                strip_space(&message[..end.min(message.len())], false)
+            }
+        }
+    }
+
+    /// Whether the message has nothing but blank lines and, when comments
+    /// are stripped, comments.
+    fn is_empty(self, message: &str) -> bool {
+        match self {
+            Cleanup::Verbatim => message.is_empty(),
+            Cleanup::Strip => message.lines().all(|l| l.trim().is_empty() || l.starts_with('#')),
+            Cleanup::Whitespace | Cleanup::Scissors => message.trim().is_empty(),
+        }
+    }
+}
+
+/// Strips trailing whitespace from every line, collapses runs of blank lines
+/// and drops leading and trailing ones, like git's `stripspace`. With
+/// `strip_comments`, lines starting with `#` go too. A message left with any
+/// text ends in a newline.
+fn strip_space(message: &str, strip_comments: bool) -> String {
+    let mut result = String::new();
+    let mut blank = false;
+    for line in message.lines().map(str::trim_end) {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            blank = !result.is_empty();
+            continue;
+        }
+        if blank {
+            result.push('\n');
+            blank = false;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
+
+/// Appends the commented help git puts below the message being edited: how
+/// it will be cleaned up, the author and date when they are worth pointing
+/// out, and the status of what is being committed.
+fn write_help(content: &mut String, cleanup: Cleanup, author: &Person, committer: &Person, date_shown: bool, config: &GitConfig) -> io::Result<()> {
+    let help = match cleanup {
+        Cleanup::Scissors => format!("{}\n# Do not modify or remove the line above.\n# Everything below it will be ignored.\n", CUT_LINE),
+        Cleanup::Strip => "# Please enter the commit message for your changes. Lines starting\n# with '#' will be ignored, and an empty message aborts the commit.\n".to_string(),
+        _ => "# Please enter the commit message for your changes. Lines starting\n# with '#' will be kept; you may remove them yourself if you want to.\n# An empty message aborts the commit.\n".to_string(),
+    };
+    content.push_str(&help);
+    content.push_str("#\n");
+
+    let mut ident_shown = false;
+    if author.name != committer.name || author.email != committer.email {
+        content.push_str(&format!("# Author:    {} <{}>\n", author.name, author.email));
+        ident_shown = true;
+    }
+    if date_shown {
+        content.push_str(&format!("# Date:      {}\n", format_date(author.timestamp, &author.timezone)));
+        ident_shown = true;
+    }
+    if ident_shown {
+        content.push_str("#\n");
+    }
+
+    let status = collect_status(&PathSpec::default(), UntrackedMode::Normal, config)?;
+    let mut out = Vec::new();
+    write_long(&mut out, &status, true)?;
+    content.push_str(&String::from_utf8_lossy(&out));
+    Ok(())
+}
+
+/// The author: the configured identity or, when amending, the old commit's,
+/// with the name and email of `--author` and the date of `--date` on top.
+fn commit_author(cmd: &CommitCommand, config: &GitConfig, amended: Option<&ParsedCommit>) -> io::Result<Person> {
+    let mut author = match (amended, &cmd.author) {
+        (Some(old), _) => old.author.clone(),
+        // the configured identity may be missing when --author gives one
+        (None, Some(_)) => {
+            let (timestamp, timezone) = ident_date(Role::Author)?;
+            Person { name: String::new(), email: String::new(), timestamp, timezone }
+        }
+        (None, None) => ident(config, Role::Author)?,
+    };
+    if let Some(value) = &cmd.author {
+        let (name, email) = parse_name_email(value)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("--author '{}' is not 'Name <email>' and matches no existing author", value)))?;
+        author.name = name;
+        author.email = email;
+    }
+    if let Some(date) = &cmd.date {
+        (author.timestamp, author.timezone) = parse_date(date)?;
+    }
+    Ok(author)
+}
+
+/// Prints git's report of a new commit: `[branch abc1234] subject`, the
+/// author and date when they are worth pointing out, then the diffstat
+/// totals and the created, deleted and renamed files.
+#[allow(clippy::too_many_arguments)]
+fn print_summary(config: &GitConfig, hash: &GitHash, is_root: bool, author: &Person, committer: &Person, date_shown: bool, parent_tree: Option<&GitHash>, tree: &GitHash) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let head = read_head()?;
+    let branch = head.branch_name().unwrap_or("detached HEAD");
+    let root = if is_root { " (root-commit)" } else { "" };
+    writeln!(out, "[{}{} {}] {}", branch, root, hash.abbrev(7), read_commit(hash)?.subject())?;
+
+    if author.name != committer.name || author.email != committer.email {
+        writeln!(out, " Author: {} <{}>", author.name, author.email)?;
+    }
+    if date_shown {
+        writeln!(out, " Date: {}", format_date(author.timestamp, &author.timezone))?;
+    }
+
+    let pathspec = PathSpec::default();
+    let opts = DiffOptions {
+        shortstat: true,
+        summary: true,
+        renames: RenameOptions { detect: Detect::Renames, ..RenameOptions::from_config(config) },
+        ..DiffOptions::default()
+    };
+    let changes = diff_trees(parent_tree, Some(tree), &pathspec, true)?;
+    let changes = detect_renames(changes, &opts.renames, || tree_files(parent_tree, &pathspec))?;
+    write_changes(&mut out, &changes, &opts)?;
+    out.flush()
+}
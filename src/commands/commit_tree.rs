@@ -1,5 +1,5 @@
 use std::{io, iter::Peekable, slice::Iter};
-use crate::{hash::GitHash, objects::{commit_object::{process_commit, Commit}, Person}};
+use crate::{config::git_config::GitConfig, hash::GitHash, objects::{commit_object::{process_commit, Commit}, ident::{ident, Role}}};
 
 pub fn run(args: &[String]) -> io::Result<()> {
     let commit = parse_commit(args)?;
@@ -18,13 +18,13 @@ fn parse_commit(args: &[String]) -> io::Result<Commit> {
     let mut iter = args.iter().peekable();
     let tree_hash = parse_hash(&mut iter)?;
     log::debug!("parsed tree hash");
-    let mut parent: Option<GitHash> = None;
+    let mut parents: Vec<GitHash> = Vec::new();
     let mut message: Option<String> = None;
 
     while let Some(arg) = iter.next() {
         match arg.as_str() {
             "-p" => {
-                parent = Some(parse_hash(&mut iter)?);
+                parents.push(parse_hash(&mut iter)?);
             }
             "-m" => {
                 message = Some(parse_commit_message(&mut iter));
@@ -39,21 +39,12 @@ fn parse_commit(args: &[String]) -> io::Result<Commit> {
         return Err(Error::new(ErrorKind::InvalidInput, "missing -m <message>"));
     }
 
-    // TODO switch that when we have auth mechanism
-    let timestamp = chrono::Utc::now().timestamp();
-    let timezone = "+0000".to_string();
-    let author = Person {
-        name: "Dor Ozery".to_string(),
-        email: "dor@example.com".to_string(),
-        timestamp,
-        timezone: timezone.clone(),
-    };
-
+    let config = GitConfig::load()?;
     Ok(Commit {
         tree: tree_hash,
-        parent_tree: parent,
-        author: author.clone(),
-        committer: author,
+        parents,
+        author: ident(&config, Role::Author)?,
+        committer: ident(&config, Role::Committer)?,
         message: message.unwrap(),
     })
 }
@@ -70,7 +61,7 @@ fn parse_hash(iter: &mut Peekable<Iter<String>>) -> io::Result<GitHash> {
 fn parse_commit_message(iter: &mut Peekable<Iter<String>>) -> String {
     let mut msg_parts = Vec::new();
 
-    while let Some(part) = iter.next() {
+    for part in iter.by_ref() {
         if part.starts_with('-') {
             break;
         }
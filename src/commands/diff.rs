@@ -0,0 +1,184 @@
+use std::{fs, io::{self, Write}, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, config::git_config::GitConfig, constants::INDEX_PATH, diff::{changes::{diff_index_to_worktree, diff_tree_to_index, diff_tree_to_worktree, diff_trees, drop_unchanged_worktree, index_files, tree_files, DiffSide, FileChange}, output::{write_change_patch, write_changes, DiffOptions, DEFAULT_ABBREV}, patch::PatchOptions, rename::{detect_renames, RenameOptions}, Whitespace}, hash::GitHash, index::index::load_index, objects::{hash_object, peel_to_tree, FileMode, ObjectType}, pathspec::PathSpec, refs::{head_commit, resolve_revision}, utils::file_utils::read_worktree_blob};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let mut out = io::stdout().lock();
+
+    let changes = if cmd.no_index { no_index_changes(&cmd)? } else { collect_changes(&cmd)? };
+    write_changes(&mut out, &changes, &cmd.opts)?;
+    let found = if cmd.diff_from_contents() {
+        // with differences ignored, only pairs that still show a patch count
+        let mut found = false;
+        for change in &changes {
+            found |= write_change_patch(&mut io::sink(), change, &cmd.opts.patch_opts)?;
+        }
+        found
+    } else {
+        !changes.is_empty()
+    };
+    out.flush()?;
+
+    // like git, --no-index always reports differences through the exit code
+    if found && (cmd.exit_code || cmd.no_index) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+pub struct DiffCommand {
+    opts: DiffOptions,
+    /// `--cached`/`--staged`: compare the index instead of the working tree.
+    cached: bool,
+    no_index: bool,
+    exit_code: bool,
+    /// Tree ids of the revisions given, in order.
+    trees: Vec<GitHash>,
+    paths: Vec<String>,
+}
+
+impl DiffCommand {
+    /// Whether whitespace or blank lines are ignored, so that a changed file
+    /// only counts as a difference if its patch shows something.
+    fn diff_from_contents(&self) -> bool {
+        let lines = &self.opts.patch_opts.lines;
+        lines.whitespace != Whitespace::Exact || lines.ignore_blank_lines
+    }
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<DiffCommand> {
+    let config = GitConfig::load()?;
+    let mut cmd = DiffCommand {
+        // unlike the plumbing commands, diff finds renames by default and reads the diff.* settings
+        opts: DiffOptions {
+            abbrev: Some(DEFAULT_ABBREV),
+            renames: RenameOptions::from_config(&config),
+            patch_opts: PatchOptions::from_config(&config)?,
+            ..DiffOptions::default()
+        },
+        cached: false,
+        no_index: false,
+        exit_code: false,
+        trees: Vec::new(),
+        paths: Vec::new(),
+    };
+    let mut positional = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => cmd.paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "--cached" | "--staged" => cmd.cached = true,
+            "--no-index" => cmd.no_index = true,
+            "--exit-code" => cmd.exit_code = true,
+            "--quiet" => {
+                cmd.opts.no_output = true;
+                cmd.exit_code = true;
+            }
+            x if cmd.opts.parse_option(x)? => {}
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => positional.push(other.to_string()),
+        }
+    }
+    if !cmd.opts.has_format() {
+        cmd.opts.patch = true;
+    }
+
+    if cmd.no_index {
+        cmd.paths.splice(0..0, positional);
+        if cmd.paths.len() != 2 {
+            return Err(CommandParseError::MissingArgument("usage: diff --no-index <path> <path>".to_string()).into());
+        }
+        return Ok(cmd);
+    }
+
+    // leading arguments that name revisions are revisions, the rest are paths
+    let mut rest = positional.into_iter().peekable();
+    while let Some(arg) = rest.peek() {
+        let Some(trees) = resolve_trees(arg)? else { break };
+        cmd.trees.extend(trees);
+        rest.next();
+    }
+    let mut paths: Vec<String> = rest.collect();
+    paths.append(&mut cmd.paths);
+    cmd.paths = paths;
+
+    if cmd.trees.len() > 2 || (cmd.cached && cmd.trees.len() > 1) {
+        return Err(CommandParseError::InvalidArgument("too many revisions".to_string()).into());
+    }
+    Ok(cmd)
+}
+
+/// The trees named by `rev` or by both ends of `a..b`, or `None` if it isn't a revision.
+fn resolve_trees(rev: &str) -> io::Result<Option<Vec<GitHash>>> {
+    let names: Vec<&str> = match rev.split_once("..") {
+        Some((from, to)) => vec![if from.is_empty() { "HEAD" } else { from }, if to.is_empty() { "HEAD" } else { to }],
+        None => vec![rev],
+    };
+
+    let mut trees = Vec::new();
+    for name in names {
+        let Some(hash) = resolve_revision(name)? else { return Ok(None) };
+        trees.push(peel_to_tree(&hash.to_hex())?);
+    }
+    Ok(Some(trees))
+}
+
+fn collect_changes(cmd: &DiffCommand) -> io::Result<Vec<FileChange>> {
+    let pathspec = PathSpec::parse(&cmd.paths)?;
+
+    let renames = &cmd.opts.renames;
+    if let [old, new] = &cmd.trees[..] {
+        let changes = diff_trees(Some(old), Some(new), &pathspec, true)?;
+        return detect_renames(changes, renames, || tree_files(Some(old), &pathspec));
+    }
+
+    let index = load_index(Path::new(INDEX_PATH))?;
+    let tree = match cmd.trees.first() {
+        Some(tree) => Some(tree.clone()),
+        None if cmd.cached => head_commit()?.map(|commit| peel_to_tree(&commit.to_hex())).transpose()?,
+        None => None,
+    };
+
+    let config = GitConfig::load()?;
+    match (cmd.cached, &tree) {
+        (true, _) => detect_renames(diff_tree_to_index(tree.as_ref(), &index, &pathspec)?, renames, || tree_files(tree.as_ref(), &pathspec)),
+        (false, Some(tree)) => {
+            let changes = drop_unchanged_worktree(diff_tree_to_worktree(Some(tree), &index, &pathspec, &config)?)?;
+            detect_renames(changes, renames, || tree_files(Some(tree), &pathspec))
+        }
+        (false, None) => detect_renames(diff_index_to_worktree(&index, &pathspec, &config)?, renames, || Ok(index_files(&index, &pathspec))),
+    }
+}
+
+/// Compares two files outside of any repository: one change from the first
+/// to the second, or none if they are the same.
+fn no_index_changes(cmd: &DiffCommand) -> io::Result<Vec<FileChange>> {
+    let sides = cmd
+        .paths
+        .iter()
+        .map(|path| {
+            let data = match fs::metadata(path) {
+                Ok(meta) if meta.is_dir() => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is a directory; --no-index compares two files", path))),
+                Ok(_) => read_worktree_blob(path)?,
+                Err(_) => return Err(io::Error::new(io::ErrorKind::NotFound, format!("Could not access '{}'", path))),
+            };
+            Ok((FileMode::from_path(Path::new(path))?, hash_object(ObjectType::Blob, &data).0))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let [(old_mode, old_hash), (new_mode, new_hash)] = &sides[..] else { unreachable!("--no-index takes two paths") };
+    if old_mode == new_mode && old_hash == new_hash {
+        return Ok(Vec::new());
+    }
+    // like git's raw output, both sides are working tree files with no id
+    let old = DiffSide { mode: old_mode.clone(), hash: GitHash::zero() };
+    let new = DiffSide { mode: new_mode.clone(), hash: GitHash::zero() };
+    let change = FileChange { old_path: Some(cmd.paths[0].clone()), ..FileChange::new(cmd.paths[1].clone(), Some(old), Some(new)) };
+    Ok(vec![change])
+}
@@ -0,0 +1,62 @@
+use std::{io::{self, Write}, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, config::git_config::GitConfig, constants::INDEX_PATH, diff::{changes::{diff_tree_to_index, diff_tree_to_worktree, tree_files}, output::{write_changes, DiffOptions}, rename::detect_renames}, hash::GitHash, index::index::load_index, objects::peel_to_tree, pathspec::PathSpec, refs::resolve_revision};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let pathspec = PathSpec::parse(&cmd.paths)?;
+    let tree = peel_to_tree(&cmd.tree_ish.to_hex())?;
+    let index = load_index(Path::new(INDEX_PATH))?;
+
+    let changes = if cmd.cached {
+        diff_tree_to_index(Some(&tree), &index, &pathspec)?
+    } else {
+        diff_tree_to_worktree(Some(&tree), &index, &pathspec, &GitConfig::load()?)?
+    };
+    let changes = detect_renames(changes, &cmd.opts.renames, || tree_files(Some(&tree), &pathspec))?;
+
+    let mut out = io::stdout().lock();
+    write_changes(&mut out, &changes, &cmd.opts)?;
+    out.flush()
+}
+
+pub struct DiffIndexCommand {
+    opts: DiffOptions,
+    /// `--cached`: compare with the index only, ignoring the working tree.
+    cached: bool,
+    tree_ish: GitHash,
+    paths: Vec<String>,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<DiffIndexCommand> {
+    let mut opts = DiffOptions::default();
+    let mut cached = false;
+    let mut tree_ish = None;
+    let mut paths = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "--cached" => cached = true,
+            x if opts.parse_option(x)? => {}
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other if tree_ish.is_none() => {
+                let hash = resolve_revision(other)?.ok_or_else(|| CommandParseError::InvalidArgument(format!("not a valid object name '{}'", other)))?;
+                tree_ish = Some(hash);
+            }
+            other => paths.push(other.to_string()),
+        }
+    }
+
+    let tree_ish = tree_ish.ok_or_else(|| CommandParseError::MissingArgument("usage: diff-index [--cached] [<options>] <tree-ish> [<path>...]".to_string()))?;
+    if !opts.has_format() {
+        opts.raw = true;
+    }
+
+    Ok(DiffIndexCommand { opts, cached, tree_ish, paths })
+}
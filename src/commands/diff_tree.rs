@@ -0,0 +1,98 @@
+use std::io::{self, Write};
+
+use crate::{commands::parse_error::CommandParseError, diff::{changes::{diff_trees, tree_files}, output::{write_changes, DiffOptions}, rename::detect_renames}, hash::GitHash, objects::{commit_object::read_commit, peel_to_tree, read_object, ObjectType}, pathspec::PathSpec, refs::resolve_revision};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let pathspec = PathSpec::parse(&cmd.paths)?;
+
+    // a single commit is compared with its first parent
+    let (old, new, commit) = match &cmd.revisions[..] {
+        [old, new] => (Some(peel_to_tree(&old.to_hex())?), Some(peel_to_tree(&new.to_hex())?), None),
+        [commit] => {
+            let (object_type, _) = read_object(&commit.to_hex())?;
+            if object_type != ObjectType::Commit {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a {}, diff-tree needs a commit or two trees", commit.to_hex(), object_type)));
+            }
+            let parsed = read_commit(commit)?;
+            let parent = match parsed.parents.first() {
+                Some(parent) => Some(read_commit(parent)?.tree),
+                None if cmd.root => None,
+                None => return Ok(()),
+            };
+            (parent, Some(parsed.tree), Some(commit.clone()))
+        }
+        _ => unreachable!("parse_command checks the revision count"),
+    };
+
+    let changes = diff_trees(old.as_ref(), new.as_ref(), &pathspec, cmd.recursive)?;
+    let changes = detect_renames(changes, &cmd.opts.renames, || tree_files(old.as_ref(), &pathspec))?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut out = io::stdout().lock();
+    if let (Some(commit), false) = (commit, cmd.no_commit_id) {
+        writeln!(out, "{}", commit.to_hex())?;
+    }
+    write_changes(&mut out, &changes, &cmd.opts)?;
+    out.flush()
+}
+
+pub struct DiffTreeCommand {
+    opts: DiffOptions,
+    recursive: bool,
+    /// `--root`: show a root commit as adding all its files.
+    root: bool,
+    no_commit_id: bool,
+    revisions: Vec<GitHash>,
+    paths: Vec<String>,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<DiffTreeCommand> {
+    let mut cmd = DiffTreeCommand {
+        opts: DiffOptions::default(),
+        recursive: false,
+        root: false,
+        no_commit_id: false,
+        revisions: Vec::new(),
+        paths: Vec::new(),
+    };
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => cmd.paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-r" => cmd.recursive = true,
+            "--root" => cmd.root = true,
+            "--no-commit-id" => cmd.no_commit_id = true,
+            x if cmd.opts.parse_option(x)? => {}
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other if cmd.revisions.len() < 2 && cmd.paths.is_empty() => match resolve_revision(other)? {
+                Some(hash) => cmd.revisions.push(hash),
+                None if cmd.revisions.is_empty() => {
+                    return Err(CommandParseError::InvalidArgument(format!("not a valid object name '{}'", other)).into());
+                }
+                None => cmd.paths.push(other.to_string()),
+            },
+            other => cmd.paths.push(other.to_string()),
+        }
+    }
+
+    if cmd.revisions.is_empty() {
+        return Err(CommandParseError::MissingArgument("usage: diff-tree [<options>] <tree-ish> [<tree-ish>] [<path>...]".to_string()).into());
+    }
+    if !cmd.opts.has_format() {
+        cmd.opts.raw = true;
+    }
+    // like git, anything showing file contents looks at whole trees
+    if cmd.opts.patch || cmd.opts.stat {
+        cmd.recursive = true;
+    }
+
+    Ok(cmd)
+}
@@ -0,0 +1,633 @@
+use std::{collections::{HashMap, HashSet}, io::{self, IsTerminal, Write}, path::Path};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    commands::parse_error::CommandParseError,
+    config::git_config::{parse_bool, GitConfig},
+    diff::{changes::{diff_trees, tree_files}, output::{write_changes, DiffOptions, DEFAULT_ABBREV}, patch::PatchOptions, rename::{detect_renames, RenameOptions}},
+    hash::GitHash,
+    graph::Graph,
+    objects::{commit_object::{peel_to_commit, read_commit, ParsedCommit}, ident::{format_date_as, parse_approxidate, DateMode}, Person},
+    pathspec::PathSpec,
+    refs::{head_commit, list_refs, read_head, shorten_ref, Head},
+    revwalk::{all_tips, parse_revision_arg, RevWalk, Sort, Tip, WalkOptions},
+};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let pathspec = PathSpec::parse(&cmd.paths)?;
+
+    let mut walk = RevWalk::new();
+    let mut opts = cmd.walk.clone();
+    // like git, the graph keeps each line of history together
+    if cmd.graph && opts.sort == Sort::Default {
+        opts.sort = Sort::Topo;
+    }
+    walk.set_options(opts);
+    if !cmd.paths.is_empty() {
+        walk.limit_to_paths(pathspec.clone());
+    }
+    for tip in &cmd.tips {
+        walk.add(tip)?;
+    }
+
+    let mut commits = Vec::new();
+    for found in walk {
+        let (hash, commit) = found?;
+        if !cmd.matches(&commit) {
+            continue;
+        }
+        // the graph draws lines to parents past the last commit shown
+        if !cmd.graph && cmd.max_count.is_some_and(|max| commits.len() >= max) {
+            break;
+        }
+        commits.push((hash, commit));
+    }
+    let shown: HashSet<GitHash> = if cmd.graph { commits.iter().map(|(hash, _)| hash.clone()).collect() } else { HashSet::new() };
+    if let Some(max) = cmd.max_count {
+        commits.truncate(max);
+    }
+    if cmd.reverse {
+        commits.reverse();
+    }
+
+    let wants_decorations = cmd.decorate != Decorate::No
+        || matches!(&cmd.pretty, Pretty::Format { template, .. } if template.contains("%d") || template.contains("%D"));
+    let mut writer = LogWriter {
+        cmd: &cmd,
+        pathspec: &pathspec,
+        graph: cmd.graph.then(Graph::new),
+        decorations: if wants_decorations { Decorations::load()? } else { Decorations::default() },
+        shown_one: false,
+        missing_newline: false,
+    };
+    let mut out = io::stdout().lock();
+    for (hash, commit) in &commits {
+        if let Some(graph) = writer.graph.as_mut() {
+            let parents = if cmd.walk.first_parent { &commit.parents[..commit.parents.len().min(1)] } else { &commit.parents[..] };
+            let parents = parents.iter().filter(|p| shown.contains(p)).cloned().collect();
+            graph.update(hash, parents);
+        }
+        writer.write_commit(&mut out, hash, commit)?;
+    }
+    out.flush()
+}
+
+/// The built-in `--pretty` formats, or a `--format` template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pretty {
+    Oneline,
+    Short,
+    Medium,
+    Full,
+    Fuller,
+    Raw,
+    /// `format:` puts a newline between commits, `tformat:` after each one.
+    Format { template: String, terminated: bool },
+}
+
+impl Pretty {
+    fn parse(value: &str) -> io::Result<Self> {
+        let pretty = match value {
+            "oneline" => Pretty::Oneline,
+            "short" => Pretty::Short,
+            "medium" => Pretty::Medium,
+            "full" => Pretty::Full,
+            "fuller" => Pretty::Fuller,
+            "raw" => Pretty::Raw,
+            x if x.starts_with("format:") => Pretty::Format { template: x["format:".len()..].to_string(), terminated: false },
+            x if x.starts_with("tformat:") => Pretty::Format { template: x["tformat:".len()..].to_string(), terminated: true },
+            // like git, an empty format or anything with a placeholder is a tformat
+            x if x.is_empty() || x.contains('%') => Pretty::Format { template: x.to_string(), terminated: true },
+            x => return Err(CommandParseError::InvalidArgument(format!("invalid --pretty format: {}", x)).into()),
+        };
+        Ok(pretty)
+    }
+}
+
+pub struct LogCommand {
+    pretty: Pretty,
+    abbrev_commit: bool,
+    date_mode: DateMode,
+    /// `-n`: stop after this many commits.
+    max_count: Option<usize>,
+    /// `--author`: show commits whose author matches any of these.
+    authors: Vec<Regex>,
+    /// `--grep`: show commits whose message matches any of these.
+    greps: Vec<Regex>,
+    /// `--since`/`--until`: the committer dates to show, inclusive.
+    since: Option<i64>,
+    until: Option<i64>,
+    walk: WalkOptions,
+    reverse: bool,
+    /// `--graph`: draw the history to the left of the commits.
+    graph: bool,
+    decorate: Decorate,
+    diff: DiffOptions,
+    tips: Vec<Tip>,
+    paths: Vec<String>,
+}
+
+impl LogCommand {
+    /// Whether a commit passes the date, author and message filters.
+    fn matches(&self, commit: &ParsedCommit) -> bool {
+        let date = commit.committer.timestamp;
+        if self.since.is_some_and(|since| date < since) || self.until.is_some_and(|until| date > until) {
+            return false;
+        }
+        let author = format!("{} <{}>", commit.author.name, commit.author.email);
+        if !self.authors.is_empty() && !self.authors.iter().any(|re| re.is_match(&author)) {
+            return false;
+        }
+        self.greps.is_empty() || self.greps.iter().any(|re| re.is_match(&commit.message))
+    }
+
+    fn abbrev(&self, hash: &GitHash) -> String {
+        if self.abbrev_commit { hash.abbrev(DEFAULT_ABBREV) } else { hash.to_hex() }
+    }
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<LogCommand> {
+    let config = GitConfig::load()?;
+    let mut cmd = LogCommand {
+        pretty: Pretty::Medium,
+        abbrev_commit: false,
+        date_mode: DateMode::Default,
+        max_count: None,
+        authors: Vec::new(),
+        greps: Vec::new(),
+        since: None,
+        until: None,
+        walk: WalkOptions::default(),
+        reverse: false,
+        graph: false,
+        decorate: match config.get("log.decorate") {
+            Some(value) => Decorate::parse(value).unwrap_or(Decorate::Short),
+            None => Decorate::Auto,
+        },
+        diff: DiffOptions {
+            abbrev: Some(DEFAULT_ABBREV),
+            renames: RenameOptions::from_config(&config),
+            patch_opts: PatchOptions::from_config(&config)?,
+            ..DiffOptions::default()
+        },
+        tips: Vec::new(),
+        paths: Vec::new(),
+    };
+    let mut authors = Vec::new();
+    let mut greps = Vec::new();
+    let mut ignore_case = false;
+    let mut only_paths = false;
+    let mut negated = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next().cloned().ok_or_else(|| CommandParseError::MissingArgument(format!("option `{}' requires a value", name)))
+        };
+        // `--opt=value` and `--opt value` are the same
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, inline)) if flag.starts_with("--") => (flag, Some(inline.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut take = |name: &str| inline.clone().map_or_else(|| value(name), Ok);
+
+        match flag {
+            _ if only_paths => cmd.paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-n" | "--max-count" => cmd.max_count = Some(parse_count(&take("max-count")?)?),
+            "--oneline" => {
+                cmd.pretty = Pretty::Oneline;
+                cmd.abbrev_commit = true;
+            }
+            "--pretty" if inline.is_none() => cmd.pretty = Pretty::Medium,
+            "--pretty" | "--format" => cmd.pretty = Pretty::parse(&take("format")?)?,
+            "--abbrev-commit" => cmd.abbrev_commit = true,
+            "--no-abbrev-commit" => cmd.abbrev_commit = false,
+            "--date" => {
+                let mode = take("date")?;
+                cmd.date_mode = DateMode::parse(&mode).ok_or_else(|| CommandParseError::InvalidArgument(format!("unknown date format {}", mode)))?;
+            }
+            "--author" => authors.push(take("author")?),
+            "--grep" => greps.push(take("grep")?),
+            "-i" | "--regexp-ignore-case" => ignore_case = true,
+            "--since" | "--after" => cmd.since = Some(parse_approxidate(&take("since")?)?),
+            "--until" | "--before" => cmd.until = Some(parse_approxidate(&take("until")?)?),
+            "--not" => negated = !negated,
+            "--all" => cmd.tips.extend(all_tips(negated)?),
+            "--reverse" => cmd.reverse = true,
+            "--graph" => cmd.graph = true,
+            "--decorate" if inline.is_none() => cmd.decorate = Decorate::Short,
+            "--decorate" => {
+                let mode = take("decorate")?;
+                cmd.decorate = Decorate::parse(&mode).ok_or_else(|| CommandParseError::InvalidArgument(format!("invalid --decorate option: {}", mode)))?;
+            }
+            "--no-decorate" => cmd.decorate = Decorate::No,
+            x if x.starts_with("-n") => cmd.max_count = Some(parse_count(&x[2..])?),
+            x if x.len() > 1 && x.starts_with('-') && x[1..].bytes().all(|c| c.is_ascii_digit()) => cmd.max_count = Some(parse_count(&x[1..])?),
+            _ if cmd.walk.parse_option(arg)? => {}
+            _ if cmd.diff.parse_option(arg)? => {}
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            // like git, a name is a revision if it resolves, else it must be a path
+            other if cmd.paths.is_empty() => match parse_revision_arg(other, negated)? {
+                Some(tips) => cmd.tips.extend(tips),
+                None if Path::new(other).exists() => cmd.paths.push(other.to_string()),
+                None => {
+                    return Err(CommandParseError::InvalidArgument(format!("ambiguous argument '{}': unknown revision or path not in the working tree.", other)).into());
+                }
+            },
+            other => cmd.paths.push(other.to_string()),
+        }
+    }
+
+    let compile = |pattern: &String| {
+        RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| io::Error::from(CommandParseError::InvalidArgument(format!("invalid pattern '{}': {}", pattern, e))))
+    };
+    cmd.authors = authors.iter().map(compile).collect::<io::Result<_>>()?;
+    cmd.greps = greps.iter().map(compile).collect::<io::Result<_>>()?;
+    if cmd.graph && cmd.reverse {
+        return Err(CommandParseError::InvalidArgument("options '--reverse' and '--graph' cannot be used together".to_string()).into());
+    }
+    if cmd.decorate == Decorate::Auto {
+        cmd.decorate = if io::stdout().is_terminal() { Decorate::Short } else { Decorate::No };
+    }
+
+    if cmd.tips.is_empty() {
+        match head_commit()? {
+            Some(hash) => cmd.tips.push(Tip { hash, hidden: false }),
+            None => {
+                let branch = read_head()?.branch_name().unwrap_or("HEAD").to_string();
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("your current branch '{}' does not have any commits yet", branch)));
+            }
+        }
+    }
+    Ok(cmd)
+}
+
+fn parse_count(value: &str) -> io::Result<usize> {
+    value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("'{}' is not a number", value)).into())
+}
+
+/// How `--decorate` names the refs pointing at a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decorate {
+    No,
+    Short,
+    Full,
+    /// Short names when writing to a terminal, else none.
+    Auto,
+}
+
+impl Decorate {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "short" => Some(Decorate::Short),
+            "full" => Some(Decorate::Full),
+            "auto" => Some(Decorate::Auto),
+            "no" => Some(Decorate::No),
+            x => parse_bool(x).map(|on| if on { Decorate::Short } else { Decorate::No }),
+        }
+    }
+}
+
+/// The refs pointing at each commit, for `--decorate` and `%d`.
+#[derive(Default)]
+struct Decorations {
+    /// Full ref names, and `HEAD`, in the order git lists them.
+    by_commit: HashMap<GitHash, Vec<String>>,
+    /// The branch HEAD is on, shown as `HEAD -> branch`.
+    head_branch: Option<String>,
+}
+
+impl Decorations {
+    fn load() -> io::Result<Self> {
+        let mut by_commit: HashMap<GitHash, Vec<String>> = HashMap::new();
+        let decorated = |name: &str| ["refs/heads/", "refs/remotes/", "refs/tags/"].iter().any(|p| name.starts_with(p)) || name == "refs/stash";
+        for (name, hash) in list_refs()? {
+            if !decorated(&name) {
+                continue;
+            }
+            // a tag decorates the commit it points at
+            if let Ok(commit) = peel_to_commit(&hash) {
+                by_commit.entry(commit).or_default().push(name);
+            }
+        }
+        if let Some(head) = head_commit()? {
+            by_commit.entry(head).or_default().push("HEAD".to_string());
+        }
+        // git lists the last ref added first, so HEAD leads
+        for names in by_commit.values_mut() {
+            names.reverse();
+        }
+        let head_branch = match read_head()? {
+            Head::Branch(name) => Some(name),
+            Head::Detached(_) => None,
+        };
+        Ok(Decorations { by_commit, head_branch })
+    }
+
+    /// The decorations of a commit as git lists them, like `HEAD -> main`
+    /// or `tag: v1.0`.
+    fn names(&self, hash: &GitHash, mode: Decorate) -> Vec<String> {
+        let Some(names) = self.by_commit.get(hash) else { return Vec::new() };
+        let display = |name: &str| if mode == Decorate::Full { name.to_string() } else { shorten_ref(name).to_string() };
+        let current = self.head_branch.as_ref().filter(|branch| names.contains(&"HEAD".to_string()) && names.contains(branch));
+
+        let mut out = Vec::new();
+        for name in names {
+            if Some(name) == current {
+                continue;
+            }
+            out.push(match (name.as_str(), current) {
+                ("HEAD", Some(branch)) => format!("HEAD -> {}", display(branch)),
+                (x, _) if x.starts_with("refs/tags/") => format!("tag: {}", display(x)),
+                (x, _) => display(x),
+            });
+        }
+        out
+    }
+}
+
+/// Writes commits one after another, with the graph and decorations if
+/// asked for.
+struct LogWriter<'a> {
+    cmd: &'a LogCommand,
+    pathspec: &'a PathSpec,
+    graph: Option<Graph>,
+    decorations: Decorations,
+    shown_one: bool,
+    /// Whether the last commit's text didn't end in a newline.
+    missing_newline: bool,
+}
+
+impl LogWriter<'_> {
+    /// ` (HEAD -> main, tag: v1)` after a commit id, or nothing.
+    fn decoration(&self, hash: &GitHash, mode: Decorate) -> String {
+        let names = self.decorations.names(hash, mode);
+        if names.is_empty() { String::new() } else { format!(" ({})", names.join(", ")) }
+    }
+
+    fn show_padding(&mut self, out: &mut impl Write) -> io::Result<()> {
+        match self.graph.as_mut() {
+            Some(graph) => graph.show_padding(out),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes one commit in the chosen format, then its diff if one was asked for.
+    fn write_commit(&mut self, out: &mut impl Write, hash: &GitHash, commit: &ParsedCommit) -> io::Result<()> {
+        let cmd = self.cmd;
+        let (terminated, empty_format) = match &cmd.pretty {
+            Pretty::Oneline => (true, false),
+            Pretty::Format { template, terminated } => (*terminated, template.is_empty()),
+            _ => (false, false),
+        };
+
+        // between commits, a separator; the graph continues alongside it
+        // unless it would draw on the end of the last commit's text
+        if self.shown_one && !terminated {
+            if !self.missing_newline {
+                self.show_padding(out)?;
+            }
+            writeln!(out)?;
+        }
+        self.shown_one = true;
+        if let Some(graph) = self.graph.as_mut() {
+            graph.show_commit(out)?;
+        }
+
+        let mode = if cmd.decorate == Decorate::Full { Decorate::Full } else { Decorate::Short };
+        let text = match &cmd.pretty {
+            Pretty::Oneline => {
+                write!(out, "{}{} ", cmd.abbrev(hash), self.decoration(hash, cmd.decorate))?;
+                commit.subject()
+            }
+            Pretty::Format { template, .. } => {
+                let decoration = self.decorations.names(hash, mode).join(", ");
+                expand_format(template, hash, commit, &cmd.date_mode, &decoration)
+            }
+            pretty => {
+                writeln!(out, "commit {}{}", cmd.abbrev(hash), self.decoration(hash, cmd.decorate))?;
+                if let Some(graph) = self.graph.as_mut() {
+                    graph.show_oneline(out)?;
+                }
+                let mut text = Vec::new();
+                write_message(&mut text, cmd, pretty, commit)?;
+                String::from_utf8_lossy(&text).into_owned()
+            }
+        };
+        self.missing_newline = !text.ends_with('\n');
+        match self.graph.as_mut() {
+            Some(graph) => graph.show_commit_msg(out, &text)?,
+            None => write!(out, "{}", text)?,
+        }
+        if terminated && !empty_format {
+            if !self.missing_newline {
+                self.show_padding(out)?;
+            }
+            writeln!(out)?;
+        }
+
+        self.write_diff(out, commit, cmd.pretty != Pretty::Oneline && !empty_format)
+    }
+
+    /// Writes the diff of a commit, after a blank line if `separate`.
+    fn write_diff(&mut self, out: &mut impl Write, commit: &ParsedCommit, separate: bool) -> io::Result<()> {
+        let cmd = self.cmd;
+        if !cmd.diff.has_format() || cmd.diff.no_output {
+            return Ok(());
+        }
+        // merges have no diff, unless only their first parent is followed
+        let parent = match &commit.parents[..] {
+            [] => None,
+            [parent] => Some(parent),
+            [parent, ..] if cmd.walk.first_parent => Some(parent),
+            _ => return Ok(()),
+        };
+        let old_tree = parent.map(|p| read_commit(p).map(|c| c.tree)).transpose()?;
+        let changes = diff_trees(old_tree.as_ref(), Some(&commit.tree), self.pathspec, true)?;
+        let changes = detect_renames(changes, &cmd.diff.renames, || tree_files(old_tree.as_ref(), self.pathspec))?;
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let prefix = self.graph.as_mut().map(Graph::padding).unwrap_or_default();
+        let mut diff = Vec::new();
+        if separate {
+            // like format-patch, a stat followed by a patch is set off by `---`
+            writeln!(diff, "{}", if cmd.diff.stat && cmd.diff.patch { "---" } else { "" })?;
+        }
+        let opts = DiffOptions { line_prefix_width: prefix.chars().count(), ..cmd.diff.clone() };
+        write_changes(&mut diff, &changes, &opts)?;
+        for line in diff.split_inclusive(|&b| b == b'\n') {
+            out.write_all(prefix.as_bytes())?;
+            out.write_all(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The header lines and indented message of the built-in multi-line
+/// formats, which follow the `commit` line.
+fn write_message(out: &mut impl Write, cmd: &LogCommand, pretty: &Pretty, commit: &ParsedCommit) -> io::Result<()> {
+    if *pretty == Pretty::Raw {
+        writeln!(out, "tree {}", commit.tree.to_hex())?;
+        for parent in &commit.parents {
+            writeln!(out, "parent {}", parent.to_hex())?;
+        }
+        writeln!(out, "author {}", commit.author)?;
+        writeln!(out, "committer {}", commit.committer)?;
+    } else {
+        if commit.parents.len() > 1 {
+            let parents: Vec<String> = commit.parents.iter().map(|p| p.abbrev(DEFAULT_ABBREV)).collect();
+            writeln!(out, "Merge: {}", parents.join(" "))?;
+        }
+        let ident = |person: &Person| format!("{} <{}>", person.name, person.email);
+        let date = |person: &Person| format_date_as(person.timestamp, &person.timezone, &cmd.date_mode);
+        match pretty {
+            Pretty::Short => writeln!(out, "Author: {}", ident(&commit.author))?,
+            Pretty::Full => {
+                writeln!(out, "Author: {}", ident(&commit.author))?;
+                writeln!(out, "Commit: {}", ident(&commit.committer))?;
+            }
+            Pretty::Fuller => {
+                writeln!(out, "Author:     {}", ident(&commit.author))?;
+                writeln!(out, "AuthorDate: {}", date(&commit.author))?;
+                writeln!(out, "Commit:     {}", ident(&commit.committer))?;
+                writeln!(out, "CommitDate: {}", date(&commit.committer))?;
+            }
+            _ => {
+                writeln!(out, "Author: {}", ident(&commit.author))?;
+                writeln!(out, "Date:   {}", date(&commit.author))?;
+            }
+        }
+    }
+    writeln!(out)?;
+
+    if *pretty == Pretty::Short {
+        return writeln!(out, "    {}", commit.subject());
+    }
+    let message = commit.message.trim_end();
+    for line in message.lines().skip_while(|l| l.trim().is_empty()) {
+        if line.trim().is_empty() {
+            writeln!(out)?;
+        } else {
+            writeln!(out, "    {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Expands the `%` placeholders of a `--format` template for one commit.
+/// `decoration` is what `%D` gives: the commit's ref names, comma-separated.
+pub fn expand_format(template: &str, hash: &GitHash, commit: &ParsedCommit, date_mode: &DateMode, decoration: &str) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find('%') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        match expand_placeholder(rest, hash, commit, date_mode, decoration) {
+            Some((text, len)) => {
+                out.push_str(&text);
+                rest = &rest[len..];
+            }
+            // unknown placeholders are kept as they are
+            None => out.push('%'),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The expansion of the placeholder at the start of `spec` (after the `%`)
+/// and how many bytes it took.
+fn expand_placeholder(spec: &str, hash: &GitHash, commit: &ParsedCommit, date_mode: &DateMode, decoration: &str) -> Option<(String, usize)> {
+    const COLORS: [(&str, &str); 4] = [("Cred", "\x1b[31m"), ("Cgreen", "\x1b[32m"), ("Cblue", "\x1b[34m"), ("Creset", "\x1b[m")];
+    if let Some((name, code)) = COLORS.iter().find(|(name, _)| spec.starts_with(name)) {
+        return Some((code.to_string(), name.len()));
+    }
+    if let Some(hex) = spec.strip_prefix('x') {
+        let digits = hex.get(..2)?;
+        let byte = u8::from_str_radix(digits, 16).ok()?;
+        return Some(((byte as char).to_string(), 3));
+    }
+
+    let mut chars = spec.chars();
+    let first = chars.next()?;
+    let simple = match first {
+        'H' => Some(hash.to_hex()),
+        'h' => Some(hash.abbrev(DEFAULT_ABBREV)),
+        'T' => Some(commit.tree.to_hex()),
+        't' => Some(commit.tree.abbrev(DEFAULT_ABBREV)),
+        'P' => Some(commit.parents.iter().map(GitHash::to_hex).collect::<Vec<_>>().join(" ")),
+        'p' => Some(commit.parents.iter().map(|p| p.abbrev(DEFAULT_ABBREV)).collect::<Vec<_>>().join(" ")),
+        's' => Some(commit.subject()),
+        'f' => Some(sanitized_subject(&commit.subject())),
+        'b' => Some(body(&commit.message)),
+        'B' => Some(commit.message.clone()),
+        'n' => Some("\n".to_string()),
+        '%' => Some("%".to_string()),
+        'd' if decoration.is_empty() => Some(String::new()),
+        'd' => Some(format!(" ({})", decoration)),
+        'D' => Some(decoration.to_string()),
+        _ => None,
+    };
+    if let Some(text) = simple {
+        return Some((text, 1));
+    }
+
+    let person = match first {
+        'a' => &commit.author,
+        'c' => &commit.committer,
+        _ => return None,
+    };
+    let date = |mode: DateMode| format_date_as(person.timestamp, &person.timezone, &mode);
+    let text = match chars.next()? {
+        'n' | 'N' => person.name.clone(),
+        'e' | 'E' => person.email.clone(),
+        'l' | 'L' => person.email.split('@').next().unwrap_or_default().to_string(),
+        'd' => date(date_mode.clone()),
+        'D' => date(DateMode::Rfc),
+        'r' => date(DateMode::Relative),
+        't' => person.timestamp.to_string(),
+        'i' => date(DateMode::Iso),
+        'I' => date(DateMode::IsoStrict),
+        's' => date(DateMode::Short),
+        _ => return None,
+    };
+    Some((text, 2))
+}
+
+/// The message after the subject paragraph.
+fn body(message: &str) -> String {
+    let mut lines = message.lines().skip_while(|l| l.trim().is_empty());
+    lines.by_ref().take_while(|l| !l.trim().is_empty()).for_each(drop);
+    let body: Vec<&str> = lines.skip_while(|l| l.trim().is_empty()).collect();
+    if body.is_empty() {
+        return String::new();
+    }
+    let mut text = body.join("\n").trim_end().to_string();
+    text.push('\n');
+    text
+}
+
+/// The subject as a file name, as `%f` gives it: runs of anything other
+/// than letters, digits, `.` and `_` become one `-`.
+fn sanitized_subject(subject: &str) -> String {
+    let mut out = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            out.push(c);
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    out.trim_end_matches(['-', '.']).to_string()
+}
@@ -0,0 +1,206 @@
+use std::{io::{self, Write}, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, ignore::matcher::IgnoreMatcher, index::{index::{load_index, Index}, index_entry::IndexEntry}, pathspec::PathSpec, utils::path_utils::quote_path, worktree::{file_state::{check_entry, FileState}, walker::WorktreeWalker}};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let index = load_index(Path::new(INDEX_PATH))?;
+    let entries: Vec<&IndexEntry> = index.iter().filter(|e| cmd.pathspec.matches(&e.path)).collect();
+    let mut out = io::stdout().lock();
+
+    if cmd.others {
+        for path in list_others(&cmd, &index)? {
+            write!(out, "{}{}", cmd.display_path(&path), cmd.terminator())?;
+        }
+    }
+
+    if cmd.cached || cmd.stage {
+        for entry in &entries {
+            cmd.print_entry(&mut out, entry)?;
+        }
+    } else if cmd.unmerged {
+        for entry in entries.iter().filter(|e| e.is_unmerged()) {
+            cmd.print_entry(&mut out, entry)?;
+        }
+    }
+
+    if cmd.deleted || cmd.modified {
+        for (i, entry) in entries.iter().enumerate() {
+            // conflicted paths are reported once, not once per stage
+            if i > 0 && entries[i - 1].path == entry.path {
+                continue;
+            }
+            let state = check_entry(entry)?;
+            if cmd.deleted && state == FileState::Deleted {
+                cmd.print_entry(&mut out, entry)?;
+            }
+            // like git, -m also lists deleted files
+            if cmd.modified && state != FileState::Unchanged {
+                cmd.print_entry(&mut out, entry)?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+/// Untracked files in scope, or with `-i` only the ones ignore rules exclude.
+fn list_others(cmd: &LsFilesCommand, index: &Index) -> io::Result<Vec<String>> {
+    // -i needs the unfiltered walk so it can report what the rules would hide
+    let walk_ignore = if cmd.exclude_standard && !cmd.ignored { Some(IgnoreMatcher::load()?) } else { None };
+    let mut walker = WorktreeWalker::new(walk_ignore);
+    let mut matcher = if cmd.ignored { Some(IgnoreMatcher::load()?) } else { None };
+
+    let mut others = Vec::new();
+    for base in cmd.pathspec.base_dirs() {
+        for path in walker.files_under(&base)? {
+            if index.contains(&path) || !cmd.pathspec.matches(&path) {
+                continue;
+            }
+            if let Some(matcher) = matcher.as_mut() {
+                if !matcher.check_path(&path, false)?.is_some_and(|m| m.is_ignored()) {
+                    continue;
+                }
+            }
+            others.push(path);
+        }
+    }
+
+    others.sort();
+    others.dedup();
+    Ok(others)
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<LsFilesCommand> {
+    let mut cmd = LsFilesCommand::default();
+    let mut paths = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-c" | "--cached" => cmd.cached = true,
+            "-s" | "--stage" => cmd.stage = true,
+            "-u" | "--unmerged" => cmd.unmerged = true,
+            "-m" | "--modified" => cmd.modified = true,
+            "-d" | "--deleted" => cmd.deleted = true,
+            "-o" | "--others" => cmd.others = true,
+            "-i" | "--ignored" => cmd.ignored = true,
+            "--exclude-standard" => cmd.exclude_standard = true,
+            "-z" => cmd.nul_terminated = true,
+            x if x.starts_with("--format=") => cmd.format = Some(x["--format=".len()..].to_string()),
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => paths.push(other.to_string()),
+        }
+    }
+
+    if cmd.ignored && !cmd.others {
+        return Err(CommandParseError::InvalidArgument("ls-files -i must be used with -o".to_string()).into());
+    }
+    if cmd.ignored && !cmd.exclude_standard {
+        return Err(CommandParseError::InvalidArgument("ls-files -i needs --exclude-standard".to_string()).into());
+    }
+    if cmd.format.is_some() && (cmd.stage || cmd.unmerged || cmd.others) {
+        return Err(CommandParseError::InvalidArgument("--format cannot be used with -s, -u or -o".to_string()).into());
+    }
+
+    if !cmd.stage && !cmd.unmerged && !cmd.modified && !cmd.deleted && !cmd.others {
+        cmd.cached = true;
+    }
+    cmd.pathspec = PathSpec::parse(&paths)?;
+
+    Ok(cmd)
+}
+
+#[derive(Default)]
+pub struct LsFilesCommand {
+    cached: bool,
+    stage: bool,
+    /// `-u`: only conflicted entries, shown with their stages.
+    unmerged: bool,
+    modified: bool,
+    deleted: bool,
+    others: bool,
+    ignored: bool,
+    exclude_standard: bool,
+    nul_terminated: bool,
+    format: Option<String>,
+    pathspec: PathSpec,
+}
+
+impl LsFilesCommand {
+    fn terminator(&self) -> char {
+        if self.nul_terminated { '\0' } else { '\n' }
+    }
+
+    /// Paths are quoted like git's unless they end in NUL.
+    fn display_path(&self, path: &str) -> String {
+        if self.nul_terminated { path.to_string() } else { quote_path(path) }
+    }
+
+    fn print_entry<W: Write>(&self, out: &mut W, entry: &IndexEntry) -> io::Result<()> {
+        let path = self.display_path(&entry.path);
+        if let Some(format) = &self.format {
+            let line = expand_format(format, entry, &path)?;
+            return write!(out, "{}{}", line, self.terminator());
+        }
+
+        if self.stage || self.unmerged {
+            write!(out, "{} {} {}\t{}{}", mode_octal(entry), entry.hash.to_hex(), entry.stage, path, self.terminator())
+        } else {
+            write!(out, "{}{}", path, self.terminator())
+        }
+    }
+}
+
+/// Index modes are always printed zero-padded to six digits.
+fn mode_octal(entry: &IndexEntry) -> String {
+    format!("{:06o}", entry.mode.clone() as u32)
+}
+
+/// Expands `--format` placeholders such as `%(objectname)` and `%(path)`, where
+/// `path` is the entry's path as displayed.
+fn expand_format(format: &str, entry: &IndexEntry, path: &str) -> io::Result<String> {
+    let mut result = String::new();
+    let mut rest = format;
+
+    while let Some(pos) = rest.find('%') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        if let Some(after) = rest.strip_prefix('%') {
+            result.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('(') {
+            let end = after.find(')').ok_or_else(|| bad_format(format))?;
+            result.push_str(&expand_atom(&after[..end], entry, path, format)?);
+            rest = &after[end + 1..];
+        } else if rest.len() >= 2 && rest.is_char_boundary(2) && u8::from_str_radix(&rest[..2], 16).is_ok() {
+            result.push(u8::from_str_radix(&rest[..2], 16).unwrap() as char);
+            rest = &rest[2..];
+        } else {
+            result.push('%');
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn expand_atom(atom: &str, entry: &IndexEntry, path: &str, format: &str) -> io::Result<String> {
+    Ok(match atom {
+        "objectmode" => mode_octal(entry),
+        "objectname" => entry.hash.to_hex(),
+        "stage" => entry.stage.to_string(),
+        "path" => path.to_string(),
+        _ => return Err(bad_format(format)),
+    })
+}
+
+fn bad_format(format: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("bad ls-files format: {}", format))
+}
@@ -1,40 +1,95 @@
-use std::{fmt, io};
+use std::io::{self, Write};
 
-use crate::objects::{read_object, ObjectType, Tree, TreeEntry};
+use crate::{
+    commands::parse_error::CommandParseError,
+    hash::GitHash,
+    objects::{peel_to_tree, read_object, unique_abbrev, ObjectType, Tree, TreeEntry},
+    refs::resolve_revision,
+    utils::path_utils::{quote_path, to_repo_path},
+};
 
 
+/// The fewest digits `--abbrev` shortens object names to.
+const DEFAULT_ABBREV: usize = 7;
+
 pub fn run(args: &[String]) -> io::Result<()> {
     let cmd = parse_command(args)?;
-    let (object_type, _) = read_object(&cmd.tree_ish())?;
-    if object_type != ObjectType::Tree {
-        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Object {} is not a tree", cmd.tree_ish())));
-    }
+    let hash = resolve_revision(cmd.tree_ish())?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Not a valid object name {}", cmd.tree_ish())))?;
 
-    let tree = Tree::load_tree_from_hash(&cmd.tree_ish())?;
-        tree.walk_tree(cmd.base_path(), &mut |entry, path| {
-        cmd.printer().print(entry, path);
-    },cmd.recursive())?;
+    let tree = Tree::load_tree_from_hash(&peel_to_tree(&hash.to_hex())?.to_hex())?;
+    let terminator = if cmd.nul_terminated { b'\0' } else { b'\n' };
+    let mut out = io::stdout().lock();
+    let mut result = Ok(());
 
-    Ok(())
+    tree.walk_tree_with(cmd.base_path(), &mut |entry, path| {
+        if result.is_err() || !cmd.is_interesting(path, entry.object_type == ObjectType::Tree) {
+            return;
+        }
+        let shown = match entry.object_type {
+            // a tree being listed is only shown itself with -t
+            ObjectType::Tree => !cmd.descends_into(path) || cmd.show_trees,
+            _ => !cmd.trees_only,
+        };
+        if shown {
+            result = cmd.printer().format(entry, path).and_then(|mut line| {
+                line.push(terminator);
+                out.write_all(&line)
+            });
+        }
+    }, &mut |entry, path| {
+        cmd.is_interesting(path, entry.object_type == ObjectType::Tree) && cmd.descends_into(path)
+    })?;
+    result?;
+
+    out.flush()
 }
 
 pub fn parse_command(args: &[String]) -> io::Result<LsTreeCommand> {
     let mut tree_ish: Option<String> = None;
-    let mut printer: Option<Box<dyn TreeEntryPrinter>> = None;
+    let mut mode = Mode::Default;
+    let mut long = false;
+    let mut format = None;
+    let mut abbrev = None;
+    let mut recursive = false;
+    let mut show_trees = false;
+    let mut trees_only = false;
+    let mut nul_terminated = false;
+    let mut paths = Vec::new();
 
-    for arg in args {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
         match arg.as_str() {
-            "--name-only" => {
-                printer = Some(Box::new(NameOnlyPrinter));
+            "-r" => recursive = true,
+            "-t" => show_trees = true,
+            "-d" => trees_only = true,
+            "-z" => nul_terminated = true,
+            "-l" | "--long" => long = true,
+            "--name-only" | "--name-status" => mode = mode.set(Mode::NameOnly, arg)?,
+            "--object-only" => mode = mode.set(Mode::ObjectOnly, arg)?,
+            // paths are always given and shown from the top of the repository
+            "--full-name" | "--full-tree" => {}
+            "--abbrev" => abbrev = Some(DEFAULT_ABBREV),
+            x if x.starts_with("--abbrev=") => {
+                let value = &x["--abbrev=".len()..];
+                let n: usize = value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("'{}' is not a number", value)))?;
+                // git never abbreviates below 4 digits
+                abbrev = Some(n.clamp(4, 40));
+            }
+            "--format" => {
+                let value = iter.next().ok_or_else(|| CommandParseError::MissingArgument("option `format' requires a value".to_string()))?;
+                format = Some(parse_format(value)?);
             }
+            x if x.starts_with("--format=") => format = Some(parse_format(&x["--format=".len()..])?),
+            "--" => paths.extend(iter.by_ref().cloned()),
             x if x.starts_with('-') => {
                 return Err(CommandParseError::UnknownFlag(x.to_string()).into());
             }
             other => {
-                if let Some(already) = tree_ish.replace(other.to_string()) {
-                    return Err(CommandParseError::InvalidArgument(format!(
-                        "Multiple tree-ish values: '{}' and '{}'", already, other
-                    )).into());
+                if tree_ish.is_none() {
+                    tree_ish = Some(other.to_string());
+                } else {
+                    paths.push(other.to_string());
                 }
             }
         }
@@ -44,21 +99,78 @@ pub fn parse_command(args: &[String]) -> io::Result<LsTreeCommand> {
         CommandParseError::MissingArgument("tree-ish (e.g. a hash or HEAD)".to_string())
     })?;
 
-    let printer = printer.unwrap_or_default();
+    if long && mode != Mode::Default {
+        let flag = if mode == Mode::NameOnly { "name-only" } else { "object-only" };
+        return Err(CommandParseError::InvalidArgument(format!("option `{}' is incompatible with --long", flag)).into());
+    }
+    let printer: Box<dyn TreeEntryPrinter> = match format {
+        Some(_) if long || mode != Mode::Default => {
+            return Err(CommandParseError::InvalidArgument("--format can't be combined with other format-altering options".to_string()).into());
+        }
+        Some(items) => Box::new(FormatPrinter { items, abbrev }),
+        None => match mode {
+            Mode::Default => Box::new(DefaultPrinter { long, abbrev, quote: !nul_terminated }),
+            Mode::NameOnly => Box::new(NameOnlyPrinter { quote: !nul_terminated }),
+            Mode::ObjectOnly => Box::new(ObjectOnlyPrinter { abbrev }),
+        },
+    };
+
+    let mut pathspec = Vec::new();
+    for path in &paths {
+        pathspec.push(LsTreePath { path: to_repo_path(path)?, dir_only: path.ends_with('/') });
+    }
 
     Ok(LsTreeCommand {
         tree_ish,
         base_path: "".to_string(),
         printer,
-        recursive: false,
+        recursive,
+        // like git, -d -r lists every tree
+        show_trees: show_trees || (trees_only && recursive),
+        trees_only,
+        nul_terminated,
+        pathspec,
     })
 }
 
+/// Which of the mutually exclusive output options was given.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Default,
+    NameOnly,
+    ObjectOnly,
+}
+
+impl Mode {
+    fn set(self, mode: Mode, flag: &str) -> io::Result<Mode> {
+        match self {
+            Mode::Default => Ok(mode),
+            other if other == mode => Ok(mode),
+            Mode::NameOnly => Err(CommandParseError::InvalidArgument(format!("option `{}' is incompatible with --name-only", &flag[2..])).into()),
+            Mode::ObjectOnly => Err(CommandParseError::InvalidArgument(format!("option `{}' is incompatible with --object-only", &flag[2..])).into()),
+        }
+    }
+}
+
+/// A path argument. Like git's ls-tree, these are literal: no wildcards,
+/// and a trailing `/` matches only a tree, whose entries are then listed.
+struct LsTreePath {
+    path: String,
+    dir_only: bool,
+}
+
 pub struct LsTreeCommand {
     tree_ish: String,
     base_path: String,
     printer: Box<dyn TreeEntryPrinter>,
     recursive: bool,
+    /// `-t`: show the trees being recursed into too.
+    show_trees: bool,
+    /// `-d`: show only trees.
+    trees_only: bool,
+    /// `-z`: end entries with NUL and don't quote paths.
+    nul_terminated: bool,
+    pathspec: Vec<LsTreePath>,
 }
 
 impl LsTreeCommand {
@@ -77,61 +189,178 @@ impl LsTreeCommand {
     pub fn recursive(&self) -> bool {
         self.recursive
     }
+
+    /// Whether an entry is named by, inside, or on the way to a path argument.
+    fn is_interesting(&self, path: &str, is_tree: bool) -> bool {
+        self.pathspec.is_empty()
+            || self.pathspec.iter().any(|spec| {
+                spec.path.is_empty()
+                    || (path == spec.path && (is_tree || !spec.dir_only))
+                    || path.strip_prefix(spec.path.as_str()).is_some_and(|rest| rest.starts_with('/'))
+                    || (is_tree && spec.path.strip_prefix(path).is_some_and(|rest| rest.starts_with('/')))
+            })
+    }
+
+    /// Whether to list a tree's entries: always with -r, else only when a
+    /// path argument reaches below it (`dir/` lists what's in `dir`).
+    fn descends_into(&self, path: &str) -> bool {
+        self.recursive()
+            || self.pathspec.iter().any(|spec| {
+                let full = if spec.dir_only && !spec.path.is_empty() { format!("{}/", spec.path) } else { spec.path.clone() };
+                full.strip_prefix(path).is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
 }
 
-#[derive(Debug)]
-pub enum CommandParseError {
-    MissingArgument(String),
-    UnknownFlag(String),
-    InvalidArgument(String),
+pub trait  TreeEntryPrinter {
+    /// The entry's line, without the terminator.
+    fn format(&self, entry: &TreeEntry, path: &str) -> io::Result<Vec<u8>>;
 }
 
-impl fmt::Display for CommandParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            CommandParseError::MissingArgument(msg) => write!(f, "Missing argument: {}", msg),
-            CommandParseError::UnknownFlag(flag) => write!(f, "Unknown flag: {}", flag),
-            CommandParseError::InvalidArgument(arg) => write!(f, "Invalid argument: {}", arg),
-        }
+fn object_name(hash: &GitHash, abbrev: Option<usize>) -> io::Result<String> {
+    match abbrev {
+        Some(min) => unique_abbrev(hash, min),
+        None => Ok(hash.to_hex()),
     }
 }
 
-impl std::error::Error for CommandParseError {}
-
-impl From<CommandParseError> for io::Error {
-    fn from(err: CommandParseError) -> Self {
-        io::Error::new(io::ErrorKind::InvalidInput, err)
+/// A blob's size in bytes, or `-` for anything else.
+fn object_size(entry: &TreeEntry) -> io::Result<String> {
+    match entry.object_type {
+        ObjectType::Blob => Ok(read_object(&entry.hash.to_hex())?.1.len().to_string()),
+        _ => Ok("-".to_string()),
     }
 }
 
-pub trait  TreeEntryPrinter {
-    fn print(&self, entry: &TreeEntry, path: &str);
+fn object_mode(entry: &TreeEntry) -> String {
+    format!("{:06o}", entry.mode.clone() as u32)
 }
 
-pub struct NameOnlyPrinter;
+pub struct NameOnlyPrinter {
+    quote: bool,
+}
 
 impl TreeEntryPrinter for NameOnlyPrinter {
-    fn print(&self, _entry: &TreeEntry, path: &str) {
-        println!("{}", path);
+    fn format(&self, _entry: &TreeEntry, path: &str) -> io::Result<Vec<u8>> {
+        Ok(if self.quote { quote_path(path) } else { path.to_string() }.into_bytes())
     }
 }
 
-pub struct DefaultPrinter;
+pub struct ObjectOnlyPrinter {
+    abbrev: Option<usize>,
+}
+
+impl TreeEntryPrinter for ObjectOnlyPrinter {
+    fn format(&self, entry: &TreeEntry, _path: &str) -> io::Result<Vec<u8>> {
+        object_name(&entry.hash, self.abbrev).map(String::into_bytes)
+    }
+}
+
+/// `<mode> <type> <object>\t<path>`, with the size before the tab for `-l`.
+pub struct DefaultPrinter {
+    long: bool,
+    abbrev: Option<usize>,
+    quote: bool,
+}
 
 impl TreeEntryPrinter for DefaultPrinter {
-    fn print(&self, entry: &TreeEntry, path: &str) {
-        println!(
-            "{} {} {}\t{}",
-            entry.mode,
-            entry.object_type,
-            entry.hash.to_hex(),
-            path
-        );
+    fn format(&self, entry: &TreeEntry, path: &str) -> io::Result<Vec<u8>> {
+        let mut line = format!("{} {} {}", object_mode(entry), entry.object_type, object_name(&entry.hash, self.abbrev)?);
+        if self.long {
+            line.push_str(&format!(" {:>7}", object_size(entry)?));
+        }
+        line.push('\t');
+        line.push_str(&if self.quote { quote_path(path) } else { path.to_string() });
+        Ok(line.into_bytes())
     }
 }
 
-impl Default for Box<dyn TreeEntryPrinter> {
-    fn default() -> Self {
-        Box::new(DefaultPrinter)
+/// One piece of a `--format` string.
+enum FormatItem {
+    Literal(Vec<u8>),
+    Mode,
+    Type,
+    Name,
+    Size,
+    SizePadded,
+    Path,
+}
+
+/// Parses `--format`: `%(objectmode)`, `%(objecttype)`, `%(objectname)`,
+/// `%(objectsize)`, `%(objectsize:padded)` and `%(path)`, plus `%%`, `%n`
+/// and `%xNN`.
+fn parse_format(format: &str) -> io::Result<Vec<FormatItem>> {
+    let mut items = Vec::new();
+    let mut literal = Vec::new();
+    let mut rest = format;
+    while let Some(at) = rest.find('%') {
+        literal.extend_from_slice(&rest.as_bytes()[..at]);
+        let spec = &rest[at + 1..];
+        let (item, len) = if spec.starts_with('%') {
+            literal.push(b'%');
+            (None, 1)
+        } else if spec.starts_with('n') {
+            literal.push(b'\n');
+            (None, 1)
+        } else if let Some(byte) = spec.strip_prefix('x').and_then(|hex| hex.get(..2)).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            literal.push(byte);
+            (None, 3)
+        } else {
+            let bad = || io::Error::new(io::ErrorKind::InvalidInput, format!("bad ls-tree format: %{}", spec.split_inclusive(')').next().unwrap_or(spec)));
+            if !spec.starts_with('(') {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("bad ls-tree format: element '{}' does not start with '('", spec)));
+            }
+            let end = spec.find(')').ok_or_else(bad)?;
+            let item = match &spec[1..end] {
+                "objectmode" => FormatItem::Mode,
+                "objecttype" => FormatItem::Type,
+                "objectname" => FormatItem::Name,
+                "objectsize" => FormatItem::Size,
+                "objectsize:padded" => FormatItem::SizePadded,
+                "path" => FormatItem::Path,
+                _ => return Err(bad()),
+            };
+            (Some(item), end + 1)
+        };
+        if let Some(item) = item {
+            if !literal.is_empty() {
+                items.push(FormatItem::Literal(std::mem::take(&mut literal)));
+            }
+            items.push(item);
+        }
+        rest = &spec[len..];
+    }
+    literal.extend_from_slice(rest.as_bytes());
+    if !literal.is_empty() {
+        items.push(FormatItem::Literal(literal));
+    }
+    Ok(items)
+}
+
+pub struct FormatPrinter {
+    items: Vec<FormatItem>,
+    abbrev: Option<usize>,
+}
+
+impl TreeEntryPrinter for FormatPrinter {
+    fn format(&self, entry: &TreeEntry, path: &str) -> io::Result<Vec<u8>> {
+        let mut line = Vec::new();
+        for item in &self.items {
+            let expanded = match item {
+                FormatItem::Literal(bytes) => {
+                    line.extend_from_slice(bytes);
+                    continue;
+                }
+                FormatItem::Mode => object_mode(entry),
+                FormatItem::Type => entry.object_type.to_string(),
+                FormatItem::Name => object_name(&entry.hash, self.abbrev)?,
+                FormatItem::Size => object_size(entry)?,
+                FormatItem::SizePadded => format!("{:>7}", object_size(entry)?),
+                // git quotes the path here even with -z
+                FormatItem::Path => quote_path(path),
+            };
+            line.extend_from_slice(expanded.as_bytes());
+        }
+        Ok(line)
     }
-}
\ No newline at end of file
+}
@@ -6,18 +6,47 @@ pub mod add;
 pub mod write_tree;
 pub mod commit_tree;
 pub mod clone;
+pub mod mv;
+pub mod check_ignore;
+pub mod ls_files;
+pub mod update_index;
+pub mod read_tree;
+pub mod status;
+pub mod diff;
+pub mod diff_tree;
+pub mod diff_index;
+pub mod checkout;
+pub mod commit;
+pub mod log;
+pub mod rev_list;
+pub mod rev_parse;
+pub mod parse_error;
 use std::io;
 
 pub fn run(args: &[String]) -> io::Result<()> {
-    match args.get(0).map(String::as_str) {
+    match args.first().map(String::as_str) {
         Some("init") => init::run(),
         Some("cat-file") => cat_file::run(args),
         Some("hash-object") => hash_object::run(args),
         Some("ls-tree") => ls_tree::run(&args[1..]),
         Some("add") => add::run(&args[1..]),
-        Some("write-tree") => write_tree::run(),
+        Some("write-tree") => write_tree::run(&args[1..]),
         Some("commit-tree") => commit_tree::run(&args[1..]),
         Some("clone") => clone::run(&args[1..]),
+        Some("mv") => mv::run(&args[1..]),
+        Some("check-ignore") => check_ignore::run(&args[1..]),
+        Some("ls-files") => ls_files::run(&args[1..]),
+        Some("update-index") => update_index::run(&args[1..]),
+        Some("read-tree") => read_tree::run(&args[1..]),
+        Some("status") => status::run(&args[1..]),
+        Some("diff") => diff::run(&args[1..]),
+        Some("diff-tree") => diff_tree::run(&args[1..]),
+        Some("diff-index") => diff_index::run(&args[1..]),
+        Some("checkout") => checkout::run(&args[1..]),
+        Some("commit") => commit::run(&args[1..]),
+        Some("log") => log::run(&args[1..]),
+        Some("rev-list") => rev_list::run(&args[1..]),
+        Some("rev-parse") => rev_parse::run(&args[1..]),
         Some(cmd) => {
             eprintln!("unknown command: {}", cmd);
             Ok(())
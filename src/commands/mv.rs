@@ -0,0 +1,206 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, index::index::{load_index, Index, IndexLock}, utils::path_utils::{file_name, join_repo_path, to_repo_path}};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let index_path = Path::new(INDEX_PATH);
+    // held from the read to the write, so no other update is lost
+    let lock = IndexLock::acquire(index_path)?;
+    let mut entries = load_index(index_path)?;
+
+    let moves = plan_moves(&cmd, &entries)?;
+
+    for mv in &moves {
+        if cmd.dry_run {
+            println!("Checking rename of '{}' to '{}'", mv.source, mv.destination);
+        }
+        if cmd.dry_run || cmd.verbose {
+            println!("Renaming {} to {}", mv.source, mv.destination);
+        }
+    }
+
+    if cmd.dry_run {
+        return Ok(());
+    }
+
+    let mut applied = Vec::with_capacity(moves.len());
+    for mv in moves {
+        match fs::rename(&mv.source, &mv.destination) {
+            Ok(()) => applied.push(mv),
+            Err(_) if cmd.skip_errors => continue,
+            Err(e) => {
+                // keep the index consistent with whatever already moved on disk
+                apply_to_index(&mut entries, &applied);
+                lock.commit(&entries)?;
+                return Err(io::Error::new(e.kind(), format!("renaming '{}' failed: {}", mv.source, e)));
+            }
+        }
+    }
+
+    apply_to_index(&mut entries, &applied);
+    lock.commit(&entries)
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<MvCommand> {
+    let mut force = false;
+    let mut skip_errors = false;
+    let mut dry_run = false;
+    let mut verbose = false;
+    let mut paths = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-f" | "--force" => force = true,
+            "-k" => skip_errors = true,
+            "-n" | "--dry-run" => dry_run = true,
+            "-v" | "--verbose" => verbose = true,
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => paths.push(other.to_string()),
+        }
+    }
+
+    if paths.len() < 2 {
+        return Err(CommandParseError::MissingArgument("usage: mv [-f] [-k] [-n] [-v] <source>... <destination>".to_string()).into());
+    }
+
+    let destination = paths.pop().unwrap();
+
+    Ok(MvCommand {
+        sources: paths,
+        destination,
+        force,
+        skip_errors,
+        dry_run,
+        verbose,
+    })
+}
+
+pub struct MvCommand {
+    sources: Vec<String>,
+    destination: String,
+    force: bool,
+    skip_errors: bool,
+    dry_run: bool,
+    verbose: bool,
+}
+
+/// A single validated rename, with both sides as repo-relative paths.
+struct Move {
+    source: String,
+    destination: String,
+    is_dir: bool,
+}
+
+fn plan_moves(cmd: &MvCommand, entries: &Index) -> io::Result<Vec<Move>> {
+    let destination = to_repo_path(&cmd.destination)?;
+    let destination_is_dir = destination.is_empty() || Path::new(&destination).is_dir();
+
+    if cmd.sources.len() > 1 && !destination_is_dir {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("destination '{}' is not a directory", cmd.destination)));
+    }
+
+    let mut moves = Vec::with_capacity(cmd.sources.len());
+    let mut targets = HashSet::new();
+
+    for source_arg in &cmd.sources {
+        let source = to_repo_path(source_arg)?;
+        let target = if destination_is_dir {
+            join_repo_path(&destination, file_name(&source))
+        } else {
+            destination.clone()
+        };
+
+        match check_move(cmd, entries, &source, &target, &targets) {
+            Ok(is_dir) => {
+                targets.insert(target.clone());
+                moves.push(Move { source, destination: target, is_dir });
+            }
+            Err(_) if cmd.skip_errors => continue,
+            Err(reason) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}, source={}, destination={}", reason, source, target)));
+            }
+        }
+    }
+
+    Ok(moves)
+}
+
+/// Validates one rename and reports whether the source is a tracked directory.
+fn check_move(cmd: &MvCommand, entries: &Index, source: &str, target: &str, targets: &HashSet<String>) -> Result<bool, &'static str> {
+    if source.is_empty() {
+        return Err("bad source");
+    }
+
+    let source_meta = fs::symlink_metadata(source).map_err(|_| "bad source")?;
+    if source == target {
+        return Err("source and destination are the same");
+    }
+
+    if targets.contains(target) {
+        return Err("multiple sources for the same target");
+    }
+
+    let target_path = Path::new(target);
+    let target_exists = fs::symlink_metadata(target_path).is_ok();
+
+    if source_meta.is_dir() {
+        if target.starts_with(&format!("{}/", source)) {
+            return Err("can not move directory into itself");
+        }
+        let tracked = entries.entries_under(source);
+        if tracked.is_empty() {
+            return Err("source directory is empty");
+        }
+        if tracked.iter().any(|e| e.is_unmerged()) {
+            return Err("conflicted");
+        }
+        if target_exists {
+            return Err("destination already exists");
+        }
+        return Ok(true);
+    }
+
+    let stages = entries.stages(source);
+    if stages.is_empty() {
+        return Err("not under version control");
+    }
+    if stages.iter().any(|e| e.is_unmerged()) {
+        return Err("conflicted");
+    }
+
+    if target_exists {
+        if !cmd.force {
+            return Err("destination exists");
+        }
+        if target_path.is_dir() {
+            return Err("cannot overwrite a directory");
+        }
+    }
+
+    Ok(false)
+}
+
+fn apply_to_index(entries: &mut Index, moves: &[Move]) {
+    for mv in moves {
+        let moved = if mv.is_dir {
+            entries.entries_under(&mv.source).to_vec()
+        } else {
+            // a forced move replaces whatever was tracked at the destination
+            entries.remove(&mv.destination);
+            entries.stages(&mv.source).to_vec()
+        };
+
+        for mut entry in moved {
+            entries.remove(&entry.path);
+            entry.path = format!("{}{}", mv.destination, &entry.path[mv.source.len()..]);
+            entries.add(entry);
+        }
+    }
+}
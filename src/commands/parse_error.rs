@@ -0,0 +1,26 @@
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum CommandParseError {
+    MissingArgument(String),
+    UnknownFlag(String),
+    InvalidArgument(String),
+}
+
+impl fmt::Display for CommandParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandParseError::MissingArgument(msg) => write!(f, "Missing argument: {}", msg),
+            CommandParseError::UnknownFlag(flag) => write!(f, "Unknown flag: {}", flag),
+            CommandParseError::InvalidArgument(arg) => write!(f, "Invalid argument: {}", arg),
+        }
+    }
+}
+
+impl std::error::Error for CommandParseError {}
+
+impl From<CommandParseError> for io::Error {
+    fn from(err: CommandParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
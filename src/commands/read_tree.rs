@@ -0,0 +1,202 @@
+use std::{collections::{BTreeMap, BTreeSet}, io, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, hash::GitHash, index::{index::{entries_from_tree, load_index, write_index, Index}, index_entry::IndexEntry}, objects::peel_to_tree, refs::resolve_revision, utils::path_utils::to_repo_path};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let index_path = Path::new(INDEX_PATH);
+
+    if cmd.empty {
+        return write_index(index_path, &Index::default());
+    }
+
+    let tree_hashes = cmd.trees.iter().map(|t| resolve_tree(t)).collect::<io::Result<Vec<_>>>()?;
+    let trees = tree_hashes
+        .iter()
+        .map(|hash| entries_from_tree(hash, ""))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let current = if cmd.ignore_index { Index::default() } else { load_index(index_path)? };
+    if cmd.merge && current.has_unmerged() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "you need to resolve your current index first"));
+    }
+
+    let index = match (&cmd.prefix, cmd.merge, trees.len()) {
+        (Some(prefix), _, _) => read_with_prefix(current, &tree_hashes[0], prefix)?,
+        (None, false, _) | (None, true, 1) => trees.into_iter().next().unwrap_or_default(),
+        (None, true, 2) => two_way_merge(&current, &trees[0], &trees[1])?,
+        (None, true, _) => three_way_merge(&current, &trees[0], &trees[1], &trees[2])?,
+    };
+
+    write_index(index_path, &index)
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<ReadTreeCommand> {
+    let mut cmd = ReadTreeCommand::default();
+
+    for arg in args {
+        match arg.as_str() {
+            "-m" => cmd.merge = true,
+            "-i" => cmd.ignore_index = true,
+            "--empty" => cmd.empty = true,
+            x if x.starts_with("--prefix=") => cmd.prefix = Some(to_repo_path(&x["--prefix=".len()..])?),
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => cmd.trees.push(other.to_string()),
+        }
+    }
+
+    if cmd.empty {
+        if !cmd.trees.is_empty() {
+            return Err(CommandParseError::InvalidArgument("passing trees as arguments contradicts --empty".to_string()).into());
+        }
+        return Ok(cmd);
+    }
+
+    if cmd.trees.is_empty() {
+        return Err(CommandParseError::MissingArgument("usage: read-tree [(-m | --prefix=<prefix>)] <tree-ish1> [<tree-ish2> [<tree-ish3>]]".to_string()).into());
+    }
+    if cmd.trees.len() > 3 {
+        return Err(CommandParseError::InvalidArgument("at most three trees can be merged".to_string()).into());
+    }
+    if cmd.prefix.is_some() && (cmd.merge || cmd.trees.len() > 1) {
+        return Err(CommandParseError::InvalidArgument("--prefix reads a single tree and cannot be combined with -m".to_string()).into());
+    }
+    if !cmd.merge && cmd.prefix.is_none() && cmd.trees.len() > 1 {
+        return Err(CommandParseError::InvalidArgument("reading multiple trees requires -m".to_string()).into());
+    }
+    if cmd.ignore_index && !cmd.merge {
+        return Err(CommandParseError::InvalidArgument("-i requires -m".to_string()).into());
+    }
+
+    Ok(cmd)
+}
+
+#[derive(Default)]
+pub struct ReadTreeCommand {
+    trees: Vec<String>,
+    merge: bool,
+    prefix: Option<String>,
+    empty: bool,
+    /// `-i`: don't check the current index when merging.
+    ignore_index: bool,
+}
+
+/// The tree a tree-ish argument names, in any form gitrevisions allows.
+fn resolve_tree(tree_ish: &str) -> io::Result<GitHash> {
+    let hash = resolve_revision(tree_ish)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Not a valid object name {}", tree_ish)))?;
+    peel_to_tree(&hash.to_hex())
+}
+
+/// Adds a tree below `prefix` to the existing index, refusing to touch existing entries.
+fn read_with_prefix(mut current: Index, tree: &GitHash, prefix: &str) -> io::Result<Index> {
+    if prefix.is_empty() && !current.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "read-tree --prefix= needs an empty index"));
+    }
+
+    // a file at the prefix itself or at one of its parent directories also clashes
+    let clash = current
+        .entries_under(prefix)
+        .first()
+        .or_else(|| prefix_and_parents(prefix).find_map(|p| current.stages(p).first()));
+    if let Some(entry) = clash {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("subdirectory '{}' already exists ('{}' is in the index)", prefix, entry.path)));
+    }
+
+    for entry in entries_from_tree(tree, prefix)?.into_entries() {
+        current.add(entry);
+    }
+    Ok(current)
+}
+
+/// `a/b/c`, `a/b` and `a` for the prefix `a/b/c`.
+fn prefix_and_parents(prefix: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(prefix).filter(|p| !p.is_empty()), |p| p.rfind('/').map(|pos| &p[..pos]))
+}
+
+fn by_path(entries: &Index) -> BTreeMap<&str, &IndexEntry> {
+    entries.iter().map(|e| (e.path.as_str(), e)).collect()
+}
+
+fn same(a: Option<&&IndexEntry>, b: Option<&&IndexEntry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.mode == b.mode && a.hash == b.hash,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn would_overwrite(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Entry '{}' would be overwritten by merge. Cannot merge.", path))
+}
+
+/// Moves the index from tree `head` to tree `target`, carrying local index
+/// changes along as long as `target` doesn't touch the same paths.
+fn two_way_merge(index: &Index, head: &Index, target: &Index) -> io::Result<Index> {
+    let (index, head, target) = (by_path(index), by_path(head), by_path(target));
+    let paths: BTreeSet<&str> = index.keys().chain(head.keys()).chain(target.keys()).copied().collect();
+
+    let mut result = Vec::new();
+    for path in paths {
+        let (i, h, m) = (index.get(path), head.get(path), target.get(path));
+
+        let chosen = if same(h, m) || same(i, m) {
+            i
+        } else if same(i, h) {
+            m
+        } else {
+            return Err(would_overwrite(path));
+        };
+
+        if let Some(entry) = chosen {
+            result.push((*entry).clone());
+        }
+    }
+
+    Ok(Index::from_entries(result))
+}
+
+/// Merges `theirs` into `ours` relative to `base`, resolving only the trivial
+/// cases; anything else is recorded as conflict stages 1 (base), 2 (ours) and 3 (theirs).
+fn three_way_merge(index: &Index, base: &Index, ours: &Index, theirs: &Index) -> io::Result<Index> {
+    let (index, base, ours, theirs) = (by_path(index), by_path(base), by_path(ours), by_path(theirs));
+    let paths: BTreeSet<&str> = base.keys().chain(ours.keys()).chain(theirs.keys()).chain(index.keys()).copied().collect();
+
+    let mut result = Vec::new();
+    for path in paths {
+        let (i, o, a, b) = (index.get(path), base.get(path), ours.get(path), theirs.get(path));
+
+        // local index changes are only safe if the merge keeps our version
+        let index_dirty = !index.is_empty() && !same(i, a);
+
+        let chosen = if same(a, b) || same(o, b) {
+            a
+        } else if same(o, a) {
+            b
+        } else {
+            if index_dirty {
+                return Err(would_overwrite(path));
+            }
+            for (stage, entry) in [(1, o), (2, a), (3, b)] {
+                if let Some(entry) = entry {
+                    result.push((*entry).clone().with_stage(stage));
+                }
+            }
+            continue;
+        };
+
+        if index_dirty && !same(chosen, a) {
+            return Err(would_overwrite(path));
+        }
+
+        let chosen = if same(chosen, a) && !index.is_empty() { i } else { chosen };
+        if let Some(entry) = chosen {
+            result.push((*entry).clone());
+        }
+    }
+
+    Ok(Index::from_entries(result))
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashSet, io::{self, Write}, path::Path};
+
+use crate::{
+    commands::parse_error::CommandParseError,
+    hash::GitHash,
+    objects::{commit_object::read_commit, read_object, tree::Tree, ObjectType},
+    pathspec::PathSpec,
+    revwalk::{all_tips, parse_revision_arg, RevWalk, Tip, WalkOptions},
+};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+
+    let mut walk = RevWalk::new();
+    walk.set_options(cmd.walk.clone());
+    if !cmd.paths.is_empty() {
+        walk.limit_to_paths(PathSpec::parse(&cmd.paths)?);
+    }
+    walk.set_max_count(cmd.max_count);
+    walk.set_reverse(cmd.reverse);
+    for tip in &cmd.tips {
+        walk.add(tip)?;
+    }
+
+    let mut out = io::stdout().lock();
+    let mut count = 0;
+    let mut commits = Vec::new();
+    for found in walk.by_ref() {
+        let (hash, commit) = found?;
+        count += 1;
+        if !cmd.count {
+            write!(out, "{}", hash.to_hex())?;
+            if cmd.parents {
+                for parent in &commit.parents {
+                    write!(out, " {}", parent.to_hex())?;
+                }
+            }
+            writeln!(out)?;
+        }
+        if cmd.objects {
+            commits.push((hash, commit));
+        }
+    }
+
+    if cmd.objects {
+        // what the hidden commits just outside the walk have is left out
+        let mut seen = HashSet::new();
+        for (_, commit) in &commits {
+            for parent in &commit.parents {
+                if walk.is_hidden(parent) && seen.insert(parent.clone()) {
+                    mark_tree_seen(&read_commit(parent)?.tree, &mut seen)?;
+                }
+            }
+        }
+
+        let mut objects = Vec::new();
+        for (hash, name) in &cmd.tags {
+            if seen.insert(hash.clone()) {
+                objects.push((hash.clone(), name.clone()));
+            }
+        }
+        for (_, commit) in &commits {
+            list_tree_objects(&commit.tree, "", &mut seen, &mut objects)?;
+        }
+        count += objects.len();
+        if !cmd.count {
+            for (hash, path) in objects {
+                writeln!(out, "{} {}", hash.to_hex(), path)?;
+            }
+        }
+    }
+
+    if cmd.count {
+        writeln!(out, "{}", count)?;
+    }
+    out.flush()
+}
+
+/// Marks a tree and everything in it as already listed.
+fn mark_tree_seen(tree: &GitHash, seen: &mut HashSet<GitHash>) -> io::Result<()> {
+    seen.insert(tree.clone());
+    for entry in Tree::load_tree_from_hash(&tree.to_hex())?.entries {
+        match entry.object_type {
+            ObjectType::Tree if !seen.contains(&entry.hash) => mark_tree_seen(&entry.hash, seen)?,
+            ObjectType::Blob => {
+                seen.insert(entry.hash);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Lists a tree and, depth first, the trees and blobs in it that haven't
+/// been listed yet, each with its path.
+fn list_tree_objects(tree: &GitHash, path: &str, seen: &mut HashSet<GitHash>, objects: &mut Vec<(GitHash, String)>) -> io::Result<()> {
+    if !seen.insert(tree.clone()) {
+        return Ok(());
+    }
+    objects.push((tree.clone(), path.to_string()));
+    for entry in Tree::load_tree_from_hash(&tree.to_hex())?.entries {
+        let entry_path = if path.is_empty() { entry.name.clone() } else { format!("{}/{}", path, entry.name) };
+        match entry.object_type {
+            ObjectType::Tree => list_tree_objects(&entry.hash, &entry_path, seen, objects)?,
+            // submodule commits aren't in this repository
+            ObjectType::Blob if seen.insert(entry.hash.clone()) => objects.push((entry.hash, entry_path)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub struct RevListCommand {
+    walk: WalkOptions,
+    tips: Vec<Tip>,
+    /// Tags named on the command line, listed by `--objects` under that name.
+    tags: Vec<(GitHash, String)>,
+    max_count: Option<usize>,
+    reverse: bool,
+    /// `--count`: print how many commits (and objects) there are instead.
+    count: bool,
+    /// `--objects`: also list the trees and blobs the commits use.
+    objects: bool,
+    /// `--parents`: print each commit's parents after it.
+    parents: bool,
+    paths: Vec<String>,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<RevListCommand> {
+    let mut cmd = RevListCommand {
+        walk: WalkOptions::default(),
+        tips: Vec::new(),
+        tags: Vec::new(),
+        max_count: None,
+        reverse: false,
+        count: false,
+        objects: false,
+        parents: false,
+        paths: Vec::new(),
+    };
+    let mut only_paths = false;
+    let mut negated = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            _ if only_paths => cmd.paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "--count" => cmd.count = true,
+            "--objects" => cmd.objects = true,
+            "--parents" => cmd.parents = true,
+            "--reverse" => cmd.reverse = true,
+            "--not" => negated = !negated,
+            "--all" => cmd.tips.extend(all_tips(negated)?),
+            "-n" | "--max-count" => {
+                let value = iter.next().ok_or_else(|| CommandParseError::MissingArgument(format!("option `{}' requires a value", arg)))?;
+                cmd.max_count = Some(parse_count(value)?);
+            }
+            x if x.starts_with("--max-count=") => cmd.max_count = Some(parse_count(&x["--max-count=".len()..])?),
+            x if x.starts_with("-n") => cmd.max_count = Some(parse_count(&x[2..])?),
+            x if x.len() > 1 && x.starts_with('-') && x[1..].bytes().all(|c| c.is_ascii_digit()) => cmd.max_count = Some(parse_count(&x[1..])?),
+            x if cmd.walk.parse_option(x)? => {}
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            // like git, a name is a revision if it resolves, else it must be a path
+            other if cmd.paths.is_empty() => match parse_revision_arg(other, negated)? {
+                Some(tips) => {
+                    for tip in &tips {
+                        if !tip.hidden && read_object(&tip.hash.to_hex())?.0 == ObjectType::Tag {
+                            cmd.tags.push((tip.hash.clone(), other.to_string()));
+                        }
+                    }
+                    cmd.tips.extend(tips);
+                }
+                None if Path::new(other).exists() => cmd.paths.push(other.to_string()),
+                None => {
+                    return Err(CommandParseError::InvalidArgument(format!("ambiguous argument '{}': unknown revision or path not in the working tree.", other)).into());
+                }
+            },
+            other => cmd.paths.push(other.to_string()),
+        }
+    }
+
+    if cmd.tips.is_empty() {
+        return Err(CommandParseError::MissingArgument("usage: git rev-list [<options>] <commit>... [--] [<path>...]".to_string()).into());
+    }
+    Ok(cmd)
+}
+
+fn parse_count(value: &str) -> io::Result<usize> {
+    value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("'{}' is not a number", value)).into())
+}
@@ -0,0 +1,184 @@
+use std::{env, io::{self, Write}, path::Path};
+
+use crate::{
+    commands::parse_error::CommandParseError,
+    constants::GIT_DIR,
+    hash::GitHash,
+    objects::unique_abbrev,
+    refs::{resolve_revision, revision::{dwim_ref, shorten_unambiguous_ref}},
+    revwalk::merge_bases,
+};
+
+
+/// The fewest digits `--short` abbreviates to.
+const DEFAULT_ABBREV: usize = 7;
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let mut out = io::stdout().lock();
+
+    if cmd.verify {
+        let hash = match verify(&cmd) {
+            Ok(hash) => hash,
+            Err(_) if cmd.quiet => std::process::exit(1),
+            Err(e) => return Err(e),
+        };
+        writeln!(out, "{}", cmd.format(&hash)?)?;
+        return out.flush();
+    }
+
+    for arg in &cmd.args {
+        match arg {
+            Arg::ShowToplevel => writeln!(out, "{}", env::current_dir()?.canonicalize()?.display())?,
+            Arg::GitDir => writeln!(out, "{}", GIT_DIR)?,
+            Arg::Path(path) => writeln!(out, "{}", path)?,
+            Arg::Revision(arg) => {
+                if let Some(lines) = show_range(&cmd, arg)? {
+                    for line in lines {
+                        writeln!(out, "{}", line)?;
+                    }
+                    continue;
+                }
+                let (negated, spec) = match arg.strip_prefix('^') {
+                    Some(rest) => (true, rest),
+                    None => (false, arg.as_str()),
+                };
+                match resolve_revision(spec) {
+                    Ok(Some(hash)) => {
+                        if let Some(shown) = cmd.show(spec, &hash)? {
+                            writeln!(out, "{}{}", if negated { "^" } else { "" }, shown)?;
+                        }
+                    }
+                    result => {
+                        // like git, the argument is echoed before failing,
+                        // unless a reflog or upstream lookup failed outright
+                        if !spec.contains("@{") {
+                            writeln!(out, "{}", arg)?;
+                            out.flush()?;
+                        }
+                        result?;
+                        return Err(CommandParseError::InvalidArgument(format!("ambiguous argument '{}': unknown revision or path not in the working tree.", arg)).into());
+                    }
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+/// `--verify`: the one revision given, which must name an object.
+fn verify(cmd: &RevParseCommand) -> io::Result<GitHash> {
+    let needed = || io::Error::new(io::ErrorKind::InvalidInput, "Needed a single revision");
+    let [Arg::Revision(spec)] = cmd.args.as_slice() else { return Err(needed()) };
+    resolve_revision(spec)?.ok_or_else(needed)
+}
+
+/// `A..B` as `B ^A`, and `A...B` as `B A` and `^` each merge base. `None` if
+/// the argument isn't a range.
+fn show_range(cmd: &RevParseCommand, spec: &str) -> io::Result<Option<Vec<String>>> {
+    let (from, to, symmetric) = match spec.split_once("...") {
+        Some((from, to)) => (from, to, true),
+        None => match spec.split_once("..") {
+            Some((from, to)) => (from, to, false),
+            None => return Ok(None),
+        },
+    };
+    let from = if from.is_empty() { "HEAD" } else { from };
+    let to = if to.is_empty() { "HEAD" } else { to };
+    let (Some(from_hash), Some(to_hash)) = (resolve_revision(from)?, resolve_revision(to)?) else { return Ok(None) };
+
+    let mut lines = Vec::new();
+    lines.extend(cmd.show(to, &to_hash)?);
+    if symmetric {
+        lines.extend(cmd.show(from, &from_hash)?);
+        for base in merge_bases(&from_hash, &to_hash)? {
+            lines.push(format!("^{}", cmd.format(&base)?));
+        }
+    } else if let Some(shown) = cmd.show(from, &from_hash)? {
+        lines.push(format!("^{}", shown));
+    }
+    Ok(Some(lines))
+}
+
+/// One argument, kept in order since options like `--git-dir` print where
+/// they appear.
+enum Arg {
+    Revision(String),
+    /// A name that isn't a revision but exists in the working tree, or
+    /// `--` and anything after it.
+    Path(String),
+    ShowToplevel,
+    GitDir,
+}
+
+pub struct RevParseCommand {
+    args: Vec<Arg>,
+    /// `--verify`: exactly one revision, which must exist.
+    verify: bool,
+    /// `-q`: with `--verify`, fail silently.
+    quiet: bool,
+    /// `--short[=<n>]`: abbreviate ids to at least this many digits.
+    short: Option<usize>,
+    /// `--abbrev-ref`: show refs by their shortest unambiguous name.
+    abbrev_ref: bool,
+}
+
+impl RevParseCommand {
+    /// How a revision is printed; `--abbrev-ref` prints nothing for one
+    /// that isn't a ref.
+    fn show(&self, spec: &str, hash: &GitHash) -> io::Result<Option<String>> {
+        if self.abbrev_ref {
+            return match dwim_ref(spec)? {
+                Some(full) => shorten_unambiguous_ref(&full).map(Some),
+                None => Ok(None),
+            };
+        }
+        self.format(hash).map(Some)
+    }
+
+    fn format(&self, hash: &GitHash) -> io::Result<String> {
+        match self.short {
+            Some(min) => unique_abbrev(hash, min),
+            None => Ok(hash.to_hex()),
+        }
+    }
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<RevParseCommand> {
+    let mut cmd = RevParseCommand { args: Vec::new(), verify: false, quiet: false, short: None, abbrev_ref: false };
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => cmd.args.push(Arg::Path(arg.clone())),
+            "--" => {
+                only_paths = true;
+                cmd.args.push(Arg::Path(arg.clone()));
+            }
+            "--verify" => cmd.verify = true,
+            "-q" | "--quiet" => cmd.quiet = true,
+            "--short" => {
+                cmd.short = Some(DEFAULT_ABBREV);
+                cmd.verify = true;
+            }
+            x if x.starts_with("--short=") => {
+                let value = &x["--short=".len()..];
+                let n: usize = value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("'{}' is not a number", value)))?;
+                // git never abbreviates below 4 digits
+                cmd.short = Some(n.clamp(4, 40));
+                cmd.verify = true;
+            }
+            "--abbrev-ref" | "--abbrev-ref=strict" | "--abbrev-ref=loose" => cmd.abbrev_ref = true,
+            "--show-toplevel" => cmd.args.push(Arg::ShowToplevel),
+            "--git-dir" => cmd.args.push(Arg::GitDir),
+            x if x.starts_with('-') && x.len() > 1 => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other if !cmd.verify && !other.starts_with('^') && !other.contains("..") && Path::new(other).exists() && resolve_revision(other).ok().flatten().is_none() => {
+                cmd.args.push(Arg::Path(other.to_string()));
+            }
+            other => cmd.args.push(Arg::Revision(other.to_string())),
+        }
+    }
+    Ok(cmd)
+}
@@ -0,0 +1,402 @@
+use std::io::{self, Write};
+
+use crate::{commands::parse_error::CommandParseError, config::git_config::GitConfig, hash::GitHash, index::index_entry::IndexEntry, objects::FileMode, pathspec::PathSpec, refs::Head, utils::path_utils::{quote_path, quote_path_with_spaces}, worktree::status::{collect_status, ChangeKind, ChangedPath, Status, UnmergedPath, UntrackedMode}};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let config = GitConfig::load()?;
+    let status = collect_status(&cmd.pathspec, cmd.untracked, &config)?;
+    let mut out = io::stdout().lock();
+
+    match cmd.format {
+        StatusFormat::Long => write_long(&mut out, &status, false)?,
+        StatusFormat::Short | StatusFormat::PorcelainV1 => write_short(&mut out, &status, &cmd)?,
+        StatusFormat::PorcelainV2 => write_porcelain_v2(&mut out, &status, &cmd)?,
+    }
+
+    out.flush()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFormat {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+}
+
+pub struct StatusCommand {
+    format: StatusFormat,
+    /// `-b`: add the branch header to the short and porcelain formats.
+    branch: bool,
+    nul_terminated: bool,
+    untracked: UntrackedMode,
+    pathspec: PathSpec,
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<StatusCommand> {
+    let mut format = None;
+    let mut branch = false;
+    let mut nul_terminated = false;
+    let mut untracked = UntrackedMode::Normal;
+    let mut paths = Vec::new();
+    let mut only_paths = false;
+
+    for arg in args {
+        match arg.as_str() {
+            _ if only_paths => paths.push(arg.clone()),
+            "--" => only_paths = true,
+            "-s" | "--short" => format = Some(StatusFormat::Short),
+            "--long" => format = Some(StatusFormat::Long),
+            "--porcelain" | "--porcelain=v1" | "--porcelain=1" => format = Some(StatusFormat::PorcelainV1),
+            "--porcelain=v2" | "--porcelain=2" => format = Some(StatusFormat::PorcelainV2),
+            "-b" | "--branch" => branch = true,
+            "-z" => nul_terminated = true,
+            "-u" | "--untracked-files" => untracked = UntrackedMode::All,
+            x if x.starts_with("--porcelain=") => {
+                return Err(CommandParseError::InvalidArgument(format!("unsupported porcelain version '{}'", &x["--porcelain=".len()..])).into());
+            }
+            x if x.starts_with("--untracked-files=") => untracked = parse_untracked_mode(&x["--untracked-files=".len()..])?,
+            // bundled short flags, like `-sb`; `-u` takes the rest as its mode
+            x if x.starts_with('-') && !x.starts_with("--") && x.len() > 2 => {
+                for (i, flag) in x[1..].char_indices() {
+                    match flag {
+                        's' => format = Some(StatusFormat::Short),
+                        'b' => branch = true,
+                        'z' => nul_terminated = true,
+                        'u' => {
+                            let value = &x[i + 2..];
+                            untracked = if value.is_empty() { UntrackedMode::All } else { parse_untracked_mode(value)? };
+                            break;
+                        }
+                        _ => return Err(CommandParseError::UnknownFlag(x.to_string()).into()),
+                    }
+                }
+            }
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => paths.push(other.to_string()),
+        }
+    }
+
+    // like git, -z alone implies the v1 porcelain format
+    let format = match format {
+        Some(format) => format,
+        None if nul_terminated => StatusFormat::PorcelainV1,
+        None => StatusFormat::Long,
+    };
+    if nul_terminated && format == StatusFormat::Long {
+        return Err(CommandParseError::InvalidArgument("-z cannot be used with --long".to_string()).into());
+    }
+
+    Ok(StatusCommand {
+        format,
+        branch,
+        nul_terminated,
+        untracked,
+        pathspec: PathSpec::parse(&paths)?,
+    })
+}
+
+fn parse_untracked_mode(value: &str) -> io::Result<UntrackedMode> {
+    match value {
+        "no" => Ok(UntrackedMode::No),
+        "normal" => Ok(UntrackedMode::Normal),
+        "all" => Ok(UntrackedMode::All),
+        other => Err(CommandParseError::InvalidArgument(format!("Invalid untracked files mode '{}'", other)).into()),
+    }
+}
+
+impl StatusCommand {
+    fn terminator(&self) -> char {
+        if self.nul_terminated { '\0' } else { '\n' }
+    }
+
+    /// Paths are quoted like git's unless they end in NUL; the short and v1
+    /// formats quote spaces too.
+    fn display_path(&self, path: &str) -> String {
+        match self.format {
+            _ if self.nul_terminated => path.to_string(),
+            StatusFormat::Short | StatusFormat::PorcelainV1 => quote_path_with_spaces(path),
+            _ => quote_path(path),
+        }
+    }
+
+    /// A changed path for the short and v1 formats: `orig -> path` for a
+    /// rename or copy, or the new path then the old one with `-z`.
+    fn display_rename(&self, change: &ChangedPath) -> String {
+        match &change.orig_path {
+            Some(orig) if self.nul_terminated => format!("{}\0{}", change.path, orig),
+            Some(orig) => format!("{} -> {}", self.display_path(orig), self.display_path(&change.path)),
+            None => self.display_path(&change.path),
+        }
+    }
+}
+
+/// Writes git's human readable status. With `for_commit`, every line is
+/// commented out and the hints are left out, for the commit message template.
+pub fn write_long<W: Write>(out: &mut W, status: &Status, for_commit: bool) -> io::Result<()> {
+    let mut lines: Vec<String> = Vec::new();
+    let hint = |lines: &mut Vec<String>, text: &str| {
+        if !for_commit {
+            lines.push(format!("  ({})", text));
+        }
+    };
+
+    match &status.head {
+        Head::Branch(_) => lines.push(format!("On branch {}", status.head.branch_name().unwrap_or_default())),
+        Head::Detached(hash) => lines.push(format!("HEAD detached at {}", hash.abbrev(7))),
+    }
+
+    if let Some(upstream) = &status.upstream {
+        match upstream.ahead_behind {
+            None => {
+                lines.push(format!("Your branch is based on '{}', but the upstream is gone.", upstream.name));
+                hint(&mut lines, "use \"git branch --unset-upstream\" to fixup");
+            }
+            Some((0, 0)) => lines.push(format!("Your branch is up to date with '{}'.", upstream.name)),
+            Some((ahead, 0)) => {
+                lines.push(format!("Your branch is ahead of '{}' by {}.", upstream.name, commits(ahead)));
+                hint(&mut lines, "use \"git push\" to publish your local commits");
+            }
+            Some((0, behind)) => {
+                lines.push(format!("Your branch is behind '{}' by {}, and can be fast-forwarded.", upstream.name, commits(behind)));
+                hint(&mut lines, "use \"git pull\" to update your local branch");
+            }
+            Some((ahead, behind)) => {
+                lines.push(format!("Your branch and '{}' have diverged,", upstream.name));
+                lines.push(format!("and have {} and {} different commits each, respectively.", ahead, behind));
+                hint(&mut lines, "use \"git pull\" if you want to integrate the remote branch with yours");
+            }
+        }
+        lines.push(String::new());
+    }
+
+    if status.head_commit.is_none() {
+        lines.push(String::new());
+        lines.push("No commits yet".to_string());
+        lines.push(String::new());
+    }
+
+    // every section ends with a blank line
+    let staged: Vec<&ChangedPath> = status.changes.iter().filter(|c| c.staged.is_some()).collect();
+    if !staged.is_empty() {
+        lines.push("Changes to be committed:".to_string());
+        if status.head_commit.is_some() {
+            hint(&mut lines, "use \"git restore --staged <file>...\" to unstage");
+        } else {
+            hint(&mut lines, "use \"git rm --cached <file>...\" to unstage");
+        }
+        for change in staged {
+            let kind = change.staged.unwrap_or(ChangeKind::Modified);
+            let path = match &change.orig_path {
+                Some(orig) => format!("{} -> {}", quote_path(orig), quote_path(&change.path)),
+                None => quote_path(&change.path),
+            };
+            lines.push(format!("\t{:<12}{}", format!("{}:", kind.label()), path));
+        }
+        lines.push(String::new());
+    }
+
+    if !status.unmerged.is_empty() {
+        lines.push("Unmerged paths:".to_string());
+        if status.unmerged.iter().any(|u| u.code().contains('D')) {
+            hint(&mut lines, "use \"git add/rm <file>...\" as appropriate to mark resolution");
+        } else {
+            hint(&mut lines, "use \"git add <file>...\" to mark resolution");
+        }
+        for unmerged in &status.unmerged {
+            lines.push(format!("\t{:<17}{}", format!("{}:", unmerged.label()), quote_path(&unmerged.path)));
+        }
+        lines.push(String::new());
+    }
+
+    let unstaged: Vec<&ChangedPath> = status.changes.iter().filter(|c| c.unstaged.is_some()).collect();
+    if !unstaged.is_empty() {
+        lines.push("Changes not staged for commit:".to_string());
+        if unstaged.iter().any(|c| c.unstaged == Some(ChangeKind::Deleted)) {
+            hint(&mut lines, "use \"git add/rm <file>...\" to update what will be committed");
+        } else {
+            hint(&mut lines, "use \"git add <file>...\" to update what will be committed");
+        }
+        hint(&mut lines, "use \"git restore <file>...\" to discard changes in working directory");
+        for change in unstaged {
+            let kind = change.unstaged.unwrap_or(ChangeKind::Modified);
+            lines.push(format!("\t{:<12}{}", format!("{}:", kind.label()), quote_path(&change.path)));
+        }
+        lines.push(String::new());
+    }
+
+    if !status.untracked.is_empty() {
+        lines.push("Untracked files:".to_string());
+        hint(&mut lines, "use \"git add <file>...\" to include in what will be committed");
+        for path in &status.untracked {
+            lines.push(format!("\t{}", quote_path(path)));
+        }
+        lines.push(String::new());
+    } else if status.untracked_mode == UntrackedMode::No && status.has_staged() {
+        lines.push("Untracked files not listed (use -u option to show untracked files)".to_string());
+    }
+
+    if for_commit {
+        for line in lines {
+            if line.is_empty() || line.starts_with('\t') {
+                writeln!(out, "#{}", line)?;
+            } else {
+                writeln!(out, "# {}", line)?;
+            }
+        }
+        return Ok(());
+    }
+
+    if !status.has_staged() {
+        lines.push(if status.has_unstaged() || !status.unmerged.is_empty() {
+            "no changes added to commit (use \"git add\" and/or \"git commit -a\")".to_string()
+        } else if !status.untracked.is_empty() {
+            "nothing added to commit but untracked files present (use \"git add\" to track)".to_string()
+        } else if status.head_commit.is_none() {
+            "nothing to commit (create/copy files and use \"git add\" to track)".to_string()
+        } else if status.untracked_mode == UntrackedMode::No {
+            "nothing to commit (use -u to show untracked files)".to_string()
+        } else {
+            "nothing to commit, working tree clean".to_string()
+        });
+    }
+
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn commits(n: usize) -> String {
+    if n == 1 { "1 commit".to_string() } else { format!("{} commits", n) }
+}
+
+fn write_short<W: Write>(out: &mut W, status: &Status, cmd: &StatusCommand) -> io::Result<()> {
+    if cmd.branch {
+        write!(out, "## {}{}", branch_header(status), cmd.terminator())?;
+    }
+
+    for (_, line) in tracked_lines(status, |change| {
+        let x = change.staged.map_or(' ', ChangeKind::code);
+        let y = change.unstaged.map_or(' ', ChangeKind::code);
+        format!("{}{} {}", x, y, cmd.display_rename(change))
+    }, |unmerged| format!("{} {}", unmerged.code(), cmd.display_path(&unmerged.path))) {
+        write!(out, "{}{}", line, cmd.terminator())?;
+    }
+
+    for path in &status.untracked {
+        write!(out, "?? {}{}", cmd.display_path(path), cmd.terminator())?;
+    }
+    Ok(())
+}
+
+fn branch_header(status: &Status) -> String {
+    let name = match &status.head {
+        Head::Branch(_) => status.head.branch_name().unwrap_or_default().to_string(),
+        Head::Detached(_) => return "HEAD (no branch)".to_string(),
+    };
+    if status.head_commit.is_none() {
+        return format!("No commits yet on {}", name);
+    }
+
+    let Some(upstream) = &status.upstream else {
+        return name;
+    };
+    let counts = match upstream.ahead_behind {
+        None => " [gone]".to_string(),
+        Some((0, 0)) => String::new(),
+        Some((ahead, 0)) => format!(" [ahead {}]", ahead),
+        Some((0, behind)) => format!(" [behind {}]", behind),
+        Some((ahead, behind)) => format!(" [ahead {}, behind {}]", ahead, behind),
+    };
+    format!("{}...{}{}", name, upstream.name, counts)
+}
+
+/// Formats changed and unmerged paths together, in path order.
+fn tracked_lines<C, U>(status: &Status, changed: C, unmerged: U) -> Vec<(String, String)>
+where
+    C: Fn(&ChangedPath) -> String,
+    U: Fn(&UnmergedPath) -> String,
+{
+    let mut lines: Vec<(String, String)> = status
+        .changes
+        .iter()
+        .map(|c| (c.path.clone(), changed(c)))
+        .chain(status.unmerged.iter().map(|u| (u.path.clone(), unmerged(u))))
+        .collect();
+    lines.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    lines
+}
+
+fn write_porcelain_v2<W: Write>(out: &mut W, status: &Status, cmd: &StatusCommand) -> io::Result<()> {
+    if cmd.branch {
+        let oid = status.head_commit.as_ref().map_or("(initial)".to_string(), GitHash::to_hex);
+        write!(out, "# branch.oid {}{}", oid, cmd.terminator())?;
+        write!(out, "# branch.head {}{}", status.head.branch_name().unwrap_or("(detached)"), cmd.terminator())?;
+        if let Some(upstream) = &status.upstream {
+            write!(out, "# branch.upstream {}{}", upstream.name, cmd.terminator())?;
+            if let Some((ahead, behind)) = upstream.ahead_behind {
+                write!(out, "# branch.ab +{} -{}{}", ahead, behind, cmd.terminator())?;
+            }
+        }
+    }
+
+    for (_, line) in tracked_lines(status, |change| {
+        let fields = format!(
+            "{}{} N... {} {} {} {} {}",
+            change.staged.map_or('.', ChangeKind::code),
+            change.unstaged.map_or('.', ChangeKind::code),
+            entry_mode(change.head.as_ref()),
+            entry_mode(change.index.as_ref()),
+            mode_octal(change.worktree_mode.as_ref()),
+            entry_hash(change.head.as_ref()),
+            entry_hash(change.index.as_ref()),
+        );
+        // renames and copies add their score and the original path
+        match &change.orig_path {
+            Some(orig) => {
+                let separator = if cmd.nul_terminated { '\0' } else { '\t' };
+                let code = change.staged.map_or('R', ChangeKind::code);
+                format!("2 {} {}{} {}{}{}", fields, code, change.similarity, cmd.display_path(&change.path), separator, cmd.display_path(orig))
+            }
+            None => format!("1 {} {}", fields, cmd.display_path(&change.path)),
+        }
+    }, |unmerged| {
+        let [base, ours, theirs] = &unmerged.stages;
+        format!(
+            "u {} N... {} {} {} {} {} {} {} {}",
+            unmerged.code(),
+            entry_mode(base.as_ref()),
+            entry_mode(ours.as_ref()),
+            entry_mode(theirs.as_ref()),
+            mode_octal(unmerged.worktree_mode.as_ref()),
+            entry_hash(base.as_ref()),
+            entry_hash(ours.as_ref()),
+            entry_hash(theirs.as_ref()),
+            cmd.display_path(&unmerged.path),
+        )
+    }) {
+        write!(out, "{}{}", line, cmd.terminator())?;
+    }
+
+    for path in &status.untracked {
+        write!(out, "? {}{}", cmd.display_path(path), cmd.terminator())?;
+    }
+    Ok(())
+}
+
+fn mode_octal(mode: Option<&FileMode>) -> String {
+    format!("{:06o}", mode.map_or(0, |m| m.clone() as u32))
+}
+
+fn entry_mode(entry: Option<&IndexEntry>) -> String {
+    mode_octal(entry.map(|e| &e.mode))
+}
+
+fn entry_hash(entry: Option<&IndexEntry>) -> String {
+    entry.map_or_else(|| GitHash::zero().to_hex(), |e| e.hash.to_hex())
+}
@@ -0,0 +1,225 @@
+use std::{fs, io::{self, BufRead}, path::Path};
+
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, hash::GitHash, index::{index::{load_index, Index, IndexLock}, index_entry::IndexEntry}, objects::{hash_object, write_object_from_path, FileMode, ObjectType}, utils::{file_utils::read_worktree_blob, path_utils::to_repo_path}, worktree::file_state::{check_entry, FileState}};
+
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let ops = parse_command(args)?;
+    let index_path = Path::new(INDEX_PATH);
+    let lock = IndexLock::acquire(index_path)?;
+    let mut entries = load_index(index_path)?;
+    let mut needs_update = false;
+
+    for op in ops {
+        match op {
+            Operation::Path(path, opts) => update_path(&mut entries, &path, &opts)?,
+            Operation::CacheInfo(entry, add) => {
+                if !add && !entries.contains(&entry.path) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: cannot add to the index - missing --add option?", entry.path)));
+                }
+                entries.add(entry);
+            }
+            Operation::IndexInfo => read_index_info(&mut entries)?,
+            Operation::Refresh(quiet) => needs_update |= !refresh(&entries, quiet)?,
+        }
+    }
+
+    lock.commit(&entries)?;
+
+    if needs_update {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Options that apply to the paths following them on the command line, as in git.
+#[derive(Debug, Clone, Default)]
+pub struct PathOptions {
+    add: bool,
+    remove: bool,
+    force_remove: bool,
+    info_only: bool,
+    chmod: Option<FileMode>,
+    verbose: bool,
+}
+
+#[derive(Debug)]
+pub enum Operation {
+    Path(String, PathOptions),
+    /// The entry, and whether `--add` allows it to create a new path.
+    CacheInfo(IndexEntry, bool),
+    IndexInfo,
+    Refresh(bool),
+}
+
+pub fn parse_command(args: &[String]) -> io::Result<Vec<Operation>> {
+    let mut opts = PathOptions::default();
+    let mut quiet = false;
+    let mut ops = Vec::new();
+    let mut only_paths = false;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            _ if only_paths => ops.push(Operation::Path(to_repo_path(arg)?, opts.clone())),
+            "--" => only_paths = true,
+            "--add" => opts.add = true,
+            "--remove" => opts.remove = true,
+            "--force-remove" => opts.force_remove = true,
+            "--info-only" => opts.info_only = true,
+            "--verbose" => opts.verbose = true,
+            "-q" => quiet = true,
+            "--chmod=+x" => opts.chmod = Some(FileMode::Executable),
+            "--chmod=-x" => opts.chmod = Some(FileMode::Normal),
+            "--refresh" | "--really-refresh" => ops.push(Operation::Refresh(quiet)),
+            "--index-info" => ops.push(Operation::IndexInfo),
+            "--cacheinfo" => {
+                let value = iter.next().ok_or_else(|| CommandParseError::MissingArgument("--cacheinfo <mode>,<sha1>,<path>".to_string()))?;
+                let (mode, hash, path) = if value.contains(',') {
+                    let mut parts = value.splitn(3, ',');
+                    let mode = parts.next().unwrap_or("");
+                    let hash = parts.next().unwrap_or("");
+                    let path = parts.next().unwrap_or("");
+                    (mode.to_string(), hash.to_string(), path.to_string())
+                } else {
+                    // legacy three argument form: --cacheinfo <mode> <sha1> <path>
+                    let hash = iter.next().cloned().unwrap_or_default();
+                    let path = iter.next().cloned().unwrap_or_default();
+                    (value.clone(), hash, path)
+                };
+                ops.push(Operation::CacheInfo(parse_cache_info(&mode, &hash, &path)?, opts.add));
+            }
+            x if x.starts_with("--chmod=") => {
+                return Err(CommandParseError::InvalidArgument(format!("option 'chmod' expects \"+x\" or \"-x\", got '{}'", x)).into());
+            }
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => ops.push(Operation::Path(to_repo_path(other)?, opts.clone())),
+        }
+    }
+
+    Ok(ops)
+}
+
+fn parse_cache_info(mode: &str, hash: &str, path: &str) -> io::Result<IndexEntry> {
+    let file_mode = FileMode::from_octal_str(mode)
+        .filter(|m| *m != FileMode::Directory)
+        .ok_or_else(|| CommandParseError::InvalidArgument(format!("git update-index: --cacheinfo cannot add {}", path)))?;
+    if path.is_empty() {
+        return Err(CommandParseError::MissingArgument("--cacheinfo <mode>,<sha1>,<path>".to_string()).into());
+    }
+    Ok(IndexEntry::new(file_mode, to_repo_path(path)?, GitHash::from_hex(hash)?))
+}
+
+fn update_path(entries: &mut Index, path: &str, opts: &PathOptions) -> io::Result<()> {
+    if opts.force_remove {
+        remove_entry(entries, path, opts);
+        return Ok(());
+    }
+
+    let tracked = entries.contains(path);
+    let exists = fs::symlink_metadata(path).map(|m| !m.is_dir()).unwrap_or(false);
+
+    if !exists {
+        if opts.remove && tracked {
+            remove_entry(entries, path, opts);
+            return Ok(());
+        }
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: does not exist and --remove not passed", path)));
+    }
+
+    if !tracked && !opts.add {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: cannot add to the index - missing --add option?", path)));
+    }
+
+    let hash = if opts.info_only {
+        hash_object(ObjectType::Blob, &read_worktree_blob(path)?).0
+    } else {
+        write_object_from_path(ObjectType::Blob, Path::new(path))?
+    };
+
+    let mut mode = FileMode::from_path(Path::new(path))?;
+    if let Some(chmod) = &opts.chmod {
+        if mode == FileMode::Symlink {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("git update-index: cannot chmod {}x '{}'", if *chmod == FileMode::Executable { "+" } else { "-" }, path)));
+        }
+        mode = chmod.clone();
+    }
+
+    if opts.verbose {
+        println!("add '{}'", path);
+    }
+    entries.add(IndexEntry::new(mode, path.to_string(), hash));
+    Ok(())
+}
+
+fn remove_entry(entries: &mut Index, path: &str, opts: &PathOptions) {
+    if opts.verbose {
+        println!("remove '{}'", path);
+    }
+    entries.remove(path);
+}
+
+/// Applies `--index-info` lines from stdin. Accepts the output formats of
+/// `ls-tree` (`mode type sha1\tpath`), `ls-files -s` (`mode sha1 stage\tpath`)
+/// and plain `mode sha1\tpath`; mode 0 removes the path.
+fn read_index_info(entries: &mut Index) -> io::Result<()> {
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("malformed index info {}", line));
+        let (meta, path) = line.split_once('\t').ok_or_else(invalid)?;
+        let fields: Vec<&str> = meta.split_whitespace().collect();
+
+        let (mode, hash, stage) = match fields.as_slice() {
+            [mode, hash] => (*mode, *hash, "0"),
+            [mode, kind, hash] if ObjectType::from_str(kind) != ObjectType::Unknown(ObjectType::UNKNOWN_FROM_STR_SENTINEL) => (*mode, *hash, "0"),
+            [mode, hash, stage] => (*mode, *hash, *stage),
+            _ => return Err(invalid()),
+        };
+
+        let path = to_repo_path(path)?;
+        if mode == "0" {
+            entries.remove(&path);
+            continue;
+        }
+
+        let stage = match stage.parse::<u8>() {
+            Ok(stage) if stage <= 3 => stage,
+            _ => return Err(invalid()),
+        };
+
+        entries.add(parse_cache_info(mode, hash, &path)?.with_stage(stage));
+    }
+
+    Ok(())
+}
+
+/// Reports entries whose working tree file no longer matches; returns true if all are up to date.
+fn refresh(entries: &Index, quiet: bool) -> io::Result<bool> {
+    let mut up_to_date = true;
+    let mut last_unmerged: Option<&str> = None;
+    for entry in entries {
+        if entry.is_unmerged() {
+            if last_unmerged != Some(entry.path.as_str()) {
+                up_to_date = false;
+                if !quiet {
+                    println!("{}: needs merge", entry.path);
+                }
+            }
+            last_unmerged = Some(&entry.path);
+            continue;
+        }
+        if check_entry(entry)? != FileState::Unchanged {
+            up_to_date = false;
+            if !quiet {
+                println!("{}: needs update", entry.path);
+            }
+        }
+    }
+    Ok(up_to_date || quiet)
+}
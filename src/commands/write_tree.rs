@@ -1,75 +1,124 @@
-use std::{collections::HashSet, io, path::Path};
+use std::{io, path::Path};
 
-use crate::{commands::add, constants::INDEX_PATH, hash::GitHash, index::{index::read_index, index_entry::IndexEntry}, objects::{write_object, FileMode, ObjectType}};
+use crate::{commands::parse_error::CommandParseError, constants::INDEX_PATH, hash::GitHash, index::{index::{load_index, Index}, index_entry::IndexEntry}, objects::{object_exists, serialize_tree, tree_entry_cmp, write_object, FileMode, ObjectType, TreeEntry}, utils::path_utils::to_repo_path};
 
 
+pub fn run(args: &[String]) -> io::Result<()> {
+    let cmd = parse_command(args)?;
+    let index = load_index(Path::new(INDEX_PATH))?;
 
-pub fn run() -> io::Result<()> {
-    // TODO delete this code once done with the codecrafters challenge.
-    // we have shifted away from their challenge and can do a proper write-tree reading from the staging.
-    add::run(&vec![".".to_string()])?;
+    if index.has_unmerged() {
+        for entry in index.iter().filter(|e| e.is_unmerged()) {
+            eprintln!("{}: unmerged ({})", entry.path, entry.hash.to_hex());
+        }
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "write-tree: error building trees"));
+    }
 
-    let entries = match read_index(Path::new(INDEX_PATH)) {
-        Ok(vec) => vec,
-        Err(_) => vec![],
+    let prefix = match &cmd.prefix {
+        Some(dir) => format!("{}/", dir),
+        None => String::new(),
     };
 
+    // intent-to-add entries are placeholders and never make it into a tree
+    let entries: Vec<&IndexEntry> = index
+        .entries_under(cmd.prefix.as_deref().unwrap_or(""))
+        .iter()
+        .filter(|e| !e.intent_to_add)
+        .collect();
+
+    if !cmd.missing_ok {
+        if let Some(entry) = entries.iter().find(|e| !object_exists(&e.hash)) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("invalid object {} {} for '{}'", entry.mode.as_str(), entry.hash.to_hex(), entry.path)));
+        }
+    }
+
     if entries.is_empty() {
-        let hash = write_object(ObjectType::Tree, b"")?;
-        println!("{}", hash.to_hex());
-        return Ok(());
+        if let Some(dir) = &cmd.prefix {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("git-write-tree: prefix {}/ not found", dir)));
+        }
     }
 
-    let hash = build_tree(&entries, "")?;
+    let hash = build_tree(&entries, &prefix)?;
     println!("{}", hash.to_hex());
 
     Ok(())
 }
 
-fn build_tree(entries: &[IndexEntry], prefix: &str) -> io::Result<GitHash> {
-    let mut seen_dirs = HashSet::new();
-    let mut result_entries: Vec<(FileMode, String, GitHash)> = Vec::new();
-
-    for entry in entries {
-        if !entry.path.starts_with(prefix) {
-            continue;
-        }
-
-        let rest = &entry.path[prefix.len()..];
+/// Writes the tree the index describes, the way `commit` records it. The
+/// index must not have unmerged entries.
+pub fn write_index_tree(index: &Index) -> io::Result<GitHash> {
+    let entries: Vec<&IndexEntry> = index.iter().filter(|e| !e.intent_to_add).collect();
+    build_tree(&entries, "")
+}
 
-        if let Some(pos) = rest.find('/') {
-            let dirname = &rest[..pos];
-            if seen_dirs.insert(dirname.to_string()) {
-                let full_prefix = format!("{}{}/", prefix, dirname);
-                let sub_entries: Vec<IndexEntry> = entries
-                    .iter()
-                    .filter(|e| e.path.starts_with(&full_prefix))
-                    .cloned()
-                    .collect();
+pub fn parse_command(args: &[String]) -> io::Result<WriteTreeCommand> {
+    let mut cmd = WriteTreeCommand::default();
 
-                let subtree_hash = build_tree(&sub_entries, &full_prefix)?;
-                result_entries.push((FileMode::Directory, dirname.to_string(), subtree_hash));
+    for arg in args {
+        match arg.as_str() {
+            "--missing-ok" => cmd.missing_ok = true,
+            x if x.starts_with("--prefix=") => {
+                let dir = to_repo_path(&x["--prefix=".len()..])?;
+                cmd.prefix = Some(dir).filter(|d| !d.is_empty());
+            }
+            x if x.starts_with('-') => {
+                return Err(CommandParseError::UnknownFlag(x.to_string()).into());
+            }
+            other => {
+                return Err(CommandParseError::InvalidArgument(format!("unexpected argument '{}'", other)).into());
             }
-        } else {
-            result_entries.push((
-                entry.mode.clone(),
-                rest.to_string(),
-                entry.hash.clone(),
-            ));
         }
     }
 
-    // Sort all entries by name (binary order as Git requires)
-    result_entries.sort_by(|a, b| a.1.as_bytes().cmp(b.1.as_bytes()));
+    Ok(cmd)
+}
 
-    // Serialize tree content
-    let mut content = Vec::new();
-    for (mode, name, hash) in result_entries {
-        let mut line = format!("{} {}", mode.as_str(), name).into_bytes();
-        line.push(0);
-        line.extend_from_slice(&hash.as_bytes());
-        content.extend(line);
+#[derive(Default)]
+pub struct WriteTreeCommand {
+    /// `--prefix=<dir>/`: write the tree for this subdirectory only.
+    prefix: Option<String>,
+    /// `--missing-ok`: don't check that the blobs in the index exist.
+    missing_ok: bool,
+}
+
+/// Writes the tree for `entries`, which are in index order and all live below
+/// `prefix` ("" or a directory ending in '/'). Each subdirectory is a contiguous
+/// run of entries, so every entry is looked at once and subtrees are written
+/// before the trees that contain them.
+fn build_tree(entries: &[&IndexEntry], prefix: &str) -> io::Result<GitHash> {
+    let mut children = Vec::new();
+    let mut i = 0;
+
+    while i < entries.len() {
+        let path = &entries[i].path;
+        let rest = &path[prefix.len()..];
+
+        match rest.find('/') {
+            Some(pos) => {
+                let dir_prefix = &path[..prefix.len() + pos + 1];
+                let len = entries[i..].partition_point(|e| e.path.starts_with(dir_prefix));
+                let hash = build_tree(&entries[i..i + len], dir_prefix)?;
+                children.push(TreeEntry {
+                    mode: FileMode::Directory,
+                    object_type: ObjectType::Tree,
+                    hash,
+                    name: rest[..pos].to_string(),
+                });
+                i += len;
+            }
+            None => {
+                children.push(TreeEntry {
+                    mode: entries[i].mode.clone(),
+                    object_type: ObjectType::from_mode(&entries[i].mode),
+                    hash: entries[i].hash.clone(),
+                    name: rest.to_string(),
+                });
+                i += 1;
+            }
+        }
     }
 
-    write_object(ObjectType::Tree, &content)
+    // index order already matches tree order except around file/directory name clashes
+    children.sort_by(|a, b| tree_entry_cmp(&a.name, a.mode == FileMode::Directory, &b.name, b.mode == FileMode::Directory));
+    write_object(ObjectType::Tree, &serialize_tree(&children))
 }
@@ -0,0 +1,197 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use crate::constants::GIT_CONFIG_PATH;
+
+/// Key/value view over git's ini-style config files.
+/// Keys are stored as `section.name` or `section.subsection.name`, with the
+/// section and name lower-cased (subsections are case sensitive, like git).
+#[derive(Debug, Default, Clone)]
+pub struct GitConfig {
+    entries: Vec<(String, String)>,
+}
+
+impl GitConfig {
+    /// Loads the global config followed by the repository config, so that
+    /// repository values take precedence.
+    pub fn load() -> io::Result<Self> {
+        let mut config = GitConfig::default();
+        for path in global_config_paths() {
+            config.merge_file(&path)?;
+        }
+        config.merge_file(Path::new(GIT_CONFIG_PATH))?;
+        Ok(config)
+    }
+
+    pub fn merge_file(&mut self, path: &Path) -> io::Result<()> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                self.entries.extend(parse_config(&content)?);
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the last value set for `key`, which is the one git honors.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize_key(key);
+        self.entries.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        let key = normalize_key(key);
+        self.entries.iter().filter(|(k, _)| *k == key).map(|(_, v)| v.as_str()).collect()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(parse_bool)
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        let value = self.get(key)?.trim();
+        let (digits, factor) = match value.chars().last()?.to_ascii_lowercase() {
+            'k' => (&value[..value.len() - 1], 1024),
+            'm' => (&value[..value.len() - 1], 1024 * 1024),
+            'g' => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+            _ => (value, 1),
+        };
+        digits.parse::<i64>().ok().map(|v| v * factor)
+    }
+
+    /// Returns a path valued key with a leading `~/` expanded to the home directory.
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        self.get(key).map(expand_home)
+    }
+}
+
+/// A key with no `=` is stored as `true`; like git, an empty value (`key =`) is false.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// `$XDG_CONFIG_HOME/git/<name>`, falling back to `~/.config/git/<name>`.
+pub fn xdg_config_path(name: &str) -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(xdg) if !xdg.is_empty() => Some(PathBuf::from(xdg).join("git").join(name)),
+        _ => home_dir().map(|home| home.join(".config").join("git").join(name)),
+    }
+}
+
+fn global_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(xdg) = xdg_config_path("config") {
+        paths.push(xdg);
+    }
+    if let Some(home) = home_dir() {
+        paths.push(home.join(".gitconfig"));
+    }
+    paths
+}
+
+fn normalize_key(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first), Some(last)) if first != last => format!(
+            "{}{}{}",
+            key[..first].to_ascii_lowercase(),
+            &key[first..last],
+            key[last..].to_ascii_lowercase()
+        ),
+        _ => key.to_ascii_lowercase(),
+    }
+}
+
+fn parse_config(content: &str) -> io::Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut section: Option<String> = None;
+
+    for (line_no, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("bad config line {}: {}", line_no + 1, raw));
+
+        if let Some(rest) = line.strip_prefix('[') {
+            let end = rest.find(']').ok_or_else(invalid)?;
+            let header = &rest[..end];
+            section = Some(match header.split_once(|c: char| c.is_whitespace()) {
+                Some((name, sub)) => {
+                    let sub = sub.trim().trim_matches('"').replace("\\\"", "\"").replace("\\\\", "\\");
+                    format!("{}.{}", name.to_ascii_lowercase(), sub)
+                }
+                // deprecated [section.subsection] syntax
+                None => match header.split_once('.') {
+                    Some((name, sub)) => format!("{}.{}", name.to_ascii_lowercase(), sub),
+                    None => header.to_ascii_lowercase(),
+                },
+            });
+
+            let trailing = rest[end + 1..].trim();
+            if trailing.is_empty() || trailing.starts_with('#') || trailing.starts_with(';') {
+                continue;
+            }
+            return Err(invalid());
+        }
+
+        let section = section.as_ref().ok_or_else(invalid)?;
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name.trim(), parse_value(value.trim())),
+            None => (line, "true".to_string()),
+        };
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        entries.push((format!("{}.{}", section, name.to_ascii_lowercase()), value));
+    }
+
+    Ok(entries)
+}
+
+/// Unquotes a value and strips a trailing comment that isn't inside quotes.
+fn parse_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => {
+                    value.pop();
+                }
+                Some(other) => value.push(other),
+                None => {}
+            },
+            '#' | ';' if !in_quotes => break,
+            other => value.push(other),
+        }
+    }
+
+    if in_quotes {
+        value
+    } else {
+        value.trim_end().to_string()
+    }
+}
@@ -4,4 +4,7 @@ pub const GIT_REF_DIR: &str = ".git/refs";
 pub const GIT_HEAD_PATH: &str = ".git/HEAD";
 pub const INDEX_PATH: &str = ".git/index";
 pub const MAX_INDEX_PATH_LEN: usize = u16::MAX as usize;
-
+pub const GIT_CONFIG_PATH: &str = ".git/config";
+pub const GIT_INFO_EXCLUDE_PATH: &str = ".git/info/exclude";
+pub const GITIGNORE_FILE: &str = ".gitignore";
+pub const COMMIT_EDITMSG_PATH: &str = ".git/COMMIT_EDITMSG";
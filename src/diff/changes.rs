@@ -0,0 +1,328 @@
+use std::{cmp::Ordering, io, path::Path};
+
+use crate::{config::git_config::GitConfig, hash::GitHash, index::{index::{entries_from_tree, Index}, index_entry::IndexEntry}, objects::{hash_object, read_object, tree_entry_cmp, FileMode, ObjectType, Tree, TreeEntry}, pathspec::PathSpec, utils::{file_utils::read_worktree_blob, parallel::{parallel_map, worker_count}, path_utils::join_repo_path}, worktree::file_state::{check_entry, FileState}};
+
+/// The status letters of `--name-status` and `--raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Copied,
+    Deleted,
+    Modified,
+    Renamed,
+    TypeChanged,
+    Unmerged,
+}
+
+impl ChangeStatus {
+    pub fn code(self) -> char {
+        match self {
+            ChangeStatus::Added => 'A',
+            ChangeStatus::Copied => 'C',
+            ChangeStatus::Deleted => 'D',
+            ChangeStatus::Modified => 'M',
+            ChangeStatus::Renamed => 'R',
+            ChangeStatus::TypeChanged => 'T',
+            ChangeStatus::Unmerged => 'U',
+        }
+    }
+}
+
+/// One side of a changed path. A zero hash stands for a working tree file
+/// whose content has not been hashed, as in git's raw output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSide {
+    pub mode: FileMode,
+    pub hash: GitHash,
+}
+
+impl DiffSide {
+    pub fn of_entry(entry: &IndexEntry) -> Self {
+        DiffSide { mode: entry.mode.clone(), hash: entry.hash.clone() }
+    }
+
+    pub fn in_worktree(&self) -> bool {
+        self.hash == GitHash::zero()
+    }
+
+    /// The content of this side, read from the object store or the working tree.
+    pub fn load(&self, path: &str) -> io::Result<Vec<u8>> {
+        if self.in_worktree() {
+            read_worktree_blob(path)
+        } else {
+            Ok(read_object(&self.hash.to_hex())?.1)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub status: ChangeStatus,
+    pub path: String,
+    /// The path the file was renamed or copied from, or for `--no-index`
+    /// the first of the two files.
+    pub old_path: Option<String>,
+    /// `None` if the path doesn't exist on that side.
+    pub old: Option<DiffSide>,
+    pub new: Option<DiffSide>,
+    /// How similar a rename or copy is to its source, in percent.
+    pub similarity: u32,
+}
+
+impl FileChange {
+    pub fn new(path: String, old: Option<DiffSide>, new: Option<DiffSide>) -> Self {
+        let status = match (&old, &new) {
+            (None, _) => ChangeStatus::Added,
+            (_, None) => ChangeStatus::Deleted,
+            (Some(o), Some(n)) if kind(&o.mode) != kind(&n.mode) => ChangeStatus::TypeChanged,
+            _ => ChangeStatus::Modified,
+        };
+        FileChange { status, path, old_path: None, old, new, similarity: 0 }
+    }
+
+    fn unmerged(path: String) -> Self {
+        FileChange { status: ChangeStatus::Unmerged, path, old_path: None, old: None, new: None, similarity: 0 }
+    }
+
+    pub fn renamed(status: ChangeStatus, old_path: String, old: DiffSide, path: String, new: Option<DiffSide>, similarity: u32) -> Self {
+        FileChange { status, path, old_path: Some(old_path), old: Some(old), new, similarity }
+    }
+
+    /// The path on the old side, which differs for renames and copies.
+    pub fn source_path(&self) -> &str {
+        self.old_path.as_deref().unwrap_or(&self.path)
+    }
+
+    pub fn is_rename_or_copy(&self) -> bool {
+        matches!(self.status, ChangeStatus::Renamed | ChangeStatus::Copied)
+    }
+}
+
+/// Files, symlinks and trees are different kinds; a change between two of them is a type change.
+fn kind(mode: &FileMode) -> u8 {
+    match mode {
+        FileMode::Symlink => 1,
+        FileMode::Directory => 2,
+        _ => 0,
+    }
+}
+
+/// Compares two trees, either of which may be missing (the empty tree).
+/// Subtrees with equal ids are skipped without being read. Without
+/// `recursive`, changed subtrees are reported as entries of their own.
+pub fn diff_trees(old: Option<&GitHash>, new: Option<&GitHash>, pathspec: &PathSpec, recursive: bool) -> io::Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+    diff_subtrees(old, new, "", pathspec, recursive, &mut changes)?;
+    Ok(changes)
+}
+
+fn load_entries(tree: Option<&GitHash>) -> io::Result<Vec<TreeEntry>> {
+    match tree {
+        Some(hash) => Ok(Tree::load_tree_from_hash(&hash.to_hex())?.entries),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn diff_subtrees(old: Option<&GitHash>, new: Option<&GitHash>, prefix: &str, pathspec: &PathSpec, recursive: bool, changes: &mut Vec<FileChange>) -> io::Result<()> {
+    if old == new {
+        return Ok(());
+    }
+
+    let old_entries = load_entries(old)?;
+    let new_entries = load_entries(new)?;
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_entries.len() || j < new_entries.len() {
+        let order = match (old_entries.get(i), new_entries.get(j)) {
+            (Some(o), Some(n)) => tree_entry_cmp(&o.name, is_tree(o), &n.name, is_tree(n)),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        let (o, n) = match order {
+            Ordering::Less => (old_entries.get(i), None),
+            Ordering::Greater => (None, new_entries.get(j)),
+            Ordering::Equal => (old_entries.get(i), new_entries.get(j)),
+        };
+        if order != Ordering::Greater {
+            i += 1;
+        }
+        if order != Ordering::Less {
+            j += 1;
+        }
+
+        let entry = o.or(n).expect("one side is present");
+        let path = join_repo_path(prefix, &entry.name);
+
+        if is_tree(entry) {
+            if !pathspec.could_match_under(&path) && !pathspec.matches(&path) {
+                continue;
+            }
+            if recursive {
+                diff_subtrees(o.map(|e| &e.hash), n.map(|e| &e.hash), &path, pathspec, recursive, changes)?;
+                continue;
+            }
+        } else if !pathspec.matches(&path) {
+            continue;
+        }
+
+        let (old_side, new_side) = (o.map(tree_side), n.map(tree_side));
+        if old_side != new_side {
+            changes.push(FileChange::new(path, old_side, new_side));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_tree(entry: &TreeEntry) -> bool {
+    entry.object_type == ObjectType::Tree
+}
+
+fn tree_side(entry: &TreeEntry) -> DiffSide {
+    DiffSide { mode: entry.mode.clone(), hash: entry.hash.clone() }
+}
+
+/// Compares a tree with the index (`diff --cached`). Conflicted paths are
+/// reported once, as unmerged.
+pub fn diff_tree_to_index(tree: Option<&GitHash>, index: &Index, pathspec: &PathSpec) -> io::Result<Vec<FileChange>> {
+    let tree_entries = flatten_tree(tree, pathspec)?;
+    let index_entries: Vec<&IndexEntry> = index.iter().filter(|e| pathspec.matches(&e.path)).collect();
+    // intent-to-add entries have nothing staged yet
+    Ok(merge_flat(&tree_entries, &index_entries, |entry| (!entry.intent_to_add).then(|| DiffSide::of_entry(entry))))
+}
+
+/// Compares the index with the working tree (plain `diff`). Files whose
+/// content changed get a zero hash on the new side.
+pub fn diff_index_to_worktree(index: &Index, pathspec: &PathSpec, config: &GitConfig) -> io::Result<Vec<FileChange>> {
+    let entries: Vec<&IndexEntry> = index.iter().filter(|e| pathspec.matches(&e.path)).collect();
+    let sides = worktree_sides(&entries, config)?;
+
+    let mut changes = Vec::new();
+    for (entry, side) in entries.iter().zip(sides) {
+        if entry.is_unmerged() {
+            if changes.last().map_or(true, |c: &FileChange| c.path != entry.path) {
+                changes.push(FileChange::unmerged(entry.path.clone()));
+            }
+            continue;
+        }
+        let old = (!entry.intent_to_add).then(|| DiffSide::of_entry(entry));
+        if old != side {
+            changes.push(FileChange::new(entry.path.clone(), old, side));
+        }
+    }
+    Ok(changes)
+}
+
+/// Compares a tree with the working tree (`diff <commit>`): paths come from
+/// the tree and the index, contents from the working tree.
+pub fn diff_tree_to_worktree(tree: Option<&GitHash>, index: &Index, pathspec: &PathSpec, config: &GitConfig) -> io::Result<Vec<FileChange>> {
+    let tree_entries = flatten_tree(tree, pathspec)?;
+    let index_entries: Vec<&IndexEntry> = index.iter().filter(|e| pathspec.matches(&e.path)).collect();
+    let sides = worktree_sides(&index_entries, config)?;
+
+    let mut worktree = index_entries.iter().zip(sides);
+    Ok(merge_flat(&tree_entries, &index_entries, |_| worktree.next().and_then(|(_, side)| side)))
+}
+
+/// Drops changes whose working tree side only looked modified: porcelain
+/// `diff` checks the content, where plumbing reports the path as changed.
+pub fn drop_unchanged_worktree(changes: Vec<FileChange>) -> io::Result<Vec<FileChange>> {
+    let mut kept = Vec::with_capacity(changes.len());
+    for change in changes {
+        if let (Some(old), Some(new)) = (&change.old, &change.new) {
+            if new.in_worktree() && old.mode == new.mode && !old.in_worktree() && hash_object(ObjectType::Blob, &new.load(&change.path)?).0 == old.hash {
+                continue;
+            }
+        }
+        kept.push(change);
+    }
+    Ok(kept)
+}
+
+/// The working tree state of each index entry: unchanged files keep their
+/// index id, changed ones get a zero hash and deleted ones `None`.
+fn worktree_sides(entries: &[&IndexEntry], config: &GitConfig) -> io::Result<Vec<Option<DiffSide>>> {
+    let states = parallel_map(entries, worker_count(config, entries.len()), |entry| check_entry(entry));
+
+    entries
+        .iter()
+        .zip(states)
+        .map(|(entry, state)| {
+            Ok(match state? {
+                FileState::Deleted => None,
+                FileState::Unchanged => Some(DiffSide::of_entry(entry)),
+                FileState::Modified => Some(DiffSide { mode: FileMode::from_path(Path::new(&entry.path))?, hash: GitHash::zero() }),
+            })
+        })
+        .collect()
+}
+
+/// Every file of a tree, the copy sources of `--find-copies-harder`.
+pub fn tree_files(tree: Option<&GitHash>, pathspec: &PathSpec) -> io::Result<Vec<(String, DiffSide)>> {
+    Ok(flatten_tree(tree, pathspec)?.iter().map(|e| (e.path.clone(), DiffSide::of_entry(e))).collect())
+}
+
+/// Every staged file of the index, the copy sources of `--find-copies-harder`.
+pub fn index_files(index: &Index, pathspec: &PathSpec) -> Vec<(String, DiffSide)> {
+    index
+        .iter()
+        .filter(|e| !e.is_unmerged() && !e.intent_to_add && pathspec.matches(&e.path))
+        .map(|e| (e.path.clone(), DiffSide::of_entry(e)))
+        .collect()
+}
+
+fn flatten_tree(tree: Option<&GitHash>, pathspec: &PathSpec) -> io::Result<Vec<IndexEntry>> {
+    let entries = match tree {
+        Some(hash) => entries_from_tree(hash, "")?.into_entries(),
+        None => Vec::new(),
+    };
+    Ok(entries.into_iter().filter(|e| pathspec.matches(&e.path)).collect())
+}
+
+/// Merges the sorted entries of a tree with those of the index. `side_of` is
+/// called once for every index entry, in order, to get the new side.
+fn merge_flat<F>(tree: &[IndexEntry], index: &[&IndexEntry], mut side_of: F) -> Vec<FileChange>
+where
+    F: FnMut(&IndexEntry) -> Option<DiffSide>,
+{
+    let mut changes: Vec<FileChange> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < tree.len() || j < index.len() {
+        let order = match (tree.get(i), index.get(j)) {
+            (Some(t), Some(e)) => t.path.as_bytes().cmp(e.path.as_bytes()),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+
+        if order == Ordering::Less {
+            changes.push(FileChange::new(tree[i].path.clone(), Some(DiffSide::of_entry(&tree[i])), None));
+            i += 1;
+            continue;
+        }
+
+        let path = &index[j].path;
+        let old = if order == Ordering::Equal { Some(DiffSide::of_entry(&tree[i])) } else { None };
+        if order == Ordering::Equal {
+            i += 1;
+        }
+
+        // all stages of a conflicted path make up a single unmerged change
+        if index[j].is_unmerged() {
+            while j < index.len() && index[j].path == *path {
+                side_of(index[j]);
+                j += 1;
+            }
+            changes.push(FileChange::unmerged(path.clone()));
+            continue;
+        }
+
+        let new = side_of(index[j]);
+        j += 1;
+        if old != new {
+            changes.push(FileChange::new(path.clone(), old, new));
+        }
+    }
+
+    changes
+}
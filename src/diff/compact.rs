@@ -0,0 +1,285 @@
+//! Post-processing of a line diff. Where equal lines make the position of a
+//! block of changes ambiguous, the block is slid to the place a reader
+//! expects: next to a change on the other side if possible, otherwise where
+//! the indentation suggests a natural boundary (git's indent heuristic).
+
+const MAX_INDENT: i32 = 200;
+const MAX_BLANKS: i32 = 20;
+const MAX_SLIDING: usize = 100;
+
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+/// One side of the diff: its lines and which of them are changed.
+pub struct Side<'a, 'b> {
+    pub ids: &'a [u32],
+    pub text: &'a [&'b [u8]],
+    pub changed: &'a mut [bool],
+}
+
+/// A run of changed lines `start..end`, possibly empty. Consecutive groups
+/// are separated by exactly one unchanged line, so the groups of both sides
+/// correspond one to one.
+#[derive(Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+impl Side<'_, '_> {
+    fn is_changed(&self, i: usize) -> bool {
+        self.changed.get(i).copied().unwrap_or(false)
+    }
+
+    fn first_group(&self) -> Group {
+        let mut end = 0;
+        while self.is_changed(end) {
+            end += 1;
+        }
+        Group { start: 0, end }
+    }
+
+    fn next_group(&self, g: &mut Group) -> bool {
+        if g.end == self.changed.len() {
+            return false;
+        }
+        g.start = g.end + 1;
+        g.end = g.start;
+        while self.is_changed(g.end) {
+            g.end += 1;
+        }
+        true
+    }
+
+    fn previous_group(&self, g: &mut Group) -> bool {
+        if g.start == 0 {
+            return false;
+        }
+        g.end = g.start - 1;
+        g.start = g.end;
+        while g.start > 0 && self.is_changed(g.start - 1) {
+            g.start -= 1;
+        }
+        true
+    }
+
+    fn slide_down(&mut self, g: &mut Group) -> bool {
+        if g.end >= self.ids.len() || self.ids[g.start] != self.ids[g.end] {
+            return false;
+        }
+        self.changed[g.start] = false;
+        self.changed[g.end] = true;
+        g.start += 1;
+        g.end += 1;
+        while self.is_changed(g.end) {
+            g.end += 1;
+        }
+        true
+    }
+
+    fn slide_up(&mut self, g: &mut Group) -> bool {
+        if g.start == 0 || self.ids[g.start - 1] != self.ids[g.end - 1] {
+            return false;
+        }
+        g.start -= 1;
+        g.end -= 1;
+        self.changed[g.start] = true;
+        self.changed[g.end] = false;
+        while g.start > 0 && self.is_changed(g.start - 1) {
+            g.start -= 1;
+        }
+        true
+    }
+}
+
+/// Slides the changed groups of `side`, keeping `other` in step with it.
+/// Without the indent heuristic a group that can move stays at its lowest position.
+pub fn compact(side: &mut Side, other: &Side, indent_heuristic: bool) {
+    let mut g = side.first_group();
+    let mut go = other.first_group();
+
+    loop {
+        if g.end != g.start {
+            let mut earliest_end;
+            let mut end_matching_other;
+            let mut size;
+
+            // slide up as far as possible, then down, merging with any group we
+            // run into, until the group stops growing
+            loop {
+                size = g.end - g.start;
+                end_matching_other = None;
+
+                while side.slide_up(&mut g) {
+                    other.previous_group(&mut go);
+                }
+                earliest_end = g.end;
+                if go.end > go.start {
+                    end_matching_other = Some(g.end);
+                }
+
+                while side.slide_down(&mut g) {
+                    other.next_group(&mut go);
+                    if go.end > go.start {
+                        end_matching_other = Some(g.end);
+                    }
+                }
+
+                if size == g.end - g.start {
+                    break;
+                }
+            }
+
+            if g.end == earliest_end {
+                // the group cannot move
+            } else if end_matching_other.is_some() {
+                // line up with the change on the other side
+                while go.end == go.start {
+                    side.slide_up(&mut g);
+                    other.previous_group(&mut go);
+                }
+            } else if indent_heuristic {
+                let best = best_indent_shift(side, g.end, earliest_end, size);
+                while g.end > best {
+                    side.slide_up(&mut g);
+                    other.previous_group(&mut go);
+                }
+            }
+        }
+
+        if !side.next_group(&mut g) {
+            break;
+        }
+        other.next_group(&mut go);
+    }
+}
+
+/// The group end, between its lowest and highest possible position, whose
+/// boundaries score best by indentation.
+fn best_indent_shift(side: &Side, end: usize, earliest_end: usize, size: usize) -> usize {
+    let lowest = earliest_end.max(end.saturating_sub(size + 1)).max(end.saturating_sub(MAX_SLIDING));
+
+    let mut best: Option<(usize, Score)> = None;
+    for shift in lowest..=end {
+        let mut score = Score::default();
+        score.add_split(&measure_split(side.text, shift));
+        score.add_split(&measure_split(side.text, shift - size));
+        if best.as_ref().map_or(true, |(_, b)| score.cmp(b) <= 0) {
+            best = Some((shift, score));
+        }
+    }
+    best.map_or(end, |(shift, _)| shift)
+}
+
+/// Width of the leading whitespace with tabs to multiples of eight, or `None` for a blank line.
+fn indent(line: &[u8]) -> Option<i32> {
+    let mut width = 0;
+    for &c in line {
+        match c {
+            b' ' => width += 1,
+            b'\t' => width += 8 - width % 8,
+            c if c.is_ascii_whitespace() || c == 0x0b => {}
+            _ => return Some(width),
+        }
+        if width >= MAX_INDENT {
+            return Some(MAX_INDENT);
+        }
+    }
+    None
+}
+
+/// The surroundings of a split point between lines `split - 1` and `split`.
+struct SplitMeasurement {
+    end_of_file: bool,
+    /// `None` for a blank line.
+    indent: Option<i32>,
+    pre_blank: i32,
+    pre_indent: Option<i32>,
+    post_blank: i32,
+    post_indent: Option<i32>,
+}
+
+fn measure_split(lines: &[&[u8]], split: usize) -> SplitMeasurement {
+    let end_of_file = split >= lines.len();
+    let line_indent = if end_of_file { None } else { indent(lines[split]) };
+
+    let mut pre_blank = 0;
+    let mut pre_indent = None;
+    for line in lines[..split.min(lines.len())].iter().rev() {
+        pre_indent = indent(line);
+        if pre_indent.is_some() {
+            break;
+        }
+        pre_blank += 1;
+        if pre_blank == MAX_BLANKS {
+            pre_indent = Some(0);
+            break;
+        }
+    }
+
+    let mut post_blank = 0;
+    let mut post_indent = None;
+    for line in lines.iter().skip(split + 1) {
+        post_indent = indent(line);
+        if post_indent.is_some() {
+            break;
+        }
+        post_blank += 1;
+        if post_blank == MAX_BLANKS {
+            post_indent = Some(0);
+            break;
+        }
+    }
+
+    SplitMeasurement { end_of_file, indent: line_indent, pre_blank, pre_indent, post_blank, post_indent }
+}
+
+#[derive(Default)]
+struct Score {
+    effective_indent: i32,
+    penalty: i32,
+}
+
+impl Score {
+    fn add_split(&mut self, m: &SplitMeasurement) {
+        if m.pre_indent.is_none() && m.pre_blank == 0 {
+            self.penalty += START_OF_FILE_PENALTY;
+        }
+        if m.end_of_file {
+            self.penalty += END_OF_FILE_PENALTY;
+        }
+
+        let post_blank = if m.indent.is_none() { 1 + m.post_blank } else { 0 };
+        let total_blank = m.pre_blank + post_blank;
+        self.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+        self.penalty += POST_BLANK_WEIGHT * post_blank;
+
+        let indent = m.indent.or(m.post_indent);
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent.unwrap_or(-1);
+
+        let (Some(indent), Some(pre_indent)) = (indent, m.pre_indent) else { return };
+        if indent > pre_indent {
+            self.penalty += if any_blanks { RELATIVE_INDENT_WITH_BLANK_PENALTY } else { RELATIVE_INDENT_PENALTY };
+        } else if indent < pre_indent {
+            self.penalty += match m.post_indent {
+                Some(post) if post > indent => if any_blanks { RELATIVE_OUTDENT_WITH_BLANK_PENALTY } else { RELATIVE_OUTDENT_PENALTY },
+                _ => if any_blanks { RELATIVE_DEDENT_WITH_BLANK_PENALTY } else { RELATIVE_DEDENT_PENALTY },
+            };
+        }
+    }
+
+    fn cmp(&self, other: &Score) -> i32 {
+        let indents = (self.effective_indent > other.effective_indent) as i32 - (self.effective_indent < other.effective_indent) as i32;
+        INDENT_WEIGHT * indents + (self.penalty - other.penalty)
+    }
+}
@@ -0,0 +1,211 @@
+//! Histogram diff, as in git's xhistogram.c: an extension of patience diff
+//! that splits at the longest common run built around the line that is
+//! rarest on the old side, rather than requiring lines to be unique.
+//! Line numbers are 1-based like git's, so 0 can mean "none".
+
+use std::collections::HashMap;
+
+use super::{myers, DiffEngine};
+
+/// Lines occurring more often than this on the old side are never used to
+/// split; a range with only such lines in common falls back to Myers.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+pub struct Histogram;
+
+impl DiffEngine for Histogram {
+    fn mark_changes(&self, a: &[u32], b: &[u32], removed: &mut [bool], added: &mut [bool]) {
+        let mut diff = HistogramDiff { a, b, removed, added };
+        diff.run(1, a.len(), 1, b.len());
+    }
+}
+
+/// A common run `begin..=end` on both sides.
+#[derive(Default)]
+struct Region {
+    begin1: usize,
+    end1: usize,
+    begin2: usize,
+    end2: usize,
+}
+
+/// The occurrences of one distinct line of the old range.
+struct Record {
+    /// Its first line.
+    ptr: usize,
+    cnt: usize,
+}
+
+/// The occurrences of every line of the old range, for finding the best split.
+struct Index {
+    /// The ranges being diffed, inclusive.
+    line1: usize,
+    end1: usize,
+    line2: usize,
+    end2: usize,
+    records: Vec<Record>,
+    by_line: HashMap<u32, usize>,
+    /// The record of each line, by line number from `line1`.
+    line_map: Vec<usize>,
+    /// The next line with the same content, or 0.
+    next_ptrs: Vec<usize>,
+    cnt: usize,
+    has_common: bool,
+}
+
+impl Index {
+    fn cnt_of(&self, line: usize) -> usize {
+        self.records[self.line_map[line - self.line1]].cnt
+    }
+
+    fn next_ptr(&self, line: usize) -> usize {
+        self.next_ptrs[line - self.line1]
+    }
+}
+
+struct HistogramDiff<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    removed: &'a mut [bool],
+    added: &'a mut [bool],
+}
+
+impl HistogramDiff<'_> {
+    fn same(&self, line1: usize, line2: usize) -> bool {
+        self.a[line1 - 1] == self.b[line2 - 1]
+    }
+
+    fn run(&mut self, mut line1: usize, mut count1: usize, mut line2: usize, mut count2: usize) {
+        loop {
+            if count1 == 0 {
+                self.added[line2 - 1..line2 - 1 + count2].fill(true);
+                return;
+            }
+            if count2 == 0 {
+                self.removed[line1 - 1..line1 - 1 + count1].fill(true);
+                return;
+            }
+
+            let (lcs, fall_back) = self.find_lcs(line1, count1, line2, count2);
+            if fall_back {
+                let (r1, r2) = (line1 - 1..line1 - 1 + count1, line2 - 1..line2 - 1 + count2);
+                let (a, b) = (&self.a[r1.clone()], &self.b[r2.clone()]);
+                myers::diff(a, b, &mut self.removed[r1], &mut self.added[r2], false);
+                return;
+            }
+            if lcs.begin1 == 0 && lcs.begin2 == 0 {
+                self.removed[line1 - 1..line1 - 1 + count1].fill(true);
+                self.added[line2 - 1..line2 - 1 + count2].fill(true);
+                return;
+            }
+
+            self.run(line1, lcs.begin1 - line1, line2, lcs.begin2 - line2);
+            count1 = line1 + count1 - 1 - lcs.end1;
+            line1 = lcs.end1 + 1;
+            count2 = line2 + count2 - 1 - lcs.end2;
+            line2 = lcs.end2 + 1;
+        }
+    }
+
+    /// The common run to split at, and whether to give up on the range
+    /// because every line it has in common is too frequent.
+    fn find_lcs(&self, line1: usize, count1: usize, line2: usize, count2: usize) -> (Region, bool) {
+        let mut index = Index {
+            line1,
+            end1: line1 + count1 - 1,
+            line2,
+            end2: line2 + count2 - 1,
+            records: Vec::new(),
+            by_line: HashMap::new(),
+            line_map: vec![0; count1],
+            next_ptrs: vec![0; count1],
+            cnt: MAX_CHAIN_LENGTH + 1,
+            has_common: false,
+        };
+
+        // scan backwards so each record ends up at its first occurrence
+        for ptr in (line1..line1 + count1).rev() {
+            let id = self.a[ptr - 1];
+            let r = match index.by_line.get(&id) {
+                Some(&r) => {
+                    index.next_ptrs[ptr - line1] = index.records[r].ptr;
+                    index.records[r].ptr = ptr;
+                    index.records[r].cnt += 1;
+                    r
+                }
+                None => {
+                    index.by_line.insert(id, index.records.len());
+                    index.records.push(Record { ptr, cnt: 1 });
+                    index.records.len() - 1
+                }
+            };
+            index.line_map[ptr - line1] = r;
+        }
+
+        let mut lcs = Region::default();
+        let mut b_ptr = line2;
+        while b_ptr < line2 + count2 {
+            b_ptr = self.try_lcs(&mut index, &mut lcs, b_ptr);
+        }
+
+        let fall_back = index.has_common && index.cnt > MAX_CHAIN_LENGTH;
+        (lcs, fall_back)
+    }
+
+    /// Grows every occurrence of new side line `b_ptr` on the old side into a
+    /// common run, keeping the best one; returns the next line to try.
+    fn try_lcs(&self, index: &mut Index, lcs: &mut Region, b_ptr: usize) -> usize {
+        let mut b_next = b_ptr + 1;
+        let Some(&r) = index.by_line.get(&self.b[b_ptr - 1]) else { return b_next };
+
+        if index.records[r].cnt > index.cnt {
+            index.has_common = true;
+            return b_next;
+        }
+        index.has_common = true;
+
+        let mut a_start = index.records[r].ptr;
+        loop {
+            let mut np = index.next_ptr(a_start);
+            let mut b_start = b_ptr;
+            let mut a_end = a_start;
+            let mut b_end = b_start;
+            let mut rc = index.records[r].cnt;
+
+            while index.line1 < a_start && index.line2 < b_start && self.same(a_start - 1, b_start - 1) {
+                a_start -= 1;
+                b_start -= 1;
+                if 1 < rc {
+                    rc = rc.min(index.cnt_of(a_start));
+                }
+            }
+            while a_end < index.end1 && b_end < index.end2 && self.same(a_end + 1, b_end + 1) {
+                a_end += 1;
+                b_end += 1;
+                if 1 < rc {
+                    rc = rc.min(index.cnt_of(a_end));
+                }
+            }
+
+            if b_next <= b_end {
+                b_next = b_end + 1;
+            }
+            if lcs.end1 - lcs.begin1 < a_end - a_start || rc < index.cnt {
+                *lcs = Region { begin1: a_start, end1: a_end, begin2: b_start, end2: b_end };
+                index.cnt = rc;
+            }
+
+            if np == 0 {
+                break;
+            }
+            while np <= a_end {
+                np = index.next_ptr(np);
+                if np == 0 {
+                    return b_next;
+                }
+            }
+            a_start = np;
+        }
+        b_next
+    }
+}
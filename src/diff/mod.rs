@@ -0,0 +1,189 @@
+pub mod changes;
+pub mod compact;
+pub mod histogram;
+pub mod myers;
+pub mod output;
+pub mod patch;
+pub mod patience;
+pub mod rename;
+pub mod unified;
+pub mod word;
+
+use std::{borrow::Cow, collections::HashMap, ops::Range};
+
+use compact::{compact, Side};
+
+/// Lines `old` of the old side replaced by lines `new` of the new side.
+/// Either range may be empty for a pure insertion or deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Splits content into lines that keep their `\n`; only the last may lack one.
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// Git's check: content with a NUL byte in its first 8000 bytes is binary.
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(8000)].contains(&0)
+}
+
+/// A line diff algorithm. Lines come as interned ids; the engine marks the
+/// lines of each side that are not part of the common subsequence it picks.
+pub trait DiffEngine {
+    fn mark_changes(&self, a: &[u32], b: &[u32], removed: &mut [bool], added: &mut [bool]);
+}
+
+/// The algorithms of `--diff-algorithm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Myers,
+    /// Myers without the heuristics that trade a minimal diff for speed.
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "myers" | "default" => Some(Algorithm::Myers),
+            "minimal" => Some(Algorithm::Minimal),
+            "patience" => Some(Algorithm::Patience),
+            "histogram" => Some(Algorithm::Histogram),
+            _ => None,
+        }
+    }
+
+    pub fn engine(self) -> &'static dyn DiffEngine {
+        match self {
+            Algorithm::Myers => &myers::Myers { minimal: false },
+            Algorithm::Minimal => &myers::Myers { minimal: true },
+            Algorithm::Patience => &patience::Patience,
+            Algorithm::Histogram => &histogram::Histogram,
+        }
+    }
+}
+
+/// Which whitespace differences make two lines different.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Whitespace {
+    #[default]
+    Exact,
+    /// `-b`: runs of whitespace are equal to each other, and trailing whitespace is ignored.
+    IgnoreChange,
+    /// `-w`: whitespace is ignored entirely.
+    IgnoreAll,
+}
+
+/// How lines are compared and which algorithm matches them up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineDiffOptions {
+    pub algorithm: Algorithm,
+    pub whitespace: Whitespace,
+    /// `--ignore-blank-lines`: leave out hunks that only add or remove blank lines.
+    pub ignore_blank_lines: bool,
+    /// Slide ambiguous changes to where the indentation suggests (git's default).
+    pub indent_heuristic: bool,
+}
+
+impl Default for LineDiffOptions {
+    fn default() -> Self {
+        LineDiffOptions { algorithm: Algorithm::Myers, whitespace: Whitespace::Exact, ignore_blank_lines: false, indent_heuristic: true }
+    }
+}
+
+/// C's `isspace`, which git uses for whitespace; unlike Rust's it includes `\v`.
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | 0x0b | 0x0c | b'\r')
+}
+
+/// Whether a line counts as blank for `--ignore-blank-lines`.
+pub fn is_blank_line(line: &[u8], whitespace: Whitespace) -> bool {
+    match whitespace {
+        Whitespace::Exact => line.len() <= 1,
+        _ => line.iter().all(|&c| is_space(c)),
+    }
+}
+
+/// Diffs two lists of lines into the changes needed to turn `old` into `new`.
+pub fn diff_lines(old: &[&[u8]], new: &[&[u8]], opts: &LineDiffOptions) -> Vec<Change> {
+    let (a, b) = intern(old, new, opts.whitespace);
+    let mut removed = vec![false; a.len()];
+    let mut added = vec![false; b.len()];
+
+    opts.algorithm.engine().mark_changes(&a, &b, &mut removed, &mut added);
+    let mut old_side = Side { ids: &a, text: old, changed: &mut removed };
+    let mut new_side = Side { ids: &b, text: new, changed: &mut added };
+    compact(&mut old_side, &new_side, opts.indent_heuristic);
+    compact(&mut new_side, &old_side, opts.indent_heuristic);
+
+    build_changes(&removed, &added)
+}
+
+/// Maps every distinct line to a small id so the algorithms compare integers.
+/// Lines that only differ in ignored whitespace share an id.
+fn intern<'a>(old: &[&'a [u8]], new: &[&'a [u8]], whitespace: Whitespace) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: HashMap<Cow<[u8]>, u32> = HashMap::new();
+    let mut id_of = |line: &'a [u8]| {
+        let next = ids.len() as u32;
+        *ids.entry(normalize(line, whitespace)).or_insert(next)
+    };
+    let a = old.iter().map(|line| id_of(line)).collect();
+    let b = new.iter().map(|line| id_of(line)).collect();
+    (a, b)
+}
+
+/// The part of a line that takes part in comparisons. The newline counts as
+/// whitespace, so with whitespace ignored a missing final newline doesn't matter.
+fn normalize(line: &[u8], whitespace: Whitespace) -> Cow<'_, [u8]> {
+    match whitespace {
+        Whitespace::Exact => Cow::Borrowed(line),
+        Whitespace::IgnoreAll => Cow::Owned(line.iter().copied().filter(|&c| !is_space(c)).collect()),
+        Whitespace::IgnoreChange => {
+            let mut key = Vec::with_capacity(line.len());
+            let mut pending_space = false;
+            for &c in line {
+                if is_space(c) {
+                    pending_space = true;
+                    continue;
+                }
+                if pending_space {
+                    key.push(b' ');
+                    pending_space = false;
+                }
+                key.push(c);
+            }
+            Cow::Owned(key)
+        }
+    }
+}
+
+/// Pairs up the runs of removed and added lines; unchanged lines match one to one.
+fn build_changes(removed: &[bool], added: &[bool]) -> Vec<Change> {
+    let (n, m) = (removed.len(), added.len());
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+
+    while i < n || j < m {
+        if (i < n && removed[i]) || (j < m && added[j]) {
+            let (old_start, new_start) = (i, j);
+            while i < n && removed[i] {
+                i += 1;
+            }
+            while j < m && added[j] {
+                j += 1;
+            }
+            changes.push(Change { old: old_start..i, new: new_start..j });
+        } else {
+            i += 1;
+            j += 1;
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,385 @@
+//! Myers' O(ND) difference algorithm in its linear space form: find the
+//! middle snake of the edit graph, split there and repeat on both halves.
+//! The heuristics follow git's xdiff so both pick the same edit script.
+
+use super::DiffEngine;
+
+/// Below this many edits the search always runs to an optimal split.
+const MIN_COST_LIMIT: usize = 256;
+/// Edit cost after which long snakes are taken as split points early.
+const HEURISTIC_MIN_COST: usize = 256;
+/// How long a run of equal lines must be to count as a good snake.
+const SNAKE_LEN: isize = 20;
+const HEURISTIC_FACTOR: isize = 4;
+/// Lines seen this often on the other side count as "common" noise.
+const MAX_EQ_LIMIT: usize = 1024;
+const SCAN_WINDOW: usize = 100;
+const KEEP_RUN_FACTOR: usize = 4;
+
+/// The default engine; `minimal` always searches for the shortest edit script.
+pub struct Myers {
+    pub minimal: bool,
+}
+
+impl DiffEngine for Myers {
+    fn mark_changes(&self, a: &[u32], b: &[u32], removed: &mut [bool], added: &mut [bool]) {
+        diff(a, b, removed, added, self.minimal);
+    }
+}
+
+/// Marks the lines of `a` and `b` that are not part of the common subsequence.
+///
+/// Unless `minimal` is set, a search that runs past a cost limit settles for
+/// a good enough split instead of an optimal one, trading a slightly longer
+/// diff for bounded run time.
+pub fn diff(a: &[u32], b: &[u32], removed: &mut [bool], added: &mut [bool], minimal: bool) {
+    // lines at the ends that match need no work at all
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let a_range = prefix..a.len() - suffix;
+    let b_range = prefix..b.len() - suffix;
+
+    let a_keep = discard_unmatched(a, a_range, b, removed, minimal);
+    let b_keep = discard_unmatched(b, b_range, a, added, minimal);
+    let ra: Vec<u32> = a_keep.iter().map(|&i| a[i]).collect();
+    let rb: Vec<u32> = b_keep.iter().map(|&j| b[j]).collect();
+
+    let diagonals = ra.len() + rb.len() + 3;
+    let mut search = Search {
+        a: &ra,
+        b: &rb,
+        forward: vec![0; diagonals],
+        backward: vec![0; diagonals],
+        base: rb.len() as isize + 1,
+        cost_limit: MIN_COST_LIMIT.max(rough_sqrt(diagonals)) as isize,
+    };
+
+    let mut pending = vec![(0, ra.len(), 0, rb.len(), minimal)];
+    while let Some((mut off1, mut lim1, mut off2, mut lim2, need_min)) = pending.pop() {
+        while off1 < lim1 && off2 < lim2 && ra[off1] == rb[off2] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && ra[lim1 - 1] == rb[lim2 - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+
+        if off1 == lim1 {
+            b_keep[off2..lim2].iter().for_each(|&j| added[j] = true);
+        } else if off2 == lim2 {
+            a_keep[off1..lim1].iter().for_each(|&i| removed[i] = true);
+        } else {
+            let region = Region { off1: off1 as isize, lim1: lim1 as isize, off2: off2 as isize, lim2: lim2 as isize };
+            let split = search.split(&region, need_min);
+            let (x, y) = (split.x as usize, split.y as usize);
+            pending.push((x, lim1, y, lim2, split.min_high));
+            pending.push((off1, x, off2, y, split.min_low));
+        }
+    }
+}
+
+/// git's cheap integer square root: a power of two close to `sqrt(n)`.
+fn rough_sqrt(mut n: usize) -> usize {
+    let mut root = 1;
+    while n > 0 {
+        root <<= 1;
+        n >>= 2;
+    }
+    root
+}
+
+/// Marks the lines of `lines[range]` that cannot or should not take part in
+/// the search as changed, and returns the indices of the rest. A line with no
+/// match on the other side can never be common; one with very many matches
+/// is dropped too when it sits among such lines, as it would only lead the
+/// search astray. Like xdiff, `minimal` keeps those, since dropping one can
+/// lengthen the diff.
+fn discard_unmatched(lines: &[u32], range: std::ops::Range<usize>, other: &[u32], changed: &mut [bool], minimal: bool) -> Vec<usize> {
+    let max_id = lines.iter().chain(other).copied().max().map_or(0, |id| id as usize + 1);
+    let mut counts = vec![0usize; max_id];
+    other.iter().for_each(|&id| counts[id as usize] += 1);
+
+    let limit = rough_sqrt(lines.len()).min(MAX_EQ_LIMIT);
+    // 0: no match, 1: keep, 2: matches too often
+    let kind: Vec<u8> = lines[range.clone()]
+        .iter()
+        .map(|&id| match counts[id as usize] {
+            0 => 0,
+            n if n >= limit && !minimal => 2,
+            _ => 1,
+        })
+        .collect();
+
+    let mut keep = Vec::new();
+    for (i, &k) in kind.iter().enumerate() {
+        if k == 1 || (k == 2 && !surrounded_by_unmatched(&kind, i)) {
+            keep.push(range.start + i);
+        } else {
+            changed[range.start + i] = true;
+        }
+    }
+    keep
+}
+
+/// Whether a frequently matching line sits in a run made up mostly of
+/// unmatched lines, scanning a limited window each way.
+fn surrounded_by_unmatched(kind: &[u8], i: usize) -> bool {
+    let start = i.saturating_sub(SCAN_WINDOW);
+    let end = (i + SCAN_WINDOW).min(kind.len() - 1);
+
+    let (mut unmatched_before, mut frequent_before) = (0, 1);
+    for &k in kind[start..i].iter().rev() {
+        match k {
+            0 => unmatched_before += 1,
+            2 => frequent_before += 1,
+            _ => break,
+        }
+    }
+    if unmatched_before == 0 {
+        return false;
+    }
+
+    let (mut unmatched_after, mut frequent_after) = (0, 1);
+    for &k in &kind[i + 1..=end] {
+        match k {
+            0 => unmatched_after += 1,
+            2 => frequent_after += 1,
+            _ => break,
+        }
+    }
+    if unmatched_after == 0 {
+        return false;
+    }
+
+    let unmatched = unmatched_before + unmatched_after;
+    let frequent = frequent_before + frequent_after;
+    frequent * KEEP_RUN_FACTOR < frequent + unmatched
+}
+
+/// The box `off1..lim1` × `off2..lim2` of the edit graph being searched.
+#[derive(Clone, Copy)]
+struct Region {
+    off1: isize,
+    lim1: isize,
+    off2: isize,
+    lim2: isize,
+}
+
+/// Where to split a box, and whether each half still needs an optimal diff.
+struct Split {
+    x: isize,
+    y: isize,
+    min_low: bool,
+    min_high: bool,
+}
+
+/// The furthest reaching paths for each diagonal `k = x - y`, searched
+/// forward from the top left and backward from the bottom right of a box.
+struct Search<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    /// Offset turning a diagonal into a vector index.
+    base: isize,
+    cost_limit: isize,
+}
+
+impl Search<'_> {
+    fn f(&self, k: isize) -> isize {
+        self.forward[(k + self.base) as usize]
+    }
+
+    fn set_f(&mut self, k: isize, x: isize) {
+        self.forward[(k + self.base) as usize] = x;
+    }
+
+    fn b(&self, k: isize) -> isize {
+        self.backward[(k + self.base) as usize]
+    }
+
+    fn set_b(&mut self, k: isize, x: isize) {
+        self.backward[(k + self.base) as usize] = x;
+    }
+
+    fn same(&self, x: isize, y: isize) -> bool {
+        self.a[x as usize] == self.b[y as usize]
+    }
+
+    /// Finds the middle snake of a box whose first and last lines are known to differ.
+    fn split(&mut self, region: &Region, need_min: bool) -> Split {
+        let Region { off1, lim1, off2, lim2 } = *region;
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax) = (fmid, fmid);
+        let (mut bmin, mut bmax) = (bmid, bmid);
+
+        self.set_f(fmid, off1);
+        self.set_b(bmid, lim1);
+
+        let mut cost = 1;
+        loop {
+            let mut got_snake = false;
+
+            // widen the forward diagonal range by one, or shrink it at the box edges
+            if fmin > dmin {
+                fmin -= 1;
+                self.set_f(fmin - 1, -1);
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                self.set_f(fmax + 1, -1);
+            } else {
+                fmax -= 1;
+            }
+
+            let mut k = fmax;
+            while k >= fmin {
+                let mut x = if self.f(k - 1) >= self.f(k + 1) { self.f(k - 1) + 1 } else { self.f(k + 1) };
+                let start = x;
+                let mut y = x - k;
+                while x < lim1 && y < lim2 && self.same(x, y) {
+                    x += 1;
+                    y += 1;
+                }
+                if x - start > SNAKE_LEN {
+                    got_snake = true;
+                }
+                self.set_f(k, x);
+                if odd && bmin <= k && k <= bmax && self.b(k) <= x {
+                    return Split { x, y, min_low: true, min_high: true };
+                }
+                k -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                self.set_b(bmin - 1, isize::MAX);
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                self.set_b(bmax + 1, isize::MAX);
+            } else {
+                bmax -= 1;
+            }
+
+            let mut k = bmax;
+            while k >= bmin {
+                let mut x = if self.b(k - 1) < self.b(k + 1) { self.b(k - 1) } else { self.b(k + 1) - 1 };
+                let start = x;
+                let mut y = x - k;
+                while x > off1 && y > off2 && self.same(x - 1, y - 1) {
+                    x -= 1;
+                    y -= 1;
+                }
+                if start - x > SNAKE_LEN {
+                    got_snake = true;
+                }
+                self.set_b(k, x);
+                if !odd && fmin <= k && k <= fmax && x <= self.f(k) {
+                    return Split { x, y, min_low: true, min_high: true };
+                }
+                k -= 2;
+            }
+
+            if !need_min {
+                if got_snake && cost > HEURISTIC_MIN_COST as isize {
+                    if let Some(split) = self.snake_split(region, (fmin, fmax, fmid), (bmin, bmax, bmid), cost) {
+                        return split;
+                    }
+                }
+                if cost >= self.cost_limit {
+                    return self.furthest_split(region, (fmin, fmax), (bmin, bmax));
+                }
+            }
+
+            cost += 1;
+        }
+    }
+
+    /// Once the search gets expensive, a path that has come far along its
+    /// diagonal and ends in a long snake is taken as the split point.
+    fn snake_split(&self, region: &Region, fwd: (isize, isize, isize), bwd: (isize, isize, isize), cost: isize) -> Option<Split> {
+        let Region { off1, lim1, off2, lim2 } = *region;
+        let (fmin, fmax, fmid) = fwd;
+        let mut best = 0;
+        let mut found = None;
+        let mut k = fmax;
+        while k >= fmin {
+            let x = self.f(k);
+            let y = x - k;
+            let v = (x - off1) + (y - off2) - (k - fmid).abs();
+            if v > HEURISTIC_FACTOR * cost && v > best && off1 + SNAKE_LEN <= x && x < lim1 && off2 + SNAKE_LEN <= y && y < lim2 && (1..=SNAKE_LEN).all(|i| self.same(x - i, y - i)) {
+                best = v;
+                found = Some((x, y));
+            }
+            k -= 2;
+        }
+        if let Some((x, y)) = found {
+            return Some(Split { x, y, min_low: true, min_high: false });
+        }
+
+        let (bmin, bmax, bmid) = bwd;
+        let mut k = bmax;
+        while k >= bmin {
+            let x = self.b(k);
+            let y = x - k;
+            let v = (lim1 - x) + (lim2 - y) - (k - bmid).abs();
+            if v > HEURISTIC_FACTOR * cost && v > best && off1 < x && x <= lim1 - SNAKE_LEN && off2 < y && y <= lim2 - SNAKE_LEN && (0..SNAKE_LEN).all(|i| self.same(x + i, y + i)) {
+                best = v;
+                found = Some((x, y));
+            }
+            k -= 2;
+        }
+        found.map(|(x, y)| Split { x, y, min_low: false, min_high: true })
+    }
+
+    /// Gives up on an optimal split and takes whichever of the forward and
+    /// backward searches has come furthest.
+    fn furthest_split(&self, region: &Region, fwd: (isize, isize), bwd: (isize, isize)) -> Split {
+        let Region { off1, lim1, off2, lim2 } = *region;
+        let (mut fbest, mut fbest_x) = (-1, -1);
+        let mut k = fwd.1;
+        while k >= fwd.0 {
+            let mut x = self.f(k).min(lim1);
+            let mut y = x - k;
+            if lim2 < y {
+                x = lim2 + k;
+                y = lim2;
+            }
+            if fbest < x + y {
+                fbest = x + y;
+                fbest_x = x;
+            }
+            k -= 2;
+        }
+
+        let (mut bbest, mut bbest_x) = (isize::MAX, isize::MAX);
+        let mut k = bwd.1;
+        while k >= bwd.0 {
+            let mut x = self.b(k).max(off1);
+            let mut y = x - k;
+            if y < off2 {
+                x = off2 + k;
+                y = off2;
+            }
+            if x + y < bbest {
+                bbest = x + y;
+                bbest_x = x;
+            }
+            k -= 2;
+        }
+
+        if (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+            Split { x: fbest_x, y: fbest - fbest_x, min_low: true, min_high: false }
+        } else {
+            Split { x: bbest_x, y: bbest - bbest_x, min_low: false, min_high: true }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use std::io::{self, Write};
+
+use crate::{commands::parse_error::CommandParseError, hash::{GitHash, HASH_HEX_LENGTH}, objects::{hash_object, ObjectType}, utils::path_utils::quote_path};
+
+use super::{changes::{ChangeStatus, DiffSide, FileChange}, diff_lines, is_binary, patch::{write_patch, DiffFile, Origin, PatchOptions}, rename::RenameOptions, split_lines, unified::build_hunks};
+
+/// Hex digits of abbreviated ids, as git prints them by default.
+pub const DEFAULT_ABBREV: usize = 7;
+/// Total width of `--stat` output.
+const STAT_WIDTH: usize = 80;
+
+/// Output options shared by `diff`, `diff-tree` and `diff-index`. Several
+/// formats can be combined; they are written in git's order.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    pub patch: bool,
+    pub raw: bool,
+    pub name_only: bool,
+    pub name_status: bool,
+    pub stat: bool,
+    /// `--numstat`: added and deleted lines per file, for scripts.
+    pub numstat: bool,
+    /// `--shortstat`: only the last line of `--stat`.
+    pub shortstat: bool,
+    /// `--summary`: created, deleted and renamed files and mode changes.
+    pub summary: bool,
+    /// `-s`: suppress all output.
+    pub no_output: bool,
+    /// Hex digits of ids in `--raw` output; `None` for full ids.
+    pub abbrev: Option<usize>,
+    /// `-z`: NUL-terminate paths in the raw and name formats.
+    pub nul_terminated: bool,
+    /// Columns taken by what is printed before each line, such as log's
+    /// graph; `--stat` narrows to fit.
+    pub line_prefix_width: usize,
+    pub patch_opts: PatchOptions,
+    pub renames: RenameOptions,
+}
+
+impl DiffOptions {
+    /// Handles `arg` if it is a diff output option. Returns `false` for anything else.
+    pub fn parse_option(&mut self, arg: &str) -> io::Result<bool> {
+        match arg {
+            "-p" | "-u" | "--patch" => self.patch = true,
+            "--raw" => self.raw = true,
+            "--name-only" => self.name_only = true,
+            "--name-status" => self.name_status = true,
+            "--stat" => self.stat = true,
+            "--numstat" => self.numstat = true,
+            "--shortstat" => self.shortstat = true,
+            "--summary" => self.summary = true,
+            "-s" | "--no-patch" => self.no_output = true,
+            "--abbrev" => self.abbrev = Some(DEFAULT_ABBREV),
+            "--no-abbrev" => self.abbrev = None,
+            "-z" => self.nul_terminated = true,
+            x if x.starts_with("--abbrev=") => {
+                let value = &x["--abbrev=".len()..];
+                let len: usize = value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("invalid --abbrev value '{}'", value)))?;
+                self.abbrev = Some(len.clamp(4, HASH_HEX_LENGTH));
+            }
+            x if x.starts_with("--unified=") || (x.starts_with("-U") && x.len() > 2) => {
+                let value = x.strip_prefix("--unified=").unwrap_or(&x[2..]);
+                self.patch_opts.context = value.parse().map_err(|_| CommandParseError::InvalidArgument(format!("invalid context length '{}'", value)))?;
+                self.patch = true;
+            }
+            x if self.patch_opts.parse_option(x)? => {}
+            x => return self.renames.parse_option(x),
+        }
+        Ok(true)
+    }
+
+    /// Whether no format was asked for, so the command's default applies.
+    pub fn has_format(&self) -> bool {
+        self.patch || self.raw || self.name_only || self.name_status || self.stat || self.numstat || self.shortstat || self.summary || self.no_output
+    }
+}
+
+/// Writes `changes` in every format selected in `opts`.
+pub fn write_changes(out: &mut impl Write, changes: &[FileChange], opts: &DiffOptions) -> io::Result<()> {
+    if opts.no_output {
+        return Ok(());
+    }
+
+    // like git, the name formats replace every other format
+    if opts.name_only || opts.name_status {
+        for change in changes {
+            write_name_line(out, change, opts)?;
+        }
+        return Ok(());
+    }
+
+    let mut separator = false;
+    if opts.raw {
+        for change in changes {
+            write_name_line(out, change, opts)?;
+        }
+        separator = true;
+    }
+
+    if opts.numstat {
+        write_numstat(out, changes, opts)?;
+        separator = true;
+    }
+
+    if opts.stat {
+        write_stat(out, changes, &opts.patch_opts, STAT_WIDTH.saturating_sub(opts.line_prefix_width))?;
+        separator = true;
+    } else if opts.shortstat {
+        write_shortstat(out, changes, &opts.patch_opts)?;
+        separator = true;
+    }
+
+    if opts.summary {
+        write_summary(out, changes)?;
+        separator = true;
+    }
+
+    if opts.patch {
+        if separator && !changes.is_empty() {
+            writeln!(out)?;
+        }
+        for change in changes {
+            write_change_patch(out, change, &opts.patch_opts)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_name_line(out: &mut impl Write, change: &FileChange, opts: &DiffOptions) -> io::Result<()> {
+    let quote = |path: &str| if opts.nul_terminated { path.to_string() } else { quote_path(path) };
+    let end = if opts.nul_terminated { '\0' } else { '\n' };
+    let sep = if opts.nul_terminated { '\0' } else { '\t' };
+
+    if opts.name_only {
+        return write!(out, "{}{}", quote(&change.path), end);
+    }
+
+    // renames and copies show their similarity and both paths
+    let (status, path) = match &change.old_path {
+        Some(old_path) if change.is_rename_or_copy() => (format!("{}{:03}", change.status.code(), change.similarity), format!("{}{}{}", quote(old_path), sep, quote(&change.path))),
+        _ => (change.status.code().to_string(), quote(change.source_path())),
+    };
+    if opts.name_status {
+        return write!(out, "{}{}{}{}", status, sep, path, end);
+    }
+
+    let hash_len = opts.abbrev.unwrap_or(HASH_HEX_LENGTH);
+    let side = |side: &Option<DiffSide>| match side {
+        Some(s) => (s.mode.as_str().to_string(), s.hash.abbrev(hash_len)),
+        None => ("000000".to_string(), GitHash::zero().abbrev(hash_len)),
+    };
+    let (old_mode, old_hash) = side(&change.old);
+    let (new_mode, new_hash) = side(&change.new);
+    write!(out, ":{:0>6} {:0>6} {} {} {}{}{}{}", old_mode, new_mode, old_hash, new_hash, status, sep, path, end)
+}
+
+/// Writes the patch of one change; returns whether anything was written.
+pub fn write_change_patch(out: &mut impl Write, change: &FileChange, opts: &PatchOptions) -> io::Result<bool> {
+    if change.status == ChangeStatus::Unmerged {
+        writeln!(out, "* Unmerged path {}", change.path)?;
+        return Ok(true);
+    }
+
+    let old_data = change.old.as_ref().map(|s| s.load(change.source_path())).transpose()?;
+    let new_data = change.new.as_ref().map(|s| s.load(&change.path)).transpose()?;
+    let old = diff_file(change.source_path(), change.old.as_ref(), old_data.as_deref());
+    let new = diff_file(&change.path, change.new.as_ref(), new_data.as_deref());
+    let origin = match change.status {
+        ChangeStatus::Renamed => Some(Origin::Renamed(change.similarity)),
+        ChangeStatus::Copied => Some(Origin::Copied(change.similarity)),
+        _ => None,
+    };
+    write_patch(out, old.as_ref(), new.as_ref(), origin, opts)
+}
+
+fn diff_file<'a>(path: &'a str, side: Option<&DiffSide>, data: Option<&'a [u8]>) -> Option<DiffFile<'a>> {
+    let (side, data) = (side?, data?);
+    let hash = if side.in_worktree() { hash_object(ObjectType::Blob, data).0 } else { side.hash.clone() };
+    // `--no-index` may compare absolute paths; like git, the header drops the leading slash
+    Some(DiffFile { path: path.trim_start_matches('/'), mode: side.mode.clone(), hash, data })
+}
+
+/// Per-file numbers for `--stat`.
+struct FileStat {
+    name: String,
+    added: usize,
+    deleted: usize,
+    /// For binary files `added` and `deleted` are the new and old sizes in bytes.
+    binary: bool,
+    unmerged: bool,
+}
+
+/// The numbers of one change, or `None` for a modified file whose changes
+/// are all ignored, which git leaves out.
+fn file_stat(change: &FileChange, opts: &PatchOptions) -> io::Result<Option<FileStat>> {
+    let name = match &change.old_path {
+        Some(old_path) => rename_name(old_path, &change.path),
+        None => quote_path(&change.path),
+    };
+    let mut stat = FileStat { name, added: 0, deleted: 0, binary: false, unmerged: false };
+    if change.status == ChangeStatus::Unmerged {
+        stat.unmerged = true;
+        return Ok(Some(stat));
+    }
+
+    let old = change.old.as_ref().map(|s| s.load(change.source_path())).transpose()?.unwrap_or_default();
+    let new = change.new.as_ref().map(|s| s.load(&change.path)).transpose()?.unwrap_or_default();
+    if is_binary(&old) || is_binary(&new) {
+        stat.binary = true;
+        if old != new {
+            stat.added = new.len();
+            stat.deleted = old.len();
+        }
+        return Ok(Some(stat));
+    }
+    if old == new {
+        return Ok(Some(stat));
+    }
+
+    // only the lines of hunks that would be shown count
+    let (old_lines, new_lines) = (split_lines(&old), split_lines(&new));
+    let changes = diff_lines(&old_lines, &new_lines, &opts.lines);
+    for hunk in build_hunks(&old_lines, &new_lines, &changes, opts.context, &opts.lines) {
+        for change in &changes[hunk.changes] {
+            stat.deleted += change.old.len();
+            stat.added += change.new.len();
+        }
+    }
+    let same_mode = matches!((&change.old, &change.new), (Some(o), Some(n)) if o.mode == n.mode);
+    if stat.added + stat.deleted == 0 && change.status == ChangeStatus::Modified && same_mode {
+        return Ok(None);
+    }
+    Ok(Some(stat))
+}
+
+/// Writes git's diffstat: a line per file with a `+`/`-` graph scaled to fit
+/// the output width, then a summary line.
+fn write_stat(out: &mut impl Write, changes: &[FileChange], opts: &PatchOptions, width: usize) -> io::Result<()> {
+    let stats = file_stats(changes, opts)?;
+    if stats.is_empty() {
+        return Ok(());
+    }
+
+    let mut max_len = 0;
+    let mut max_change = 0;
+    let mut number_width = 0;
+    let mut bin_width = 0;
+    for stat in &stats {
+        max_len = max_len.max(stat.name.chars().count());
+        if stat.unmerged {
+            continue;
+        }
+        if stat.binary {
+            bin_width = bin_width.max(14 + decimal_width(stat.added) + decimal_width(stat.deleted));
+            number_width = 3;
+            continue;
+        }
+        max_change = max_change.max(stat.added + stat.deleted);
+    }
+
+    number_width = number_width.max(decimal_width(max_change));
+    let width = width.max(16 + 6 + number_width);
+    let mut graph_width = if max_change + 4 > bin_width { max_change } else { bin_width - 4 };
+    let mut name_width = max_len;
+
+    if name_width + number_width + 6 + graph_width > width {
+        if graph_width + number_width + 6 > width * 3 / 8 {
+            graph_width = (width * 3 / 8).saturating_sub(number_width + 6).max(6);
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let (mut insertions, mut deletions) = (0, 0);
+    for stat in &stats {
+        let (prefix, name) = scale_name(&stat.name, name_width);
+        let padding = name_width.saturating_sub(prefix.len() + name.chars().count());
+        write!(out, " {}{}{} | ", prefix, name, " ".repeat(padding))?;
+
+        if stat.unmerged {
+            writeln!(out, "{:>width$}", "Unmerged", width = number_width)?;
+            continue;
+        }
+        if stat.binary {
+            write!(out, "{:>width$}", "Bin", width = number_width)?;
+            if stat.added == 0 && stat.deleted == 0 {
+                writeln!(out)?;
+            } else {
+                writeln!(out, " {} -> {} bytes", stat.deleted, stat.added)?;
+            }
+            continue;
+        }
+
+        insertions += stat.added;
+        deletions += stat.deleted;
+        let total = stat.added + stat.deleted;
+        let (mut add, mut del) = (stat.added, stat.deleted);
+        if graph_width <= max_change {
+            let mut scaled = scale_linear(total, graph_width, max_change);
+            if scaled < 2 && add > 0 && del > 0 {
+                scaled = 2;
+            }
+            if add < del {
+                add = scale_linear(add, graph_width, max_change);
+                del = scaled - add;
+            } else {
+                del = scale_linear(del, graph_width, max_change);
+                add = scaled - del;
+            }
+        }
+        writeln!(out, "{:>width$}{}{}{}", total, if total > 0 { " " } else { "" }, "+".repeat(add), "-".repeat(del), width = number_width)?;
+    }
+
+    write_totals(out, stats.len(), insertions, deletions)
+}
+
+fn file_stats(changes: &[FileChange], opts: &PatchOptions) -> io::Result<Vec<FileStat>> {
+    let stats = changes.iter().map(|c| file_stat(c, opts)).collect::<io::Result<Vec<_>>>()?;
+    Ok(stats.into_iter().flatten().collect())
+}
+
+/// Writes a `<added> <deleted> <path>` line per file, tab separated, with
+/// `-` counts for binary files. With `-z` a rename's paths follow an empty one.
+fn write_numstat(out: &mut impl Write, changes: &[FileChange], opts: &DiffOptions) -> io::Result<()> {
+    for change in changes {
+        let Some(stat) = file_stat(change, &opts.patch_opts)? else { continue };
+        let counts = if stat.binary { "-\t-".to_string() } else { format!("{}\t{}", stat.added, stat.deleted) };
+        match &change.old_path {
+            _ if !opts.nul_terminated => writeln!(out, "{}\t{}", counts, stat.name)?,
+            Some(old_path) => write!(out, "{}\t\0{}\0{}\0", counts, old_path, change.path)?,
+            None => write!(out, "{}\t{}\0", counts, change.path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes only the totals line of the diffstat, as `--shortstat` does.
+fn write_shortstat(out: &mut impl Write, changes: &[FileChange], opts: &PatchOptions) -> io::Result<()> {
+    let stats = file_stats(changes, opts)?;
+    if stats.is_empty() {
+        return Ok(());
+    }
+    let counted = stats.iter().filter(|s| !s.unmerged && !s.binary);
+    let (insertions, deletions) = counted.fold((0, 0), |(i, d), s| (i + s.added, d + s.deleted));
+    write_totals(out, stats.len(), insertions, deletions)
+}
+
+fn write_totals(out: &mut impl Write, files: usize, insertions: usize, deletions: usize) -> io::Result<()> {
+    write!(out, " {} file{} changed", files, if files == 1 { "" } else { "s" })?;
+    if insertions > 0 || deletions == 0 {
+        write!(out, ", {} insertion{}(+)", insertions, if insertions == 1 { "" } else { "s" })?;
+    }
+    if deletions > 0 || insertions == 0 {
+        write!(out, ", {} deletion{}(-)", deletions, if deletions == 1 { "" } else { "s" })?;
+    }
+    writeln!(out)
+}
+
+/// Writes the `--summary` lines: files created or deleted with their mode,
+/// renames and copies with their similarity, and mode changes.
+fn write_summary(out: &mut impl Write, changes: &[FileChange]) -> io::Result<()> {
+    for change in changes {
+        match (&change.old, &change.new) {
+            (None, Some(new)) => writeln!(out, " create mode {} {}", new.mode.as_str(), quote_path(&change.path))?,
+            (Some(old), None) => writeln!(out, " delete mode {} {}", old.mode.as_str(), quote_path(&change.path))?,
+            (Some(old), Some(new)) => {
+                if let Some(old_path) = change.old_path.as_ref().filter(|_| change.is_rename_or_copy()) {
+                    let verb = if change.status == ChangeStatus::Copied { "copy" } else { "rename" };
+                    writeln!(out, " {} {} ({}%)", verb, rename_name(old_path, &change.path), change.similarity)?;
+                }
+                // a rename line already names the file
+                if old.mode != new.mode && change.is_rename_or_copy() {
+                    writeln!(out, " mode change {} => {}", old.mode.as_str(), new.mode.as_str())?;
+                } else if old.mode != new.mode {
+                    writeln!(out, " mode change {} => {} {}", old.mode.as_str(), new.mode.as_str(), quote_path(&change.path))?;
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Names a rename by its common prefix and suffix, like `dir/{old => new}.rs`.
+fn rename_name(old: &str, new: &str) -> String {
+    if quote_path(old) != old || quote_path(new) != new {
+        return format!("{} => {}", quote_path(old), quote_path(new));
+    }
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+
+    // the common prefix ends at a slash
+    let mut prefix = 0;
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        if x != y {
+            break;
+        }
+        if *x == b'/' {
+            prefix = i + 1;
+        }
+    }
+
+    // the common suffix starts at a slash, which may be the prefix's last one
+    let mut suffix = 0;
+    let floor = prefix.saturating_sub(1);
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > floor && j > floor && a[i - 1] == b[j - 1] {
+        if a[i - 1] == b'/' {
+            suffix = a.len() - (i - 1);
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    let old_mid = &old[prefix..old.len().saturating_sub(suffix).max(prefix)];
+    let new_mid = &new[prefix..new.len().saturating_sub(suffix).max(prefix)];
+    if prefix + suffix == 0 {
+        return format!("{} => {}", old_mid, new_mid);
+    }
+    format!("{}{{{} => {}}}{}", &old[..prefix], old_mid, new_mid, &old[old.len() - suffix..])
+}
+
+/// Shortens a name that doesn't fit to `...` and its tail, cut at a `/` if possible.
+fn scale_name(name: &str, width: usize) -> (&'static str, &str) {
+    let len = name.chars().count();
+    if len <= width {
+        return ("", name);
+    }
+    let keep = width.saturating_sub(3);
+    let start = name.char_indices().nth(len - keep).map_or(name.len(), |(i, _)| i);
+    let tail = &name[start..];
+    ("...", tail.find('/').map_or(tail, |slash| &tail[slash..]))
+}
+
+/// Scales `it` to the graph width, showing at least one column for any change.
+fn scale_linear(it: usize, width: usize, max_change: usize) -> usize {
+    if it == 0 {
+        return 0;
+    }
+    1 + it * (width - 1) / max_change
+}
+
+fn decimal_width(n: usize) -> usize {
+    n.to_string().len()
+}
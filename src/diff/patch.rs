@@ -0,0 +1,223 @@
+use std::io::{self, Write};
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{commands::parse_error::CommandParseError, config::git_config::GitConfig, hash::GitHash, objects::FileMode, utils::path_utils::quote_path};
+
+use super::{
+    diff_lines, is_binary, split_lines,
+    unified::{build_hunks, write_hunks, UnifiedWriter, META_COLOR, RESET},
+    word::{WordDiff, WordDiffWriter},
+    Algorithm, LineDiffOptions, Whitespace,
+};
+
+/// Hex digits shown for object ids on the `index` line.
+const ABBREV_LEN: usize = 7;
+
+/// One side of a file pair.
+#[derive(Debug, Clone)]
+pub struct DiffFile<'a> {
+    pub path: &'a str,
+    pub mode: FileMode,
+    pub hash: GitHash,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchOptions {
+    /// Lines of context around each change (`-U`).
+    pub context: usize,
+    pub lines: LineDiffOptions,
+    pub word_diff: Option<WordDiff>,
+    /// What counts as a word in word diffs; runs of non-whitespace by default.
+    pub word_regex: Option<Regex>,
+}
+
+/// How the new side of a pair came from an old file at another path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Renamed with this similarity, in percent.
+    Renamed(u32),
+    Copied(u32),
+}
+
+impl Default for PatchOptions {
+    fn default() -> Self {
+        PatchOptions { context: 3, lines: LineDiffOptions::default(), word_diff: None, word_regex: None }
+    }
+}
+
+impl PatchOptions {
+    /// The defaults of porcelain commands: `diff.algorithm`, `diff.wordRegex`
+    /// and `diff.indentHeuristic`.
+    pub fn from_config(config: &GitConfig) -> io::Result<Self> {
+        let mut opts = PatchOptions::default();
+        if let Some(name) = config.get("diff.algorithm") {
+            opts.lines.algorithm = Algorithm::parse(name).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown value for config 'diff.algorithm': {}", name)))?;
+        }
+        if let Some(pattern) = config.get("diff.wordregex") {
+            opts.set_word_regex(pattern)?;
+        }
+        if let Some(enabled) = config.get_bool("diff.indentheuristic") {
+            opts.lines.indent_heuristic = enabled;
+        }
+        Ok(opts)
+    }
+
+    /// Handles `arg` if it picks the diff algorithm, how whitespace is
+    /// compared or a word diff. Returns `false` for anything else.
+    pub fn parse_option(&mut self, arg: &str) -> io::Result<bool> {
+        match arg {
+            "--minimal" => self.lines.algorithm = Algorithm::Minimal,
+            "--patience" => self.lines.algorithm = Algorithm::Patience,
+            "--histogram" => self.lines.algorithm = Algorithm::Histogram,
+            "-w" | "--ignore-all-space" => self.lines.whitespace = self.lines.whitespace.max(Whitespace::IgnoreAll),
+            "-b" | "--ignore-space-change" => self.lines.whitespace = self.lines.whitespace.max(Whitespace::IgnoreChange),
+            "--ignore-blank-lines" => self.lines.ignore_blank_lines = true,
+            "--indent-heuristic" => self.lines.indent_heuristic = true,
+            "--no-indent-heuristic" => self.lines.indent_heuristic = false,
+            "--word-diff" => self.word_diff = Some(WordDiff::Plain),
+            "--color-words" => self.word_diff = Some(WordDiff::Color),
+            x if x.starts_with("--diff-algorithm=") => {
+                let name = &x["--diff-algorithm=".len()..];
+                self.lines.algorithm = Algorithm::parse(name).ok_or_else(|| CommandParseError::InvalidArgument(format!("unknown diff algorithm '{}'", name)))?;
+            }
+            x if x.starts_with("--word-diff=") => {
+                let mode = &x["--word-diff=".len()..];
+                self.word_diff = WordDiff::parse(mode).ok_or_else(|| CommandParseError::InvalidArgument(format!("bad --word-diff argument: {}", mode)))?;
+            }
+            x if x.starts_with("--word-diff-regex=") => {
+                self.set_word_regex(&x["--word-diff-regex=".len()..])?;
+                self.word_diff = self.word_diff.or(Some(WordDiff::Plain));
+            }
+            x if x.starts_with("--color-words=") => {
+                self.set_word_regex(&x["--color-words=".len()..])?;
+                self.word_diff = Some(WordDiff::Color);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Compiles a word regex the way git does: POSIX extended syntax where
+    /// `^` and `$` match at line boundaries.
+    pub fn set_word_regex(&mut self, pattern: &str) -> io::Result<()> {
+        let regex = RegexBuilder::new(pattern)
+            .multi_line(true)
+            .unicode(false)
+            .build()
+            .map_err(|_| CommandParseError::InvalidArgument(format!("invalid regular expression: {}", pattern)))?;
+        self.word_regex = Some(regex);
+        Ok(())
+    }
+}
+
+/// Writes the git-style patch for one file; `None` marks a side where the
+/// file does not exist and `origin` a rename or copy. A file that turns into
+/// a symlink or back is shown as a deletion followed by an addition, like git does.
+///
+/// Like git, a pair whose differences are all ignored (say by `-w`) prints
+/// nothing unless its header says something on its own. Returns whether
+/// anything was written.
+pub fn write_patch(out: &mut impl Write, old: Option<&DiffFile>, new: Option<&DiffFile>, origin: Option<Origin>, opts: &PatchOptions) -> io::Result<bool> {
+    if let (Some(o), Some(n), None) = (old, new, origin) {
+        if (o.mode == FileMode::Symlink) != (n.mode == FileMode::Symlink) {
+            let deleted = write_patch(out, Some(o), None, None, opts)?;
+            let added = write_patch(out, None, Some(n), None, opts)?;
+            return Ok(deleted || added);
+        }
+    }
+
+    let Some(any) = old.or(new) else { return Ok(false) };
+    let color = opts.word_diff == Some(WordDiff::Color);
+    let old_path = old.map_or(any.path, |f| f.path);
+    let new_path = new.map_or(any.path, |f| f.path);
+
+    let mut header = Vec::new();
+    let mut meta = |line: String| {
+        if color {
+            writeln!(header, "{}{}{}", META_COLOR, line, RESET)
+        } else {
+            writeln!(header, "{}", line)
+        }
+    };
+    meta(format!("diff --git {} {}", quote_path(&format!("a/{}", old_path)), quote_path(&format!("b/{}", new_path))))?;
+    match (old, new) {
+        (None, Some(n)) => meta(format!("new file mode {}", n.mode))?,
+        (Some(o), None) => meta(format!("deleted file mode {}", o.mode))?,
+        (Some(o), Some(n)) if o.mode != n.mode => {
+            meta(format!("old mode {}", o.mode))?;
+            meta(format!("new mode {}", n.mode))?;
+        }
+        _ => {}
+    }
+    let (verb, similarity) = match origin {
+        Some(Origin::Renamed(similarity)) => ("rename", similarity),
+        Some(Origin::Copied(similarity)) => ("copy", similarity),
+        None => ("", 0),
+    };
+    if origin.is_some() {
+        meta(format!("similarity index {}%", similarity))?;
+        meta(format!("{} from {}", verb, quote_path(old_path)))?;
+        meta(format!("{} to {}", verb, quote_path(new_path)))?;
+    }
+    let must_show = origin.is_some() || old.zip(new).map_or(true, |(o, n)| o.mode != n.mode);
+
+    let old_hash = old.map_or_else(GitHash::zero, |f| f.hash.clone());
+    let new_hash = new.map_or_else(GitHash::zero, |f| f.hash.clone());
+    if old_hash != new_hash {
+        let mode = match (old, new) {
+            (Some(o), Some(n)) if o.mode == n.mode => format!(" {}", o.mode),
+            _ => String::new(),
+        };
+        meta(format!("index {}..{}{}", old_hash.abbrev(ABBREV_LEN), new_hash.abbrev(ABBREV_LEN), mode))?;
+    }
+    if old_hash == new_hash {
+        if must_show {
+            out.write_all(&header)?;
+        }
+        return Ok(must_show);
+    }
+
+    let old_name = old.map_or_else(|| "/dev/null".to_string(), |f| format!("a/{}", f.path));
+    let new_name = new.map_or_else(|| "/dev/null".to_string(), |f| format!("b/{}", f.path));
+    let old_data = old.map_or(&[][..], |f| f.data);
+    let new_data = new.map_or(&[][..], |f| f.data);
+
+    if is_binary(old_data) || is_binary(new_data) {
+        out.write_all(&header)?;
+        writeln!(out, "Binary files {} and {} differ", quote_path(&old_name), quote_path(&new_name))?;
+        return Ok(true);
+    }
+
+    let old_lines = split_lines(old_data);
+    let new_lines = split_lines(new_data);
+    let changes = diff_lines(&old_lines, &new_lines, &opts.lines);
+    let hunks = build_hunks(&old_lines, &new_lines, &changes, opts.context, &opts.lines);
+    if hunks.is_empty() {
+        if must_show {
+            out.write_all(&header)?;
+        }
+        return Ok(must_show);
+    }
+
+    out.write_all(&header)?;
+    for (marker, name) in [("---", &old_name), ("+++", &new_name)] {
+        let (set, reset) = if color { (META_COLOR, RESET) } else { ("", "") };
+        writeln!(out, "{}{} {}{}{}", set, marker, quote_path(name), reset, name_terminator(name))?;
+    }
+    match opts.word_diff {
+        Some(mode) => {
+            let mut words = WordDiffWriter::new(out, mode, opts.word_regex.as_ref());
+            write_hunks(&mut words, &old_lines, &new_lines, &changes, &hunks)?;
+            words.finish()?;
+        }
+        None => write_hunks(&mut UnifiedWriter { out, color }, &old_lines, &new_lines, &changes, &hunks)?,
+    }
+    Ok(true)
+}
+
+/// Git ends `---`/`+++` names containing a space with a tab, so the name stays unambiguous.
+fn name_terminator(name: &str) -> &'static str {
+    if name.contains(' ') { "\t" } else { "" }
+}
@@ -0,0 +1,147 @@
+//! Patience diff, as in git's xpatience.c: lines that occur exactly once on
+//! both sides are matched up by their longest common subsequence, and the
+//! gaps between them are diffed the same way. A gap without unique common
+//! lines falls back to Myers.
+
+use std::{collections::HashMap, ops::Range};
+
+use super::{myers, DiffEngine};
+
+pub struct Patience;
+
+impl DiffEngine for Patience {
+    fn mark_changes(&self, a: &[u32], b: &[u32], removed: &mut [bool], added: &mut [bool]) {
+        let mut diff = PatienceDiff { a, b, removed, added };
+        diff.run(0..a.len(), 0..b.len());
+    }
+}
+
+/// A distinct line of the old side's range, in order of first occurrence.
+struct Entry {
+    line1: usize,
+    /// The line on the new side, if the line is unique on both sides.
+    line2: Option<usize>,
+    /// Set when the line repeats on either side.
+    non_unique: bool,
+    previous: Option<usize>,
+}
+
+struct PatienceDiff<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    removed: &'a mut [bool],
+    added: &'a mut [bool],
+}
+
+impl PatienceDiff<'_> {
+    fn run(&mut self, r1: Range<usize>, r2: Range<usize>) {
+        if r1.is_empty() {
+            self.added[r2].fill(true);
+            return;
+        }
+        if r2.is_empty() {
+            self.removed[r1].fill(true);
+            return;
+        }
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut by_line: HashMap<u32, usize> = HashMap::new();
+        for i in r1.clone() {
+            match by_line.get(&self.a[i]) {
+                Some(&e) => entries[e].non_unique = true,
+                None => {
+                    by_line.insert(self.a[i], entries.len());
+                    entries.push(Entry { line1: i, line2: None, non_unique: false, previous: None });
+                }
+            }
+        }
+        let mut has_matches = false;
+        for j in r2.clone() {
+            if let Some(&e) = by_line.get(&self.b[j]) {
+                has_matches = true;
+                let entry = &mut entries[e];
+                if entry.line2.is_some() {
+                    entry.non_unique = true;
+                } else {
+                    entry.line2 = Some(j);
+                }
+            }
+        }
+
+        if !has_matches {
+            self.removed[r1].fill(true);
+            self.added[r2].fill(true);
+            return;
+        }
+
+        let anchors = longest_common_sequence(&mut entries);
+        if anchors.is_empty() {
+            let (a, b) = (&self.a[r1.clone()], &self.b[r2.clone()]);
+            myers::diff(a, b, &mut self.removed[r1], &mut self.added[r2], false);
+            return;
+        }
+        self.walk_common_sequence(&anchors, r1, r2);
+    }
+
+    /// Diffs the gaps around and between the matched unique lines, growing
+    /// each match by the equal lines next to it first.
+    fn walk_common_sequence(&mut self, anchors: &[(usize, usize)], r1: Range<usize>, r2: Range<usize>) {
+        let (mut line1, mut line2) = (r1.start, r2.start);
+        let mut k = 0;
+        loop {
+            let (mut next1, mut next2) = match anchors.get(k) {
+                Some(&(l1, l2)) => (l1, l2),
+                None => (r1.end, r2.end),
+            };
+            if k < anchors.len() {
+                while next1 > line1 && next2 > line2 && self.a[next1 - 1] == self.b[next2 - 1] {
+                    next1 -= 1;
+                    next2 -= 1;
+                }
+            }
+            while line1 < next1 && line2 < next2 && self.a[line1] == self.b[line2] {
+                line1 += 1;
+                line2 += 1;
+            }
+
+            if next1 > line1 || next2 > line2 {
+                self.run(line1..next1, line2..next2);
+            }
+            if k == anchors.len() {
+                return;
+            }
+
+            while k + 1 < anchors.len() && anchors[k + 1] == (anchors[k].0 + 1, anchors[k].1 + 1) {
+                k += 1;
+            }
+            line1 = anchors[k].0 + 1;
+            line2 = anchors[k].1 + 1;
+            k += 1;
+        }
+    }
+}
+
+/// The longest run of unique common lines that is in order on both sides,
+/// found by patience sorting on their new side positions.
+fn longest_common_sequence(entries: &mut [Entry]) -> Vec<(usize, usize)> {
+    let mut sequence: Vec<usize> = Vec::new();
+    for e in 0..entries.len() {
+        let Some(line2) = entries[e].line2.filter(|_| !entries[e].non_unique) else { continue };
+        let i = sequence.partition_point(|&s| entries[s].line2 < Some(line2));
+        entries[e].previous = i.checked_sub(1).map(|p| sequence[p]);
+        if i == sequence.len() {
+            sequence.push(e);
+        } else {
+            sequence[i] = e;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut entry = sequence.last().copied();
+    while let Some(e) = entry {
+        result.push((entries[e].line1, entries[e].line2.unwrap_or_default()));
+        entry = entries[e].previous;
+    }
+    result.reverse();
+    result
+}
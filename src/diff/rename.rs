@@ -0,0 +1,496 @@
+use std::{collections::HashMap, io};
+
+use crate::{commands::parse_error::CommandParseError, config::git_config::{parse_bool, GitConfig}, hash::GitHash, objects::{hash_object, FileMode, ObjectType}, utils::path_utils::file_name};
+
+use super::{changes::{ChangeStatus, DiffSide, FileChange}, is_binary};
+
+/// Similarity scores are fractions of this, as in git.
+pub const MAX_SCORE: u64 = 60000;
+/// Pairs less similar than this are not renames unless asked otherwise (50%).
+const DEFAULT_MIN_SCORE: u64 = 30000;
+/// Git's default `diff.renameLimit`.
+const DEFAULT_RENAME_LIMIT: usize = 1000;
+/// How many of the best sources are remembered for each destination.
+const CANDIDATES_PER_DEST: usize = 4;
+/// Exact matches looked at per destination before settling for the best so far.
+const MAX_IDENTICAL_CANDIDATES: usize = 100;
+/// Modulus of the span hashes used to estimate similarity.
+const SPAN_HASH_BASE: u32 = 107927;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detect {
+    Off,
+    Renames,
+    /// Renames, plus copies from files that are modified (or, with
+    /// `--find-copies-harder`, from any file of the old side).
+    Copies,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameOptions {
+    pub detect: Detect,
+    /// Minimum similarity of an inexact rename or copy, out of [`MAX_SCORE`].
+    pub min_score: u64,
+    /// `--find-copies-harder`: unmodified files are copy sources too.
+    pub find_copies_harder: bool,
+    /// Above this many sources and destinations, only exact renames are found.
+    pub limit: usize,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions { detect: Detect::Off, min_score: DEFAULT_MIN_SCORE, find_copies_harder: false, limit: DEFAULT_RENAME_LIMIT }
+    }
+}
+
+impl RenameOptions {
+    /// The defaults of porcelain `diff`, from `diff.renames` and `diff.renameLimit`.
+    pub fn from_config(config: &GitConfig) -> Self {
+        let detect = match config.get("diff.renames") {
+            Some("copy" | "copies") => Detect::Copies,
+            Some(value) if parse_bool(value) == Some(false) => Detect::Off,
+            _ => Detect::Renames,
+        };
+        let limit = config.get_int("diff.renamelimit").map_or(DEFAULT_RENAME_LIMIT, |n| n.max(0) as usize);
+        RenameOptions { detect, limit, ..RenameOptions::default() }
+    }
+
+    /// Handles `arg` if it is a rename detection option. Returns `false` for anything else.
+    pub fn parse_option(&mut self, arg: &str) -> io::Result<bool> {
+        if let Some(score) = option_value(arg, "-M", "--find-renames") {
+            self.min_score = parse_score(arg, score)?;
+            self.detect = Detect::Renames;
+        } else if let Some(score) = option_value(arg, "-C", "--find-copies") {
+            self.min_score = parse_score(arg, score)?;
+            // like git, a second -C means --find-copies-harder
+            if self.detect == Detect::Copies {
+                self.find_copies_harder = true;
+            }
+            self.detect = Detect::Copies;
+        } else if arg == "--find-copies-harder" {
+            self.find_copies_harder = true;
+        } else if arg == "--no-renames" {
+            self.detect = Detect::Off;
+        } else if let Some(limit) = arg.strip_prefix("-l").filter(|n| !n.is_empty()) {
+            self.limit = limit.parse().map_err(|_| CommandParseError::InvalidArgument(format!("invalid rename limit '{}'", limit)))?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn mode(&self) -> Detect {
+        if self.find_copies_harder { Detect::Copies } else { self.detect }
+    }
+}
+
+/// The score part of `-M<n>`/`--find-renames=<n>`, `Some("")` when there is none.
+fn option_value<'a>(arg: &'a str, short: &str, long: &str) -> Option<&'a str> {
+    if arg == long {
+        return Some("");
+    }
+    arg.strip_prefix(short).or_else(|| arg.strip_prefix(long).and_then(|rest| rest.strip_prefix('=')))
+}
+
+/// Parses a score like git: `50%`, `.5` and `5` all mean half. No score means the default.
+fn parse_score(arg: &str, value: &str) -> io::Result<u64> {
+    let invalid = || CommandParseError::InvalidArgument(format!("invalid argument to {}", arg.trim_end_matches(value)));
+    let (mut num, mut scale, mut dot) = (0u64, 1u64, false);
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if !dot => {
+                scale = 1;
+                dot = true;
+            }
+            '%' => {
+                scale = if dot { scale * 100 } else { 100 };
+                if chars.next().is_some() {
+                    return Err(invalid().into());
+                }
+                break;
+            }
+            '0'..='9' => {
+                if scale < 100000 {
+                    scale *= 10;
+                    num = num * 10 + c.to_digit(10).unwrap_or(0) as u64;
+                }
+            }
+            _ => return Err(invalid().into()),
+        }
+    }
+
+    let score = if num >= scale { MAX_SCORE } else { MAX_SCORE * num / scale };
+    Ok(if score == 0 { DEFAULT_MIN_SCORE } else { score })
+}
+
+/// A file a destination may have been renamed or copied from.
+struct Source {
+    path: String,
+    side: DiffSide,
+    /// Pairs made from this source so far. Sources that stay in the new
+    /// side start at one, so they can only be copied.
+    used: usize,
+    /// Only there because of `--find-copies-harder`.
+    unmodified: bool,
+}
+
+struct Dest {
+    /// Index of the addition in the change list.
+    change: usize,
+    /// The source and score once matched.
+    matched: Option<(usize, u64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    dest: usize,
+    source: usize,
+    score: u64,
+    same_name: bool,
+}
+
+/// Sorts better candidates first: higher score, then an unchanged file name.
+/// Empty slots sink to the end.
+fn candidate_cmp(a: &Option<Candidate>, b: &Option<Candidate>) -> std::cmp::Ordering {
+    match (a, b) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(a), Some(b)) => b.score.cmp(&a.score).then(b.same_name.cmp(&a.same_name)),
+    }
+}
+
+/// The content of a file as far as similarity is concerned.
+struct Content {
+    hash: GitHash,
+    size: usize,
+    /// Bytes per span hash, sorted by hash.
+    spans: Vec<(u32, u64)>,
+}
+
+/// Loads file contents once, however often they are compared.
+#[derive(Default)]
+struct ContentCache {
+    loaded: HashMap<(String, GitHash), Content>,
+}
+
+impl ContentCache {
+    /// Loads a side if needed and returns the key to find it under.
+    fn load(&mut self, path: &str, side: &DiffSide) -> io::Result<(String, GitHash)> {
+        let key = (path.to_string(), side.hash.clone());
+        if !self.loaded.contains_key(&key) {
+            let data = side.load(path)?;
+            let hash = if side.in_worktree() { hash_object(ObjectType::Blob, &data).0 } else { side.hash.clone() };
+            let content = Content { hash, size: data.len(), spans: span_hashes(&data) };
+            self.loaded.insert(key.clone(), content);
+        }
+        Ok(key)
+    }
+
+    /// The object id of a side, hashing working tree files when needed.
+    fn hash(&mut self, path: &str, side: &DiffSide) -> io::Result<GitHash> {
+        if side.in_worktree() {
+            let key = self.load(path, side)?;
+            return Ok(self.loaded[&key].hash.clone());
+        }
+        Ok(side.hash.clone())
+    }
+}
+
+/// Pairs up deleted (and, for copies, kept) files with added ones, the way
+/// git's diffcore-rename does: identical content first, then files with the
+/// same unique name, then the most similar pairs overall. `old_files` lists
+/// the old side and is only called for `--find-copies-harder`.
+pub fn detect_renames<F>(changes: Vec<FileChange>, opts: &RenameOptions, old_files: F) -> io::Result<Vec<FileChange>>
+where
+    F: FnOnce() -> io::Result<Vec<(String, DiffSide)>>,
+{
+    let mode = opts.mode();
+    if mode == Detect::Off {
+        return Ok(changes);
+    }
+    let copies = mode == Detect::Copies;
+
+    let mut sources = Vec::new();
+    let mut dests = Vec::new();
+    for (i, change) in changes.iter().enumerate() {
+        match (change.status, &change.old) {
+            (ChangeStatus::Added, _) => dests.push(Dest { change: i, matched: None }),
+            (ChangeStatus::Deleted, Some(old)) => sources.push(Source { path: change.path.clone(), side: old.clone(), used: 0, unmodified: false }),
+            (ChangeStatus::Modified | ChangeStatus::TypeChanged, Some(old)) if copies => {
+                sources.push(Source { path: change.path.clone(), side: old.clone(), used: 1, unmodified: false });
+            }
+            _ => {}
+        }
+    }
+    if copies && opts.find_copies_harder {
+        let changed: std::collections::HashSet<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        for (path, side) in old_files()? {
+            if !changed.contains(path.as_str()) {
+                sources.push(Source { path, side, used: 1, unmodified: true });
+            }
+        }
+        sources.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+    }
+    if dests.is_empty() || sources.is_empty() {
+        return Ok(changes);
+    }
+
+    let mut cache = ContentCache::default();
+    find_identical(&changes, &mut sources, &mut dests, copies, &mut cache)?;
+
+    if !copies {
+        find_same_names(&changes, &mut sources, &mut dests, opts, &mut cache)?;
+    }
+    find_similar(&changes, &mut sources, &mut dests, opts, copies, &mut cache)?;
+
+    Ok(apply_renames(changes, &mut sources, &dests))
+}
+
+fn find_identical(changes: &[FileChange], sources: &mut [Source], dests: &mut [Dest], copies: bool, cache: &mut ContentCache) -> io::Result<()> {
+    let mut by_hash: HashMap<GitHash, Vec<usize>> = HashMap::new();
+    for (i, source) in sources.iter().enumerate() {
+        by_hash.entry(source.side.hash.clone()).or_default().push(i);
+    }
+
+    for dest in dests.iter_mut() {
+        let change = &changes[dest.change];
+        let target = change.new.as_ref().expect("additions have a new side");
+        let Some(candidates) = by_hash.get(&cache.hash(&change.path, target)?) else { continue };
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut considered = 0;
+        for &i in candidates {
+            let source = &sources[i];
+            // anything but regular files must keep its mode
+            let regular = is_regular(&source.side.mode) && is_regular(&target.mode);
+            if !regular && source.side.mode != target.mode {
+                continue;
+            }
+            if source.used > 0 && !copies {
+                continue;
+            }
+            let score = usize::from(source.used == 0) + usize::from(file_name(&source.path) == file_name(&change.path));
+            if best.map_or(true, |(_, best_score)| score > best_score) {
+                best = Some((i, score));
+                if score == 2 {
+                    break;
+                }
+            }
+            considered += 1;
+            if considered == MAX_IDENTICAL_CANDIDATES {
+                break;
+            }
+        }
+
+        if let Some((i, _)) = best {
+            sources[i].used += 1;
+            dest.matched = Some((i, MAX_SCORE));
+        }
+    }
+    Ok(())
+}
+
+/// Pairs a source and a destination that are the only ones with their file
+/// name, if they are similar enough: most renames move a file without renaming it.
+fn find_same_names(changes: &[FileChange], sources: &mut [Source], dests: &mut [Dest], opts: &RenameOptions, cache: &mut ContentCache) -> io::Result<()> {
+    let min_score = opts.min_score + (MAX_SCORE - opts.min_score) / 2;
+
+    let mut source_names: HashMap<String, Option<usize>> = HashMap::new();
+    for (i, source) in sources.iter().enumerate().filter(|(_, s)| s.used == 0) {
+        source_names.entry(file_name(&source.path).to_string()).and_modify(|unique| *unique = None).or_insert(Some(i));
+    }
+    let mut dest_names: HashMap<&str, Option<usize>> = HashMap::new();
+    for (i, dest) in dests.iter().enumerate().filter(|(_, d)| d.matched.is_none()) {
+        dest_names.entry(file_name(&changes[dest.change].path)).and_modify(|unique| *unique = None).or_insert(Some(i));
+    }
+
+    for (i, source) in sources.iter_mut().enumerate() {
+        if source.used > 0 {
+            continue;
+        }
+        let name = file_name(&source.path);
+        let (Some(&Some(s)), Some(&Some(d))) = (source_names.get(name), dest_names.get(name)) else { continue };
+        if s != i || dests[d].matched.is_some() {
+            continue;
+        }
+        let change = &changes[dests[d].change];
+        let score = estimate_similarity(source, &change.path, change.new.as_ref().expect("additions have a new side"), opts.min_score, cache)?;
+        if score >= min_score {
+            source.used += 1;
+            dests[d].matched = Some((i, score));
+        }
+    }
+    Ok(())
+}
+
+/// Scores every remaining destination against every source and takes the
+/// best pairs first; copies may reuse a source that was already taken.
+fn find_similar(changes: &[FileChange], sources: &mut [Source], dests: &mut [Dest], opts: &RenameOptions, copies: bool, cache: &mut ContentCache) -> io::Result<()> {
+    // without copies, sources used by a rename are done with
+    let usable: Vec<usize> = (0..sources.len()).filter(|&i| copies || sources[i].used == 0).collect();
+    let remaining = dests.iter().filter(|d| d.matched.is_none()).count();
+    if remaining == 0 || usable.is_empty() {
+        return Ok(());
+    }
+
+    let mut skip_unmodified = false;
+    if !within_limit(remaining, usable.len(), opts.limit) {
+        let modified = usable.iter().filter(|&&i| !sources[i].unmodified).count();
+        if opts.find_copies_harder && within_limit(remaining, modified, opts.limit) {
+            eprintln!("warning: only found copies from modified paths due to too many files.");
+            skip_unmodified = true;
+        } else {
+            eprintln!("warning: exhaustive rename detection was skipped due to too many files.");
+            eprintln!("warning: you may want to set your diff.renameLimit variable to at least {} and retry the command.", remaining.max(usable.len()));
+            return Ok(());
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for (d, dest) in dests.iter().enumerate().filter(|(_, d)| d.matched.is_none()) {
+        let change = &changes[dest.change];
+        let target = change.new.as_ref().expect("additions have a new side");
+        let mut best: [Option<Candidate>; CANDIDATES_PER_DEST] = [None; CANDIDATES_PER_DEST];
+
+        for &s in &usable {
+            if skip_unmodified && sources[s].unmodified {
+                continue;
+            }
+            let score = estimate_similarity(&sources[s], &change.path, target, opts.min_score, cache)?;
+            let candidate = Some(Candidate { dest: d, source: s, score, same_name: file_name(&sources[s].path) == file_name(&change.path) });
+
+            let mut worst = 0;
+            for i in 1..CANDIDATES_PER_DEST {
+                if candidate_cmp(&best[i], &best[worst]).is_gt() {
+                    worst = i;
+                }
+            }
+            if candidate_cmp(&best[worst], &candidate).is_gt() {
+                best[worst] = candidate;
+            }
+        }
+        candidates.extend(best);
+    }
+    candidates.sort_by(candidate_cmp);
+
+    for copy_pass in [false, true] {
+        if copy_pass && !copies {
+            break;
+        }
+        for candidate in candidates.iter().flatten() {
+            if candidate.score < opts.min_score {
+                break;
+            }
+            if dests[candidate.dest].matched.is_some() || (!copy_pass && sources[candidate.source].used > 0) {
+                continue;
+            }
+            sources[candidate.source].used += 1;
+            dests[candidate.dest].matched = Some((candidate.source, candidate.score));
+        }
+    }
+    Ok(())
+}
+
+fn within_limit(dests: usize, sources: usize, limit: usize) -> bool {
+    (dests <= limit || sources <= limit) && (dests as u64) * (sources as u64) <= (limit as u64) * (limit as u64)
+}
+
+/// Replaces matched additions with renames and copies, and drops the
+/// deletions that were renamed away. A source used several times is copied
+/// to all but its last destination, which it is renamed to.
+fn apply_renames(changes: Vec<FileChange>, sources: &mut [Source], dests: &[Dest]) -> Vec<FileChange> {
+    let matches: HashMap<usize, (usize, u64)> = dests.iter().filter_map(|d| d.matched.map(|m| (d.change, m))).collect();
+    let renamed_away: std::collections::HashSet<String> = sources.iter().filter(|s| s.used > 0 && !s.unmodified).map(|s| s.path.clone()).collect();
+
+    let mut result = Vec::with_capacity(changes.len());
+    for (i, change) in changes.into_iter().enumerate() {
+        if let Some(&(s, score)) = matches.get(&i) {
+            let source = &mut sources[s];
+            source.used -= 1;
+            let status = if source.used > 0 { ChangeStatus::Copied } else { ChangeStatus::Renamed };
+            let similarity = (score * 100 / MAX_SCORE) as u32;
+            result.push(FileChange::renamed(status, source.path.clone(), source.side.clone(), change.path, change.new, similarity));
+            continue;
+        }
+        if change.status == ChangeStatus::Deleted && renamed_away.contains(&change.path) {
+            continue;
+        }
+        result.push(change);
+    }
+    result
+}
+
+fn is_regular(mode: &FileMode) -> bool {
+    matches!(mode, FileMode::Normal | FileMode::Executable)
+}
+
+/// How much of the destination's content comes from the source, out of
+/// [`MAX_SCORE`]. Only regular files can be renamed with edits.
+fn estimate_similarity(source: &Source, path: &str, target: &DiffSide, min_score: u64, cache: &mut ContentCache) -> io::Result<u64> {
+    if !is_regular(&source.side.mode) || !is_regular(&target.mode) {
+        return Ok(0);
+    }
+
+    let (src_key, dst_key) = (cache.load(&source.path, &source.side)?, cache.load(path, target)?);
+    let (src, dst) = (&cache.loaded[&src_key], &cache.loaded[&dst_key]);
+    let (src_size, dst_size) = (src.size as u64, dst.size as u64);
+    let max_size = src_size.max(dst_size);
+    let delta_size = max_size - src_size.min(dst_size);
+    // edits that change the size this much can't leave the files similar enough
+    if max_size * (MAX_SCORE - min_score) < delta_size * MAX_SCORE {
+        return Ok(0);
+    }
+    if dst_size == 0 {
+        return Ok(0);
+    }
+
+    let copied = copied_bytes(&src.spans, &dst.spans);
+    Ok(copied * MAX_SCORE / max_size)
+}
+
+/// Splits content into spans ending at a newline or after 64 bytes and adds
+/// up the bytes of spans with the same hash. Text ignores the CR of CRLF.
+fn span_hashes(data: &[u8]) -> Vec<(u32, u64)> {
+    let text = !is_binary(data);
+    let mut counts: HashMap<u32, u64> = HashMap::new();
+    let (mut accum1, mut accum2, mut n) = (0u32, 0u32, 0u64);
+
+    for (i, &c) in data.iter().enumerate() {
+        if text && c == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        let old1 = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old1 >> 25);
+        accum1 = accum1.wrapping_add(c as u32);
+        n += 1;
+        if n < 64 && c != b'\n' {
+            continue;
+        }
+        *counts.entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % SPAN_HASH_BASE).or_default() += n;
+        (accum1, accum2, n) = (0, 0, 0);
+    }
+    if n > 0 {
+        *counts.entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % SPAN_HASH_BASE).or_default() += n;
+    }
+
+    let mut spans: Vec<(u32, u64)> = counts.into_iter().collect();
+    spans.sort_unstable();
+    spans
+}
+
+/// Bytes of the destination that also appear in the source, span by span.
+fn copied_bytes(source: &[(u32, u64)], dest: &[(u32, u64)]) -> u64 {
+    let mut copied = 0;
+    let mut d = dest.iter().peekable();
+    for &(hash, src_count) in source {
+        while d.next_if(|&&(h, _)| h < hash).is_some() {}
+        if let Some(&(_, dst_count)) = d.next_if(|&&(h, _)| h == hash) {
+            copied += src_count.min(dst_count);
+        }
+    }
+    copied
+}
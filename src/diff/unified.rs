@@ -0,0 +1,207 @@
+use std::{io::{self, Write}, ops::Range};
+
+use super::{is_blank_line, Change, LineDiffOptions};
+
+/// How many bytes of a function line git shows after a hunk header.
+const FUNC_CONTEXT_LEN: usize = 80;
+
+/// The colors of git's default palette used in colored output.
+pub const RESET: &str = "\x1b[m";
+pub const META_COLOR: &str = "\x1b[1m";
+pub const OLD_COLOR: &str = "\x1b[31m";
+pub const NEW_COLOR: &str = "\x1b[32m";
+const FRAG_COLOR: &str = "\x1b[36m";
+
+/// A block of output: changes close enough to share their context lines.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    /// The changes shown in this hunk, as indices into the change list.
+    pub changes: Range<usize>,
+}
+
+/// Receives the hunks of a unified diff: a header for each, then its lines.
+pub trait HunkSink {
+    fn header(&mut self, ranges: &str, func: Option<&[u8]>) -> io::Result<()>;
+    /// A line with its `' '`, `'-'` or `'+'` prefix; only the last line may lack a newline.
+    fn line(&mut self, prefix: u8, line: &[u8]) -> io::Result<()>;
+}
+
+/// Groups changes into hunks with `context` lines around them, the way git's
+/// xdl_get_hunk does. Changes whose context would touch or overlap are merged
+/// into one hunk. With `--ignore-blank-lines`, changes of only blank lines
+/// are dropped unless they are close to a change that is shown.
+pub fn build_hunks(old: &[&[u8]], new: &[&[u8]], changes: &[Change], context: usize, opts: &LineDiffOptions) -> Vec<Hunk> {
+    let ignorable: Vec<bool> = changes
+        .iter()
+        .map(|c| {
+            opts.ignore_blank_lines
+                && old[c.old.clone()].iter().chain(&new[c.new.clone()]).all(|line| is_blank_line(line, opts.whitespace))
+        })
+        .collect();
+    let max_common = 2 * context;
+    let max_ignorable = context;
+
+    let mut hunks = Vec::new();
+    let mut first = 0;
+    loop {
+        // leading ignorable changes go unless the next change is close
+        let mut i = first;
+        while i < changes.len() && ignorable[i] {
+            if i + 1 == changes.len() || changes[i + 1].old.start - changes[i].old.end >= max_ignorable {
+                first = i + 1;
+            }
+            i += 1;
+        }
+        if first >= changes.len() {
+            break;
+        }
+
+        let mut last = first;
+        let mut ignored = 0;
+        for cur in first + 1..changes.len() {
+            let distance = changes[cur].old.start - changes[cur - 1].old.end;
+            if distance > max_common {
+                break;
+            }
+            if distance < max_ignorable && (!ignorable[cur] || last == cur - 1) {
+                last = cur;
+                ignored = 0;
+            } else if distance < max_ignorable {
+                ignored += changes[cur].new.len();
+            } else if last != cur - 1 && changes[cur].old.start + ignored - changes[last].old.end > max_common {
+                break;
+            } else if !ignorable[cur] {
+                last = cur;
+                ignored = 0;
+            } else {
+                ignored += changes[cur].new.len();
+            }
+        }
+
+        let (head, tail) = (&changes[first], &changes[last]);
+        let after = context.min(old.len() - tail.old.end).min(new.len() - tail.new.end);
+        hunks.push(Hunk {
+            old: head.old.start.saturating_sub(context)..tail.old.end + after,
+            new: head.new.start.saturating_sub(context)..tail.new.end + after,
+            changes: first..last + 1,
+        });
+        first = last + 1;
+    }
+
+    hunks
+}
+
+/// Feeds the hunks of a unified diff to `sink`. Like git, context lines come
+/// from the new side, which matters when whitespace differences are ignored.
+pub fn write_hunks(sink: &mut impl HunkSink, old: &[&[u8]], new: &[&[u8]], changes: &[Change], hunks: &[Hunk]) -> io::Result<()> {
+    let mut funcs = FunctionFinder::default();
+    for hunk in hunks {
+        let ranges = format!("-{} +{}", format_range(&hunk.old), format_range(&hunk.new));
+        sink.header(&ranges, funcs.find(old, hunk.old.start))?;
+
+        let mut pos = (hunk.old.start, hunk.new.start);
+        let head = &changes[hunk.changes.start];
+        for line in &new[pos.1..head.new.start] {
+            sink.line(b' ', line)?;
+        }
+        pos = (head.old.start, head.new.start);
+        for change in &changes[hunk.changes.clone()] {
+            while pos.0 < change.old.start && pos.1 < change.new.start {
+                sink.line(b' ', new[pos.1])?;
+                pos = (pos.0 + 1, pos.1 + 1);
+            }
+            for line in &old[change.old.clone()] {
+                sink.line(b'-', line)?;
+            }
+            for line in &new[change.new.clone()] {
+                sink.line(b'+', line)?;
+            }
+            pos = (change.old.end, change.new.end);
+        }
+        for line in &new[pos.1..hunk.new.end] {
+            sink.line(b' ', line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes hunks as plain unified diff text.
+pub struct UnifiedWriter<'a, W: Write> {
+    pub out: &'a mut W,
+    pub color: bool,
+}
+
+impl<W: Write> HunkSink for UnifiedWriter<'_, W> {
+    fn header(&mut self, ranges: &str, func: Option<&[u8]>) -> io::Result<()> {
+        if self.color {
+            write!(self.out, "{}@@ {} @@{}", FRAG_COLOR, ranges, RESET)?;
+            if let Some(func) = func {
+                write!(self.out, " {}", RESET)?;
+                self.out.write_all(func)?;
+                self.out.write_all(RESET.as_bytes())?;
+            }
+        } else {
+            write!(self.out, "@@ {} @@", ranges)?;
+            if let Some(func) = func {
+                self.out.write_all(b" ")?;
+                self.out.write_all(func)?;
+            }
+        }
+        self.out.write_all(b"\n")
+    }
+
+    fn line(&mut self, prefix: u8, line: &[u8]) -> io::Result<()> {
+        self.out.write_all(&[prefix])?;
+        self.out.write_all(line)?;
+        if !line.ends_with(b"\n") {
+            self.out.write_all(b"\n\\ No newline at end of file\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// `start,len` with 1-based line numbers; an empty range names the line before it.
+fn format_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => format!("{}", range.start + 1),
+        len => format!("{},{}", range.start + 1, len),
+    }
+}
+
+/// Finds the line shown after each hunk header. Hunks come in order, so each
+/// search only needs to scan the lines since the previous one.
+#[derive(Default)]
+struct FunctionFinder<'a> {
+    scanned: usize,
+    last: Option<&'a [u8]>,
+}
+
+impl<'a> FunctionFinder<'a> {
+    /// The nearest line above `before` that looks like the start of a function,
+    /// using git's default rule: it begins with a letter, `_` or `$`.
+    fn find(&mut self, lines: &[&'a [u8]], before: usize) -> Option<&'a [u8]> {
+        let found = lines[self.scanned.min(before)..before]
+            .iter()
+            .rev()
+            .find(|line| line.first().is_some_and(|&c| c.is_ascii_alphabetic() || c == b'_' || c == b'$'));
+        if let Some(line) = found {
+            self.last = Some(trim_function_line(line));
+        }
+        self.scanned = before;
+        self.last
+    }
+}
+
+fn trim_function_line(line: &[u8]) -> &[u8] {
+    let mut line = &line[..line.len().min(FUNC_CONTEXT_LEN)];
+    while let [rest @ .., last] = line {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        line = rest;
+    }
+    line
+}
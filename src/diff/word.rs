@@ -0,0 +1,234 @@
+//! Word diff, as in git's `--word-diff`: the removed and added lines of each
+//! run are split into words, the words are diffed, and the new text is shown
+//! with the changed words marked inline.
+
+use std::{io::{self, Write}, ops::Range};
+
+use regex::bytes::Regex;
+
+use super::{diff_lines, is_space, unified::{HunkSink, UnifiedWriter, NEW_COLOR, OLD_COLOR, RESET}, LineDiffOptions};
+
+/// How changed words are marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDiff {
+    /// `[-old-]{+new+}`
+    Plain,
+    /// Changed words in red and green, without markers.
+    Color,
+    /// A line per word with a `-`, `+` or ` ` prefix and `~` for each newline, for scripts.
+    Porcelain,
+}
+
+impl WordDiff {
+    /// Parses the mode of `--word-diff=<mode>`; `none` turns word diff off.
+    pub fn parse(mode: &str) -> Option<Option<WordDiff>> {
+        match mode {
+            "plain" => Some(Some(WordDiff::Plain)),
+            "color" => Some(Some(WordDiff::Color)),
+            "porcelain" => Some(Some(WordDiff::Porcelain)),
+            "none" => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// How one kind of text is written: marker around it and its color.
+struct Style {
+    prefix: &'static str,
+    suffix: &'static str,
+    color: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Context,
+    Old,
+    New,
+}
+
+/// Collects the lines of each hunk and writes them as a word diff.
+pub struct WordDiffWriter<'a, W: Write> {
+    out: &'a mut W,
+    mode: WordDiff,
+    regex: Option<&'a Regex>,
+    minus: Vec<u8>,
+    plus: Vec<u8>,
+}
+
+impl<'a, W: Write> WordDiffWriter<'a, W> {
+    pub fn new(out: &'a mut W, mode: WordDiff, regex: Option<&'a Regex>) -> Self {
+        WordDiffWriter { out, mode, regex, minus: Vec::new(), plus: Vec::new() }
+    }
+
+    /// Writes the words still pending at the end of the last hunk.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush_words()
+    }
+
+    fn style(&self, kind: Kind) -> Style {
+        let (prefix, suffix) = match (self.mode, kind) {
+            (WordDiff::Plain, Kind::Old) => ("[-", "-]"),
+            (WordDiff::Plain, Kind::New) => ("{+", "+}"),
+            (WordDiff::Porcelain, Kind::Old) => ("-", "\n"),
+            (WordDiff::Porcelain, Kind::New) => ("+", "\n"),
+            (WordDiff::Porcelain, Kind::Context) => (" ", "\n"),
+            _ => ("", ""),
+        };
+        let color = match (self.mode, kind) {
+            (WordDiff::Color, Kind::Old) => OLD_COLOR,
+            (WordDiff::Color, Kind::New) => NEW_COLOR,
+            _ => "",
+        };
+        Style { prefix, suffix, color }
+    }
+
+    fn newline(&self) -> &'static str {
+        if self.mode == WordDiff::Porcelain { "~\n" } else { "\n" }
+    }
+
+    /// Diffs the words of the collected lines and writes the new text with
+    /// the changes marked.
+    fn flush_words(&mut self) -> io::Result<()> {
+        if self.minus.is_empty() && self.plus.is_empty() {
+            return Ok(());
+        }
+        let minus = std::mem::take(&mut self.minus);
+        let plus = std::mem::take(&mut self.plus);
+        if plus.is_empty() {
+            return self.write_text(Kind::Old, &minus);
+        }
+
+        let minus_words = split_words(&minus, self.regex);
+        let plus_words = split_words(&plus, self.regex);
+        let old: Vec<&[u8]> = minus_words.iter().map(|w| &minus[w.clone()]).collect();
+        let new: Vec<&[u8]> = plus_words.iter().map(|w| &plus[w.clone()]).collect();
+        let opts = LineDiffOptions { indent_heuristic: false, ..LineDiffOptions::default() };
+
+        // like git, an empty run of words sits at the end of the word before it
+        let span = |words: &[Range<usize>], run: &Range<usize>| {
+            if run.is_empty() {
+                let at = run.start.checked_sub(1).map_or(0, |i| words[i].end);
+                at..at
+            } else {
+                words[run.start].start..words[run.end - 1].end
+            }
+        };
+        let mut current = 0;
+        for change in diff_lines(&old, &new, &opts) {
+            let removed = span(&minus_words, &change.old);
+            let added = span(&plus_words, &change.new);
+            if current != added.start {
+                self.write_text(Kind::Context, &plus[current..added.start])?;
+            }
+            if !removed.is_empty() {
+                self.write_text(Kind::Old, &minus[removed])?;
+            }
+            if !added.is_empty() {
+                self.write_text(Kind::New, &plus[added.clone()])?;
+            }
+            current = added.end;
+        }
+        if current != plus.len() {
+            self.write_text(Kind::Context, &plus[current..])?;
+        }
+        Ok(())
+    }
+
+    /// Writes an unchanged line; only porcelain output keeps its prefix.
+    fn write_context_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+        if self.mode == WordDiff::Porcelain {
+            self.out.write_all(b" ")?;
+            self.out.write_all(text)?;
+            return self.out.write_all(b"\n~\n");
+        }
+
+        let (text, cr) = match text.strip_suffix(b"\r") {
+            Some(text) => (text, "\r"),
+            None => (text, ""),
+        };
+        self.out.write_all(text)?;
+        if self.mode == WordDiff::Color && !text.is_empty() {
+            self.out.write_all(RESET.as_bytes())?;
+        }
+        writeln!(self.out, "{}", cr)
+    }
+
+    /// Writes text in a style, ending the style at each newline.
+    fn write_text(&mut self, kind: Kind, text: &[u8]) -> io::Result<()> {
+        let style = self.style(kind);
+        let newline = self.newline();
+        let mut segments = text.split(|&c| c == b'\n').peekable();
+        while let Some(segment) = segments.next() {
+            if !segment.is_empty() {
+                write!(self.out, "{}{}", style.color, style.prefix)?;
+                self.out.write_all(segment)?;
+                self.out.write_all(style.suffix.as_bytes())?;
+                if !style.color.is_empty() {
+                    self.out.write_all(RESET.as_bytes())?;
+                }
+            }
+            if segments.peek().is_some() {
+                self.out.write_all(newline.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> HunkSink for WordDiffWriter<'_, W> {
+    fn header(&mut self, ranges: &str, func: Option<&[u8]>) -> io::Result<()> {
+        self.flush_words()?;
+        UnifiedWriter { out: &mut *self.out, color: self.mode == WordDiff::Color }.header(ranges, func)
+    }
+
+    fn line(&mut self, prefix: u8, line: &[u8]) -> io::Result<()> {
+        let buffer = match prefix {
+            b'-' => &mut self.minus,
+            b'+' => &mut self.plus,
+            _ => {
+                self.flush_words()?;
+                return self.write_context_line(line);
+            }
+        };
+        buffer.extend_from_slice(line);
+        if !line.ends_with(b"\n") {
+            buffer.push(b'\n');
+        }
+        Ok(())
+    }
+}
+
+/// The words of `text`: matches of the word regex, cut at newlines, or else
+/// runs of non-whitespace.
+fn split_words(text: &[u8], regex: Option<&Regex>) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut begin = 0;
+    while begin < text.len() {
+        let end;
+        match regex {
+            Some(regex) => {
+                let Some(m) = regex.find(&text[begin..]) else { break };
+                let start = begin + m.start();
+                end = text[start..begin + m.end()].iter().position(|&c| c == b'\n').map_or(begin + m.end(), |p| start + p);
+                begin = start;
+                if begin == end {
+                    begin += 1;
+                    continue;
+                }
+            }
+            None => {
+                while begin < text.len() && is_space(text[begin]) {
+                    begin += 1;
+                }
+                if begin == text.len() {
+                    break;
+                }
+                end = begin + text[begin..].iter().position(|&c| is_space(c)).unwrap_or(text.len() - begin);
+            }
+        }
+        words.push(begin..end);
+        begin = end;
+    }
+    words
+}
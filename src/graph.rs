@@ -0,0 +1,603 @@
+//! The ASCII history graph drawn to the left of `log --graph` output.
+//!
+//! This follows git's renderer: each commit takes one or more lines of graph
+//! (lines to make room for an octopus merge, the commit line itself, the
+//! edges out of a merge, and lines that move branch lanes left into place),
+//! and the text of the commit is printed alongside them.
+
+use std::{io::{self, Write}, mem};
+
+use crate::hash::GitHash;
+
+/// Which kind of graph line comes next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Branch lines carried straight down, between commits.
+    Padding,
+    /// `...` where the previous commit's lines were cut short.
+    Skip,
+    /// Widening the lanes to the right of an octopus merge.
+    PreCommit,
+    /// The line with the `*`.
+    Commit,
+    /// The edges from a merge to its parents.
+    PostMerge,
+    /// Moving lanes left towards their final columns.
+    Collapsing,
+}
+
+pub struct Graph {
+    commit: Option<GitHash>,
+    /// The parents of the current commit that are in the graph.
+    parents: Vec<GitHash>,
+    width: isize,
+    expansion_row: isize,
+    state: State,
+    prev_state: State,
+    commit_index: isize,
+    prev_commit_index: isize,
+    /// How a merge's edges leave the commit: 0 when its first parent is in
+    /// a column to the left, 1 otherwise, -1 before that is known.
+    merge_layout: isize,
+    /// How many columns the current commit adds to the right of it.
+    edges_added: isize,
+    prev_edges_added: isize,
+    /// The commit each lane leads to, before and after the current commit.
+    columns: Vec<GitHash>,
+    new_columns: Vec<GitHash>,
+    /// For each screen position (two per lane), the lane in `new_columns`
+    /// the line there is headed for, or -1.
+    mapping: Vec<isize>,
+    old_mapping: Vec<isize>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Graph {
+            commit: None,
+            parents: Vec::new(),
+            width: 0,
+            expansion_row: 0,
+            state: State::Padding,
+            prev_state: State::Padding,
+            commit_index: 0,
+            prev_commit_index: 0,
+            merge_layout: 0,
+            edges_added: 0,
+            prev_edges_added: 0,
+            columns: Vec::new(),
+            new_columns: Vec::new(),
+            mapping: Vec::new(),
+            old_mapping: Vec::new(),
+        }
+    }
+}
+
+/// One line of graph being built, with its width in characters.
+struct Line {
+    text: String,
+    width: isize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Line { text: String::new(), width: 0 }
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.width += 1;
+    }
+
+    fn push_n(&mut self, c: char, n: isize) {
+        for _ in 0..n {
+            self.push(c);
+        }
+    }
+}
+
+const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+
+impl Graph {
+    pub fn new() -> Self {
+        Graph::default()
+    }
+
+    /// Moves on to the next commit to draw. `parents` are those of its
+    /// parents that will be drawn too, in order.
+    pub fn update(&mut self, commit: &GitHash, parents: Vec<GitHash>) {
+        self.commit = Some(commit.clone());
+        self.parents = parents;
+        self.prev_commit_index = self.commit_index;
+        self.update_columns();
+        self.expansion_row = 0;
+
+        // if the previous commit's lines never finished, mark the gap
+        self.state = if self.state != State::Padding {
+            State::Skip
+        } else if self.needs_pre_commit_line() {
+            State::PreCommit
+        } else {
+            State::Commit
+        };
+    }
+
+    fn num_parents(&self) -> isize {
+        self.parents.len() as isize
+    }
+
+    fn is_current(&self, hash: &GitHash) -> bool {
+        self.commit.as_ref() == Some(hash)
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.prev_state = self.state;
+        self.state = state;
+    }
+
+    fn mapping_at(mapping: &[isize], i: isize) -> isize {
+        usize::try_from(i).ok().and_then(|i| mapping.get(i)).copied().unwrap_or(-1)
+    }
+
+    fn set_mapping(&mut self, i: isize, target: isize) {
+        let i = i as usize;
+        if i >= self.mapping.len() {
+            self.mapping.resize(i + 1, -1);
+        }
+        self.mapping[i] = target;
+    }
+
+    fn find_new_column(&self, commit: &GitHash) -> Option<usize> {
+        self.new_columns.iter().position(|c| c == commit)
+    }
+
+    fn insert_into_new_columns(&mut self, commit: &GitHash, idx: isize) {
+        let i = match self.find_new_column(commit) {
+            Some(i) => i as isize,
+            None => {
+                self.new_columns.push(commit.clone());
+                self.new_columns.len() as isize - 1
+            }
+        };
+
+        let mapping_idx;
+        if self.num_parents() > 1 && idx > -1 && self.merge_layout == -1 {
+            // the first parent of a merge picks the layout of the merge's
+            // edges, depending on whether that parent is to its left
+            let dist = idx - i;
+            let shift = if dist > 1 { 2 * dist - 3 } else { 1 };
+            self.merge_layout = if dist > 0 { 0 } else { 1 };
+            self.edges_added = self.num_parents() + self.merge_layout - 2;
+            mapping_idx = self.width + (self.merge_layout - 1) * shift;
+            self.width += 2 * self.merge_layout;
+        } else if self.edges_added > 0 && i == Self::mapping_at(&self.mapping, self.width - 2) {
+            // an edge added by the merge joins the lane right next to it
+            mapping_idx = self.width - 2;
+            self.edges_added = -1;
+        } else {
+            mapping_idx = self.width;
+            self.width += 2;
+        }
+        self.set_mapping(mapping_idx, i);
+    }
+
+    fn update_columns(&mut self) {
+        mem::swap(&mut self.columns, &mut self.new_columns);
+        self.new_columns.clear();
+
+        let max_new_columns = self.columns.len() + self.parents.len();
+        self.mapping = vec![-1; 2 * max_new_columns];
+        self.width = 0;
+        self.prev_edges_added = self.edges_added;
+        self.edges_added = 0;
+
+        // the current commit may not be in any lane yet, in which case it
+        // gets a new one at the end
+        let commit = self.commit.clone().expect("graph updated without a commit");
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let col_commit = if i == self.columns.len() {
+                if seen_this {
+                    break;
+                }
+                commit.clone()
+            } else {
+                self.columns[i].clone()
+            };
+
+            if col_commit == commit {
+                seen_this = true;
+                self.commit_index = i as isize;
+                self.merge_layout = -1;
+                for parent in self.parents.clone() {
+                    self.insert_into_new_columns(&parent, i as isize);
+                }
+                // the commit always takes up at least one lane
+                if self.parents.is_empty() {
+                    self.width += 2;
+                }
+            } else {
+                self.insert_into_new_columns(&col_commit, -1);
+            }
+        }
+
+        while self.mapping.len() > 1 && self.mapping.last().is_some_and(|&m| m < 0) {
+            self.mapping.pop();
+        }
+    }
+
+    fn num_dashed_parents(&self) -> isize {
+        self.num_parents() + self.merge_layout - 3
+    }
+
+    /// An octopus merge needs two rows per dashed parent to widen the lanes
+    /// to its right.
+    fn num_expansion_rows(&self) -> isize {
+        self.num_dashed_parents() * 2
+    }
+
+    fn needs_pre_commit_line(&self) -> bool {
+        self.num_parents() >= 3
+            && self.commit_index < self.columns.len() as isize - 1
+            && self.expansion_row < self.num_expansion_rows()
+    }
+
+    /// Whether every line has reached its lane; a line one position to the
+    /// right of it is fine too, as it is drawn as `/`.
+    fn is_mapping_correct(&self) -> bool {
+        self.mapping.iter().enumerate().all(|(i, &target)| target < 0 || target == i as isize / 2)
+    }
+
+    /// Whether all the lines of the current commit have been drawn.
+    pub fn is_commit_finished(&self) -> bool {
+        self.state == State::Padding
+    }
+
+    fn pad(&self, line: &mut Line) {
+        if line.width < self.width {
+            line.push_n(' ', self.width - line.width);
+        }
+    }
+
+    fn padding_line(&self, line: &mut Line) {
+        for _ in &self.new_columns {
+            line.push('|');
+            line.push(' ');
+        }
+    }
+
+    fn skip_line(&mut self, line: &mut Line) {
+        line.text.push_str("...");
+        line.width += 3;
+        if self.needs_pre_commit_line() {
+            self.set_state(State::PreCommit);
+        } else {
+            self.set_state(State::Commit);
+        }
+    }
+
+    fn pre_commit_line(&mut self, line: &mut Line) {
+        let mut seen_this = false;
+        for (i, col) in self.columns.iter().enumerate() {
+            if self.is_current(col) {
+                seen_this = true;
+                line.push('|');
+                line.push_n(' ', self.expansion_row);
+            } else if seen_this && self.expansion_row == 0 {
+                // lines after a merge that were drawn as `\` stay that way
+                if self.prev_state == State::PostMerge && self.prev_commit_index < i as isize {
+                    line.push('\\');
+                } else {
+                    line.push('|');
+                }
+            } else if seen_this {
+                line.push('\\');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        self.expansion_row += 1;
+        if !self.needs_pre_commit_line() {
+            self.set_state(State::Commit);
+        }
+    }
+
+    /// The dashes from an octopus merge's `*` to its later parents.
+    fn octopus_merge(&self, line: &mut Line) {
+        let dashed_parents = self.num_dashed_parents();
+        for i in 0..dashed_parents {
+            line.push('-');
+            line.push(if i == dashed_parents - 1 { '.' } else { '-' });
+        }
+    }
+
+    fn commit_line(&mut self, line: &mut Line) {
+        let mut seen_this = false;
+        for i in 0..=self.columns.len() {
+            let col_commit = if i == self.columns.len() {
+                if seen_this {
+                    break;
+                }
+                self.commit.clone().expect("graph updated without a commit")
+            } else {
+                self.columns[i].clone()
+            };
+            let i = i as isize;
+
+            if self.is_current(&col_commit) {
+                seen_this = true;
+                line.push('*');
+                if self.num_parents() > 2 {
+                    self.octopus_merge(line);
+                }
+            } else if seen_this && self.edges_added > 1 {
+                line.push('\\');
+            } else if seen_this && self.edges_added == 1 {
+                // the first line of this commit; a line drawn as `\` on
+                // the previous merge's last line keeps going that way
+                if self.prev_state == State::PostMerge && self.prev_edges_added > 0 && self.prev_commit_index < i {
+                    line.push('\\');
+                } else {
+                    line.push('|');
+                }
+            } else if self.prev_state == State::Collapsing
+                && Self::mapping_at(&self.old_mapping, 2 * i + 1) == i
+                && Self::mapping_at(&self.mapping, 2 * i) < i
+            {
+                line.push('/');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        if self.num_parents() > 1 {
+            self.set_state(State::PostMerge);
+        } else if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+    }
+
+    fn post_merge_line(&mut self, line: &mut Line) {
+        let first_parent = self.parents[0].clone();
+        let mut seen_this = false;
+        let mut parent_col_seen = false;
+        for i in 0..=self.columns.len() {
+            let col_commit = if i == self.columns.len() {
+                if seen_this {
+                    break;
+                }
+                self.commit.clone().expect("graph updated without a commit")
+            } else {
+                self.columns[i].clone()
+            };
+            let i = i as isize;
+
+            if self.is_current(&col_commit) {
+                // one edge per parent, towards the lane it is in
+                seen_this = true;
+                let mut idx = self.merge_layout as usize;
+                for j in 0..self.parents.len() {
+                    line.push(MERGE_CHARS[idx]);
+                    if idx == 2 {
+                        if self.edges_added > 0 || j < self.parents.len() - 1 {
+                            line.push(' ');
+                        }
+                    } else {
+                        idx += 1;
+                    }
+                }
+                if self.edges_added == 0 {
+                    line.push(' ');
+                }
+            } else if seen_this {
+                line.push(if self.edges_added > 0 { '\\' } else { '|' });
+                line.push(' ');
+            } else {
+                line.push('|');
+                if self.merge_layout != 0 || i != self.commit_index - 1 {
+                    line.push(if parent_col_seen { '_' } else { ' ' });
+                }
+            }
+
+            if col_commit == first_parent {
+                parent_col_seen = true;
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+    }
+
+    fn collapsing_line(&mut self, line: &mut Line) {
+        let mut used_horizontal = false;
+        let mut horizontal_edge = -1;
+        let mut horizontal_edge_target = -1;
+
+        let size = self.mapping.len();
+        self.old_mapping = mem::replace(&mut self.mapping, vec![-1; size]);
+
+        for i in 0..size as isize {
+            let target = self.old_mapping[i as usize];
+            if target < 0 {
+                continue;
+            }
+            // lines only ever move left, so when lines cross, only one of
+            // them is moving
+            if target * 2 == i {
+                self.mapping[i as usize] = target;
+            } else if self.mapping[i as usize - 1] < 0 {
+                // nothing to the left: move one position left
+                self.mapping[i as usize - 1] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge = i;
+                    horizontal_edge_target = target;
+                    let mut j = target * 2 + 3;
+                    while j < i - 2 {
+                        self.mapping[j as usize] = target;
+                        j += 2;
+                    }
+                }
+            } else if self.mapping[i as usize - 1] == target {
+                // the line to the left goes to the same lane: merge into it
+            } else {
+                // cross over the line to the left
+                self.mapping[i as usize - 2] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge_target = target;
+                    horizontal_edge = i - 1;
+                    let mut j = target * 2 + 3;
+                    while j < i - 2 {
+                        self.mapping[j as usize] = target;
+                        j += 2;
+                    }
+                }
+            }
+        }
+
+        self.old_mapping.copy_from_slice(&self.mapping);
+        if self.mapping.last().is_some_and(|&m| m < 0) {
+            self.mapping.pop();
+        }
+
+        for i in 0..self.mapping.len() as isize {
+            let target = self.mapping[i as usize];
+            if target < 0 {
+                line.push(' ');
+            } else if target * 2 == i {
+                line.push('|');
+            } else if target == horizontal_edge_target && i != horizontal_edge - 1 {
+                // only the first segment of a horizontal edge carries on
+                // to the next line
+                if i != target * 2 + 3 {
+                    self.mapping[i as usize] = -1;
+                }
+                used_horizontal = true;
+                line.push('_');
+            } else {
+                if used_horizontal && i < horizontal_edge {
+                    self.mapping[i as usize] = -1;
+                }
+                line.push('/');
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        }
+    }
+
+    /// The next line of graph, and whether it is the commit's own line.
+    fn next_line(&mut self) -> (String, bool) {
+        let mut line = Line::new();
+        if self.commit.is_none() {
+            return (line.text, false);
+        }
+        let mut shown_commit_line = false;
+        match self.state {
+            State::Padding => self.padding_line(&mut line),
+            State::Skip => self.skip_line(&mut line),
+            State::PreCommit => self.pre_commit_line(&mut line),
+            State::Commit => {
+                self.commit_line(&mut line);
+                shown_commit_line = true;
+            }
+            State::PostMerge => self.post_merge_line(&mut line),
+            State::Collapsing => self.collapsing_line(&mut line),
+        }
+        self.pad(&mut line);
+        (line.text, shown_commit_line)
+    }
+
+    /// The graph to put before lines that come between commits' graph
+    /// lines, such as a diff: the lanes carried straight down.
+    pub fn padding(&mut self) -> String {
+        if self.state != State::Commit {
+            return self.next_line().0;
+        }
+        let mut line = Line::new();
+        for col in &self.columns {
+            line.push('|');
+            if self.is_current(col) && self.num_parents() > 2 {
+                line.push_n(' ', (self.num_parents() - 2) * 2);
+            } else {
+                line.push(' ');
+            }
+        }
+        self.pad(&mut line);
+        self.prev_state = State::Padding;
+        line.text
+    }
+
+    /// Writes the graph lines up to and including the commit's own line,
+    /// leaving the cursor after it.
+    pub fn show_commit(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.is_commit_finished() {
+            return write!(out, "{}", self.padding());
+        }
+        while !self.is_commit_finished() {
+            let (line, shown_commit_line) = self.next_line();
+            write!(out, "{}", line)?;
+            if shown_commit_line {
+                break;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the next line of graph, with no newline.
+    pub fn show_oneline(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (line, _) = self.next_line();
+        write!(out, "{}", line)
+    }
+
+    pub fn show_padding(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let line = self.padding();
+        write!(out, "{}", line)
+    }
+
+    /// Writes whatever graph lines the commit has left.
+    fn show_remainder(&mut self, out: &mut impl Write) -> io::Result<()> {
+        while !self.is_commit_finished() {
+            self.show_oneline(out)?;
+            if !self.is_commit_finished() {
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the text of a commit next to its graph lines, the first line
+    /// of `text` after the graph already written, then the rest of its graph.
+    pub fn show_commit_msg(&mut self, out: &mut impl Write, text: &str) -> io::Result<()> {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            write!(out, "{}", &rest[..end])?;
+            rest = &rest[end..];
+            if !rest.is_empty() {
+                self.show_oneline(out)?;
+            }
+        }
+
+        if !self.is_commit_finished() {
+            let newline_terminated = text.ends_with('\n');
+            if !newline_terminated {
+                writeln!(out)?;
+            }
+            self.show_remainder(out)?;
+            if newline_terminated {
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
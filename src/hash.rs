@@ -7,10 +7,21 @@ pub const HASH_SIZE_BYTES: usize = 20;
 pub const HASH_HEX_LENGTH: usize = 40;
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GitHash([u8; HASH_SIZE_BYTES]);
 
 impl GitHash {
+    /// The all-zero id git uses for "no object".
+    pub fn zero() -> Self {
+        Self([0u8; HASH_SIZE_BYTES])
+    }
+
+    /// The first `len` hex digits of the id.
+    pub fn abbrev(&self, len: usize) -> String {
+        let mut hex = self.to_hex();
+        hex.truncate(len.min(HASH_HEX_LENGTH));
+        hex
+    }
 
     pub fn from_bytes(bytes: &[u8]) -> Self {
         let mut hasher = Sha1::new();
@@ -63,7 +74,7 @@ impl GitHash {
     }
 
     pub fn hash_version() -> &'static str {
-        return "sha1";
+        "sha1"
     }
 }
 
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, fs, io, path::{Path, PathBuf}};
+
+use crate::{config::git_config::{xdg_config_path, GitConfig}, constants::{GITIGNORE_FILE, GIT_INFO_EXCLUDE_PATH}, ignore::pattern::IgnorePattern, utils::path_utils::join_repo_path};
+
+/// The patterns read from one ignore file.
+#[derive(Debug)]
+pub struct PatternList {
+    /// Repo-relative directory the patterns are relative to ("" for the root).
+    pub base: String,
+    /// Where the patterns came from, as shown by `check-ignore -v`.
+    pub source: String,
+    pub patterns: Vec<IgnorePattern>,
+}
+
+impl PatternList {
+    pub fn from_file(path: &Path, base: &str, source: &str) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(Self::parse(&content, base, source))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(content: &str, base: &str, source: &str) -> Self {
+        let patterns = content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| IgnorePattern::parse(line, i + 1))
+            .collect();
+
+        Self {
+            base: base.to_string(),
+            source: source.to_string(),
+            patterns,
+        }
+    }
+
+    /// Returns the last pattern matching the path, which is the one that decides.
+    fn last_match(&self, path: &str, is_dir: bool) -> Option<&IgnorePattern> {
+        let relative = if self.base.is_empty() {
+            path
+        } else {
+            path.strip_prefix(&self.base)?.strip_prefix('/')?
+        };
+        self.patterns.iter().rev().find(|p| p.matches(relative, is_dir))
+    }
+}
+
+/// The rule that decided whether a path is ignored.
+#[derive(Debug, Clone)]
+pub struct IgnoreMatch {
+    pub source: String,
+    pub line: usize,
+    pub pattern: String,
+    pub negated: bool,
+}
+
+impl IgnoreMatch {
+    pub fn is_ignored(&self) -> bool {
+        !self.negated
+    }
+
+    fn from_pattern(list: &PatternList, pattern: &IgnorePattern) -> Self {
+        Self {
+            source: list.source.clone(),
+            line: pattern.line,
+            pattern: pattern.original.clone(),
+            negated: pattern.negated,
+        }
+    }
+}
+
+/// Applies git's ignore rules: per-directory `.gitignore` files (deeper files win),
+/// then `.git/info/exclude`, then `core.excludesFile`.
+pub struct IgnoreMatcher {
+    dir_lists: HashMap<String, Option<PatternList>>,
+    global_lists: Vec<PatternList>,
+}
+
+impl IgnoreMatcher {
+    pub fn load() -> io::Result<Self> {
+        let config = GitConfig::load()?;
+        Self::with_config(&config)
+    }
+
+    pub fn with_config(config: &GitConfig) -> io::Result<Self> {
+        let mut global_lists = Vec::new();
+
+        if let Some(list) = PatternList::from_file(Path::new(GIT_INFO_EXCLUDE_PATH), "", GIT_INFO_EXCLUDE_PATH)? {
+            global_lists.push(list);
+        }
+
+        let excludes_file: Option<PathBuf> = config.get_path("core.excludesFile").or_else(|| xdg_config_path("ignore"));
+        if let Some(path) = excludes_file {
+            if let Some(list) = PatternList::from_file(&path, "", &path.to_string_lossy())? {
+                global_lists.push(list);
+            }
+        }
+
+        Ok(Self {
+            dir_lists: HashMap::new(),
+            global_lists,
+        })
+    }
+
+    /// Finds the deciding rule for a path, looking only at the path itself.
+    /// Callers walking the tree top-down use this and prune ignored directories.
+    pub fn find_match(&mut self, path: &str, is_dir: bool) -> io::Result<Option<IgnoreMatch>> {
+        let dirs = parent_dirs(path);
+        for dir in &dirs {
+            self.load_dir(dir)?;
+        }
+
+        for dir in &dirs {
+            if let Some(Some(list)) = self.dir_lists.get(dir) {
+                if let Some(pattern) = list.last_match(path, is_dir) {
+                    return Ok(Some(IgnoreMatch::from_pattern(list, pattern)));
+                }
+            }
+        }
+
+        for list in &self.global_lists {
+            if let Some(pattern) = list.last_match(path, is_dir) {
+                return Ok(Some(IgnoreMatch::from_pattern(list, pattern)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn is_excluded(&mut self, path: &str, is_dir: bool) -> io::Result<bool> {
+        Ok(self.find_match(path, is_dir)?.is_some_and(|m| m.is_ignored()))
+    }
+
+    /// Like `find_match`, but a path inside an ignored directory is reported as
+    /// ignored by that directory's rule, since git never looks inside it.
+    pub fn check_path(&mut self, path: &str, is_dir: bool) -> io::Result<Option<IgnoreMatch>> {
+        let mut end = 0;
+        while let Some(offset) = path[end..].find('/') {
+            if let Some(m) = self.find_match(&path[..end + offset], true)? {
+                if m.is_ignored() {
+                    return Ok(Some(m));
+                }
+            }
+            end += offset + 1;
+        }
+        self.find_match(path, is_dir)
+    }
+
+    fn load_dir(&mut self, dir: &str) -> io::Result<()> {
+        if self.dir_lists.contains_key(dir) {
+            return Ok(());
+        }
+
+        let file = join_repo_path(dir, GITIGNORE_FILE);
+        let list = PatternList::from_file(Path::new(&file), dir, &file)?;
+        self.dir_lists.insert(dir.to_string(), list);
+        Ok(())
+    }
+}
+
+/// The directories whose `.gitignore` apply to `path`, deepest first.
+fn parent_dirs(path: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    let mut current = path;
+    while let Some(pos) = current.rfind('/') {
+        current = &current[..pos];
+        dirs.push(current.to_string());
+    }
+    dirs.push(String::new());
+    dirs
+}
@@ -0,0 +1,79 @@
+use crate::utils::wildmatch::{wildmatch, WM_PATHNAME};
+
+/// One line of a `.gitignore`-style file.
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    /// The pattern as written in the file, used for `check-ignore -v`.
+    pub original: String,
+    /// 1-based line number in the source file.
+    pub line: usize,
+    pub negated: bool,
+    /// Trailing `/`: only matches directories.
+    pub dir_only: bool,
+    /// The pattern contained a `/` (other than a trailing one), so it is matched
+    /// against the full path relative to its file instead of just the basename.
+    pub anchored: bool,
+    glob: String,
+}
+
+impl IgnorePattern {
+    /// Parses a single line, returning `None` for blank lines and comments.
+    pub fn parse(line: &str, line_no: usize) -> Option<Self> {
+        let original = trim_trailing_spaces(line.trim_end_matches('\r'));
+        if original.is_empty() || original.starts_with('#') {
+            return None;
+        }
+
+        let mut glob = original.as_str();
+        let negated = glob.starts_with('!');
+        // a leading `!` negates; `\!` and `\#` escape a literal first character
+        if negated || glob.starts_with("\\!") || glob.starts_with("\\#") {
+            glob = &glob[1..];
+        }
+
+        let dir_only = glob.ends_with('/');
+        let glob = glob.trim_end_matches('/');
+        if glob.is_empty() {
+            return None;
+        }
+
+        let anchored = glob.contains('/');
+        let glob = glob.strip_prefix('/').unwrap_or(glob).to_string();
+
+        Some(Self {
+            original: original.clone(),
+            line: line_no,
+            negated,
+            dir_only,
+            anchored,
+            glob,
+        })
+    }
+
+    /// Matches a path relative to the directory holding the pattern's file.
+    pub fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        if self.anchored {
+            wildmatch(&self.glob, relative_path, WM_PATHNAME)
+        } else {
+            let basename = relative_path.rsplit('/').next().unwrap_or(relative_path);
+            wildmatch(&self.glob, basename, WM_PATHNAME)
+        }
+    }
+}
+
+/// Drops unescaped trailing spaces, as git does for ignore files.
+fn trim_trailing_spaces(line: &str) -> String {
+    let mut end = line.len();
+    let bytes = line.as_bytes();
+    while end > 0 && bytes[end - 1] == b' ' {
+        if end >= 2 && bytes[end - 2] == b'\\' {
+            break;
+        }
+        end -= 1;
+    }
+    line[..end].to_string()
+}
@@ -1,15 +1,16 @@
-use std::{fs::File, io::{self, Read, Write}, path::Path};
+use std::{cmp::Ordering, collections::HashSet, fs::{self, File, OpenOptions}, io::{self, Read, Write}, path::{Path, PathBuf}};
 use log::debug;
 
-use crate::{hash::{GitHash, HASH_SIZE_BYTES}, index::index_entry::IndexEntry, objects::FileMode};
+use crate::{hash::{GitHash, HASH_SIZE_BYTES}, index::index_entry::IndexEntry, objects::{FileMode, ObjectType, Tree}};
 
-/// High-level descriptor of the index file format (version 1).
+/// High-level descriptor of an index file format version.
 /// Encapsulates constants and behavior for parsing header and entries.
 #[derive(Debug, Clone, Copy)]
 pub struct IndexFormatDescriptor {
     pub magic: &'static [u8],
     pub version: u32,
     pub mode_size: usize,
+    pub flags_size: usize,
     pub path_len_size: usize,
     pub hash_size: usize,
     pub max_path_len: usize,
@@ -29,7 +30,7 @@ impl IndexFormatDescriptor {
         reader.read_exact(&mut version_buf)?;
         let version = u32::from_be_bytes(version_buf);
 
-        if version != self.version {
+        if index_format_for_version(version).is_none() {
             return Err(io::Error::new(io::ErrorKind::InvalidData, "Unsupported version"));
         }
 
@@ -55,6 +56,14 @@ impl IndexFormatDescriptor {
         let mode = FileMode::from_u32(mode_val)
             .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid file mode: {mode_val:#o}")))?;
 
+        let flags = if self.flags_size > 0 {
+            let mut flags_buf = [0u8; 2];
+            reader.read_exact(&mut flags_buf)?;
+            u16::from_be_bytes(flags_buf)
+        } else {
+            0
+        };
+
         let mut len_buf = [0u8; 2];
         reader.read_exact(&mut len_buf)?;
         let path_len = u16::from_be_bytes(len_buf) as usize;
@@ -71,13 +80,28 @@ impl IndexFormatDescriptor {
         reader.read_exact(&mut hash_buf)?;
         let hash = GitHash::from_raw_bytes(&hash_buf);
     
-        Ok(IndexEntry { mode, path, hash })
+        Ok(IndexEntry {
+            mode,
+            path,
+            hash,
+            intent_to_add: flags & ENTRY_FLAG_INTENT_TO_ADD != 0,
+            stage: ((flags & ENTRY_STAGE_MASK) >> ENTRY_STAGE_SHIFT) as u8,
+        })
     }
 
     pub fn write_entry<W: Write>(&self, writer: &mut W, entry: &IndexEntry) -> io::Result<()> {
         let mode = entry.mode.clone() as u32;
         writer.write_all(&mode.to_be_bytes())?;
 
+        if self.flags_size > 0 {
+            let mut flags = 0u16;
+            if entry.intent_to_add {
+                flags |= ENTRY_FLAG_INTENT_TO_ADD;
+            }
+            flags |= ((entry.stage as u16) << ENTRY_STAGE_SHIFT) & ENTRY_STAGE_MASK;
+            writer.write_all(&flags.to_be_bytes())?;
+        }
+
         let path_bytes = entry.path.as_bytes();
         let path_len = path_bytes.len();
         if path_len > self.max_path_len {
@@ -103,40 +127,309 @@ pub struct IndexHeader {
     pub entry_count: u32,
 }
 
-/// Default descriptor for our index format v1
+/// Entry flag marking a path recorded with `add --intent-to-add`.
+pub const ENTRY_FLAG_INTENT_TO_ADD: u16 = 0x4000;
+
+/// Bits of the entry flags holding the merge stage (0-3).
+pub const ENTRY_STAGE_MASK: u16 = 0x3000;
+pub const ENTRY_STAGE_SHIFT: u16 = 12;
+
+/// Descriptor for our original index format v1 (no per-entry flags)
 pub const INDEX_FORMAT_V1: IndexFormatDescriptor = IndexFormatDescriptor {
     magic: b"IDX",
     version: 1,
     mode_size: 4,
+    flags_size: 0,
+    path_len_size: 2,
+    hash_size: HASH_SIZE_BYTES,
+    max_path_len: u16::MAX as usize,
+};
+
+/// Descriptor for index format v2, which adds a 16 bit flags field after the mode
+pub const INDEX_FORMAT_V2: IndexFormatDescriptor = IndexFormatDescriptor {
+    magic: b"IDX",
+    version: 2,
+    mode_size: 4,
+    flags_size: 2,
     path_len_size: 2,
     hash_size: HASH_SIZE_BYTES,
     max_path_len: u16::MAX as usize,
 };
 
-pub fn read_index(path: &Path) -> io::Result<Vec<IndexEntry>> {
+/// The format new index files are written in.
+pub const INDEX_FORMAT_CURRENT: IndexFormatDescriptor = INDEX_FORMAT_V2;
+
+pub fn index_format_for_version(version: u32) -> Option<IndexFormatDescriptor> {
+    match version {
+        1 => Some(INDEX_FORMAT_V1),
+        2 => Some(INDEX_FORMAT_V2),
+        _ => None,
+    }
+}
+
+pub fn read_index(path: &Path) -> io::Result<Index> {
     let mut file = File::open(path)?;
 
     // Use the descriptor to read the header
-    let header = INDEX_FORMAT_V1.read_header(&mut file)?;
+    let header = INDEX_FORMAT_CURRENT.read_header(&mut file)?;
     debug!("header.version: {} header.entry_count: {}", header.version, header.entry_count);
+    let format = index_format_for_version(header.version)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Unsupported version"))?;
     let mut entries = Vec::with_capacity(header.entry_count as usize);
     for _ in 0..header.entry_count {
-        let entry = INDEX_FORMAT_V1.read_entry(&mut file)?;
+        let entry = format.read_entry(&mut file)?;
         entries.push(entry);
     }
 
-    Ok(entries)
+    // version 1 files were written in walk order, so only later versions
+    // can be trusted to be sorted
+    match header.version {
+        1 => Ok(Index::from_entries(entries)),
+        _ => Index::from_sorted(entries),
+    }
 }
 
-pub fn write_index(path: &Path, entries: &[IndexEntry]) -> io::Result<()> {
-    let mut file = File::create(path)?;
+/// Reads the index if one exists, treating a missing or empty index file as no entries.
+pub fn load_index(path: &Path) -> io::Result<Index> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() == 0 => Ok(Index::default()),
+        Ok(_) => read_index(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Index::default()),
+        Err(e) => Err(e),
+    }
+}
 
-    // Use the descriptor to write the header
-    INDEX_FORMAT_V1.write_header(&mut file, entries.len() as u32)?;
+/// Writes the index to `<path>.lock` first and renames it into place, so readers
+/// never observe a partially written index.
+pub fn write_index(path: &Path, index: &Index) -> io::Result<()> {
+    IndexLock::acquire(path)?.commit(index)
+}
+
+/// An exclusive hold on the index through `<path>.lock`. Commands that update
+/// the index take it before reading, so a concurrent writer's update can't be
+/// lost in between. Dropping it without committing releases the lock.
+pub struct IndexLock {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+    committed: bool,
+}
 
-    for entry in entries {
-        INDEX_FORMAT_V1.write_entry(&mut file, entry)?;
+impl IndexLock {
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let lock_path = path.with_extension("lock");
+        // create_new, so a second writer fails instead of sharing the lock
+        let file = match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!(
+                    "Unable to create '{}': index.lock exists; another process may be writing the index, or one crashed and the file must be removed",
+                    lock_path.display()
+                )));
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(IndexLock { path: path.to_path_buf(), lock_path, file: Some(file), committed: false })
     }
 
+    /// Writes `index` to the lock file and renames it over the index.
+    pub fn commit(mut self, index: &Index) -> io::Result<()> {
+        let mut file = self.file.take().expect("the lock is only committed once");
+        write_entries(&mut file, index).and_then(|_| file.sync_all())?;
+        drop(file);
+        fs::rename(&self.lock_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        // a committed lock has been renamed away, and the name may be another writer's by now
+        if !self.committed {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+fn write_entries(file: &mut File, index: &Index) -> io::Result<()> {
+    // Use the descriptor to write the header
+    INDEX_FORMAT_CURRENT.write_header(file, index.len() as u32)?;
+
+    for entry in index.iter() {
+        INDEX_FORMAT_CURRENT.write_entry(file, entry)?;
+    }
     Ok(())
 }
+
+/// The in-memory index: entries kept sorted by path bytes and then stage, so
+/// lookups, inserts and removals are binary searches.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Builds an index from entries in any order; of two entries with the same
+    /// path and stage, the later one wins.
+    pub fn from_entries(mut entries: Vec<IndexEntry>) -> Self {
+        // stable, so duplicates keep their relative order
+        entries.sort_by(IndexEntry::cmp_order);
+
+        let mut sorted: Vec<IndexEntry> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match sorted.last_mut() {
+                Some(last) if last.cmp_order(&entry) == Ordering::Equal => *last = entry,
+                _ => sorted.push(entry),
+            }
+        }
+        Self { entries: sorted }
+    }
+
+    /// Wraps entries that must already be in index order, as read from disk.
+    pub fn from_sorted(entries: Vec<IndexEntry>) -> io::Result<Self> {
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].cmp_order(&pair[1]) != Ordering::Less) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("index entries are out of order or duplicated at '{}'", pair[1].path)));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, IndexEntry> {
+        self.entries.iter()
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<IndexEntry> {
+        self.entries
+    }
+
+    fn search(&self, path: &str, stage: u8) -> Result<usize, usize> {
+        self.entries.binary_search_by(|e| e.path.as_bytes().cmp(path.as_bytes()).then(e.stage.cmp(&stage)))
+    }
+
+    /// Index of the first entry for `path` (or where it would go), whatever its stage.
+    fn path_start(&self, path: &str) -> usize {
+        self.entries.partition_point(|e| e.path.as_bytes() < path.as_bytes())
+    }
+
+    pub fn get(&self, path: &str, stage: u8) -> Option<&IndexEntry> {
+        self.search(path, stage).ok().map(|i| &self.entries[i])
+    }
+
+    /// All entries for `path`: a single stage 0 entry, or its conflict stages.
+    pub fn stages(&self, path: &str) -> &[IndexEntry] {
+        let start = self.path_start(path);
+        let len = self.entries[start..].partition_point(|e| e.path == path);
+        &self.entries[start..start + len]
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        !self.stages(path).is_empty()
+    }
+
+    /// Entries below the directory `dir`, which are contiguous in index order.
+    pub fn entries_under(&self, dir: &str) -> &[IndexEntry] {
+        if dir.is_empty() {
+            return &self.entries;
+        }
+        let prefix = format!("{}/", dir);
+        let start = self.path_start(&prefix);
+        let len = self.entries[start..].partition_point(|e| e.path.starts_with(&prefix));
+        &self.entries[start..start + len]
+    }
+
+    pub fn has_unmerged(&self) -> bool {
+        self.entries.iter().any(IndexEntry::is_unmerged)
+    }
+
+    /// Inserts or replaces `entry`. As in git, a stage 0 entry replaces every
+    /// stage of its path, while a conflict stage only replaces stage 0 and itself.
+    pub fn add(&mut self, entry: IndexEntry) {
+        if entry.is_unmerged() {
+            if let Ok(i) = self.search(&entry.path, 0) {
+                self.entries.remove(i);
+            }
+        } else {
+            let start = self.path_start(&entry.path);
+            let len = self.stages(&entry.path).len();
+            self.entries.drain(start..start + len);
+        }
+
+        match self.search(&entry.path, entry.stage) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+
+    /// Adds many entries the way repeated [`Index::add`] calls would, but with a
+    /// single merge instead of one shifting insert per entry.
+    pub fn add_all(&mut self, entries: Vec<IndexEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+        let mut merged = HashSet::new();
+        let mut conflicted = HashSet::new();
+        let mut conflict_stages = HashSet::new();
+        for entry in &entries {
+            if entry.is_unmerged() {
+                conflicted.insert(entry.path.clone());
+                conflict_stages.insert((entry.path.clone(), entry.stage));
+            } else {
+                merged.insert(entry.path.clone());
+            }
+        }
+
+        let mut kept = std::mem::take(&mut self.entries);
+        kept.retain(|e| {
+            let replaced = merged.contains(&e.path) || (conflicted.contains(&e.path) && (e.stage == 0 || conflict_stages.contains(&(e.path.clone(), e.stage))));
+            !replaced
+        });
+        kept.extend(entries);
+        *self = Index::from_entries(kept);
+    }
+
+    /// Removes every stage of `path`, returning whether anything was removed.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let start = self.path_start(path);
+        let len = self.stages(path).len();
+        self.entries.drain(start..start + len);
+        len > 0
+    }
+
+    pub fn retain<F: FnMut(&IndexEntry) -> bool>(&mut self, f: F) {
+        self.entries.retain(f);
+    }
+}
+
+impl<'a> IntoIterator for &'a Index {
+    type Item = &'a IndexEntry;
+    type IntoIter = std::slice::Iter<'a, IndexEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+/// Flattens a tree into index entries for every blob below it, with paths under `prefix`.
+pub fn entries_from_tree(tree_hash: &GitHash, prefix: &str) -> io::Result<Index> {
+    let tree = Tree::load_tree_from_hash(&tree_hash.to_hex())?;
+    let mut entries = Vec::new();
+    tree.walk_tree(prefix, &mut |entry, path| {
+        if entry.object_type != ObjectType::Tree {
+            entries.push(IndexEntry::new(entry.mode.clone(), path.to_string(), entry.hash.clone()));
+        }
+    }, true)?;
+
+    Ok(Index::from_entries(entries))
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use crate::{hash::GitHash, objects::FileMode};
 
 #[derive(Debug, Clone)]
@@ -5,4 +7,33 @@ pub struct IndexEntry {
     pub mode: FileMode,
     pub path: String,
     pub hash: GitHash,
-}
\ No newline at end of file
+    /// Set for paths recorded with `add --intent-to-add`; such entries are not written into trees.
+    pub intent_to_add: bool,
+    /// Merge stage: 0 for a normal entry, 1 (base), 2 (ours) or 3 (theirs) for a conflicted path.
+    pub stage: u8,
+}
+
+impl IndexEntry {
+    pub fn new(mode: FileMode, path: String, hash: GitHash) -> Self {
+        Self {
+            mode,
+            path,
+            hash,
+            intent_to_add: false,
+            stage: 0,
+        }
+    }
+
+    pub fn with_stage(self, stage: u8) -> Self {
+        Self { stage, ..self }
+    }
+
+    pub fn is_unmerged(&self) -> bool {
+        self.stage != 0
+    }
+
+    /// Index order: by path bytes, then by stage.
+    pub fn cmp_order(&self, other: &Self) -> Ordering {
+        self.path.as_bytes().cmp(other.path.as_bytes()).then(self.stage.cmp(&other.stage))
+    }
+}
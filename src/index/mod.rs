@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod index;
 pub mod index_entry;
\ No newline at end of file
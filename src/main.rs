@@ -4,10 +4,18 @@ use std::env;
 use std::io;
 
 pub mod clone;
+pub mod config;
+pub mod diff;
 pub mod constants;
+pub mod ignore;
 pub mod index;
 pub mod hash;
+pub mod pathspec;
+pub mod refs;
+pub mod graph;
+pub mod revwalk;
 pub mod utils;
+pub mod worktree;
 fn main() -> io::Result<()> {
     env_logger::init();
     eprintln!("Logs from your program will appear here!");
@@ -1,9 +1,11 @@
-use crate::{hash::GitHash, objects::{write_object, Person}};
+use std::io;
+
+use crate::{hash::GitHash, objects::{ident::parse_person, read_object, write_object, ObjectType, Person}};
 
 
 pub struct Commit {
     pub tree: GitHash,
-    pub parent_tree: Option<GitHash>,
+    pub parents: Vec<GitHash>,
     pub message: String,
     pub committer: Person,
     pub author: Person, 
@@ -13,7 +15,7 @@ impl Commit {
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut lines = vec![format!("tree {}", self.tree.to_hex())];
         
-        if let Some(ref parent) = self.parent_tree {
+        for parent in &self.parents {
             lines.push(format!("parent {}", parent.to_hex()));
         }
 
@@ -28,5 +30,95 @@ impl Commit {
 
 pub fn process_commit(commit: &Commit) -> std::io::Result<GitHash> {
     let content = commit.to_bytes();
-    return write_object(super::ObjectType::Commit, &content);
-}
\ No newline at end of file
+    write_object(super::ObjectType::Commit, &content)
+}
+
+/// Extracts the `tree` header of a raw commit object.
+pub fn parse_commit_tree(content: &[u8]) -> io::Result<GitHash> {
+    let text = String::from_utf8_lossy(content);
+    let tree_line = text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("tree "))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed commit: missing tree header"))?;
+    GitHash::from_hex(tree_line.trim())
+}
+
+/// A commit object as read back from the object store.
+#[derive(Debug, Clone)]
+pub struct ParsedCommit {
+    pub tree: GitHash,
+    pub parents: Vec<GitHash>,
+    pub author: Person,
+    pub committer: Person,
+    /// Everything after the headers, as stored.
+    pub message: String,
+}
+
+impl ParsedCommit {
+    /// The message's first paragraph on one line, as git's `%s` shows it.
+    pub fn subject(&self) -> String {
+        let lines = self.message.lines().skip_while(|l| l.trim().is_empty());
+        lines.take_while(|l| !l.trim().is_empty()).map(str::trim).collect::<Vec<_>>().join(" ")
+    }
+}
+
+pub fn parse_commit(content: &[u8]) -> io::Result<ParsedCommit> {
+    let text = String::from_utf8_lossy(content);
+    let (headers, message) = text.split_once("\n\n").unwrap_or((&text[..], ""));
+
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut committer = None;
+
+    // continuation lines of multi-line headers (e.g. gpgsig) start with a space
+    for line in headers.lines().filter(|l| !l.starts_with(' ')) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "tree" => tree = Some(GitHash::from_hex(value.trim())?),
+            "parent" => parents.push(GitHash::from_hex(value.trim())?),
+            "author" => author = parse_person(value),
+            "committer" => committer = parse_person(value),
+            _ => {}
+        }
+    }
+
+    Ok(ParsedCommit {
+        tree: tree.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed commit: missing tree header"))?,
+        parents,
+        author: author.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed commit: missing author header"))?,
+        committer: committer.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed commit: missing committer header"))?,
+        message: message.to_string(),
+    })
+}
+
+pub fn read_commit(hash: &GitHash) -> io::Result<ParsedCommit> {
+    let (object_type, content) = read_object(&hash.to_hex())?;
+    if object_type != ObjectType::Commit {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("object {} is a {}, not a commit", hash.to_hex(), object_type)));
+    }
+    parse_commit(&content)
+}
+
+/// The object an annotated tag points at, from its `object` header.
+pub fn tag_target(content: &[u8]) -> io::Result<GitHash> {
+    let text = String::from_utf8_lossy(content);
+    let target = text.lines().next().and_then(|line| line.strip_prefix("object "));
+    GitHash::from_hex(target.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed tag: missing object header"))?.trim())
+}
+
+/// Follows annotated tags to the commit they point at.
+pub fn peel_to_commit(hash: &GitHash) -> io::Result<GitHash> {
+    let mut hash = hash.clone();
+    // bounded so a corrupt tag chain can't loop forever
+    for _ in 0..10 {
+        let (object_type, content) = read_object(&hash.to_hex())?;
+        match object_type {
+            ObjectType::Commit => return Ok(hash),
+            ObjectType::Tag => hash = tag_target(&content)?,
+            other => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("object {} is a {}, not a commit", hash.to_hex(), other))),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, format!("tag chain too deep at {}", hash.to_hex())))
+}
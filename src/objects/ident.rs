@@ -0,0 +1,323 @@
+//! Author and committer identities: where they come from (environment and
+//! config), the `Name <email> <timestamp> <tz>` form commits store, and the
+//! date formats git accepts and prints.
+
+use std::{env, io};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone};
+
+use crate::{config::git_config::GitConfig, objects::Person};
+
+/// Which side of a commit an identity is for; each has its own environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    fn env_prefix(self) -> &'static str {
+        match self {
+            Role::Author => "GIT_AUTHOR",
+            Role::Committer => "GIT_COMMITTER",
+        }
+    }
+
+    fn config_section(self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+}
+
+/// The identity for `role`, dated now unless `GIT_<ROLE>_DATE` says otherwise.
+/// Names and emails come from `GIT_<ROLE>_NAME`/`_EMAIL`, then
+/// `<role>.name`/`.email`, then `user.name`/`.email`, as in git.
+pub fn ident(config: &GitConfig, role: Role) -> io::Result<Person> {
+    let prefix = role.env_prefix();
+    let lookup = |field: &str| {
+        env::var(format!("{}_{}", prefix, field.to_ascii_uppercase()))
+            .ok()
+            .or_else(|| config.get(&format!("{}.{}", role.config_section(), field)).map(str::to_string))
+            .or_else(|| config.get(&format!("user.{}", field)).map(str::to_string))
+            .filter(|v| !v.trim().is_empty())
+    };
+
+    let (Some(name), Some(email)) = (lookup("name"), lookup("email")) else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Author identity unknown\n\n*** Please tell me who you are.\n\nRun\n\n  git config --global user.email \"you@example.com\"\n  git config --global user.name \"Your Name\"\n\nto set your account's default identity.",
+        ));
+    };
+
+    let (timestamp, timezone) = ident_date(role)?;
+    Ok(Person { name: name.trim().to_string(), email: email.trim().to_string(), timestamp, timezone })
+}
+
+/// The date for `role`: `GIT_<ROLE>_DATE` if set, else now.
+pub fn ident_date(role: Role) -> io::Result<(i64, String)> {
+    match env::var(format!("{}_DATE", role.env_prefix())) {
+        Ok(date) => parse_date(&date),
+        Err(_) => Ok(now()),
+    }
+}
+
+/// Whether `GIT_<ROLE>_DATE` overrides the date.
+pub fn date_overridden(role: Role) -> bool {
+    env::var_os(format!("{}_DATE", role.env_prefix())).is_some()
+}
+
+/// The current time and the local UTC offset.
+pub fn now() -> (i64, String) {
+    let now = Local::now();
+    (now.timestamp(), format_offset(now.offset().fix().local_minus_utc()))
+}
+
+/// Parses `Name <email>` as given to `--author`.
+pub fn parse_name_email(value: &str) -> Option<(String, String)> {
+    let (name, rest) = value.split_once('<')?;
+    let (email, tail) = rest.split_once('>')?;
+    if !tail.trim().is_empty() {
+        return None;
+    }
+    Some((name.trim().to_string(), email.trim().to_string()))
+}
+
+/// Parses the `Name <email> <timestamp> <tz>` of a commit's author or
+/// committer header.
+pub fn parse_person(value: &str) -> Option<Person> {
+    let (name, rest) = value.split_once('<')?;
+    let (email, date) = rest.rsplit_once('>')?;
+    let mut date = date.split_whitespace();
+    let timestamp = date.next()?.parse().ok()?;
+    let timezone = date.next().unwrap_or("+0000").to_string();
+    Some(Person { name: name.trim().to_string(), email: email.to_string(), timestamp, timezone })
+}
+
+/// Parses a date as `--date` and `GIT_AUTHOR_DATE` take it: git's internal
+/// `<unix-timestamp> <tz>` (optionally with a leading `@`), RFC 2822, ISO
+/// 8601 and git's default format. A date without a zone is local time.
+pub fn parse_date(value: &str) -> io::Result<(i64, String)> {
+    let value = value.trim();
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid date format: {}", value));
+
+    // like git, a number of nine or more digits is a timestamp even without `@`
+    let epoch = value.strip_prefix('@');
+    let mut parts = epoch.unwrap_or(value).split_whitespace();
+    if let Some(first) = parts.next().filter(|w| w.bytes().all(|c| c.is_ascii_digit()) && (epoch.is_some() || w.len() >= 9)) {
+        let timestamp = first.parse().map_err(|_| invalid())?;
+        let timezone = match parts.next() {
+            Some(tz) => format_offset(parse_offset(tz).ok_or_else(invalid)?),
+            None => "+0000".to_string(),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        return Ok((timestamp, timezone));
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Ok((date.timestamp(), format_offset(date.offset().local_minus_utc())));
+    }
+
+    // the zone, if any, is the last word, or glued to an ISO time
+    let (local, offset) = split_zone(value);
+    let naive = parse_naive(local).ok_or_else(invalid)?;
+    let offset = match offset {
+        Some(offset) => offset,
+        None => Local.from_local_datetime(&naive).earliest().ok_or_else(invalid)?.offset().fix().local_minus_utc(),
+    };
+    let zone = FixedOffset::east_opt(offset).ok_or_else(invalid)?;
+    let date = zone.from_local_datetime(&naive).single().ok_or_else(invalid)?;
+    Ok((date.timestamp(), format_offset(offset)))
+}
+
+fn split_zone(value: &str) -> (&str, Option<i32>) {
+    if let Some(local) = value.strip_suffix('Z') {
+        return (local, Some(0));
+    }
+    if let Some((local, zone)) = value.rsplit_once(' ') {
+        if let Some(offset) = parse_offset(zone) {
+            return (local.trim_end(), Some(offset));
+        }
+    }
+    // `2005-04-07T22:13:13+02:00`
+    if let Some(pos) = value.rfind(['+', '-']).filter(|&pos| value[..pos].contains(':')) {
+        if let Some(offset) = parse_offset(&value[pos..]) {
+            return (&value[..pos], Some(offset));
+        }
+    }
+    (value, None)
+}
+
+fn parse_naive(value: &str) -> Option<NaiveDateTime> {
+    const FORMATS: [&str; 6] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M", "%a %b %e %H:%M:%S %Y", "%Y.%m.%d %H:%M:%S"];
+    let value = value.trim();
+    // fractional seconds are dropped, as git does
+    let value = match value.rsplit_once('.') {
+        Some((whole, fraction)) if whole.contains(':') && fraction.bytes().all(|c| c.is_ascii_digit()) => whole,
+        _ => value,
+    };
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
+}
+
+/// Parses `+hhmm`, `-hhmm` or `+hh:mm` into seconds east of UTC.
+fn parse_offset(zone: &str) -> Option<i32> {
+    let sign = match zone.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: String = zone[1..].chars().filter(|&c| c != ':').collect();
+    if digits.len() != 4 || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Formats a timestamp in its own zone the way git shows dates by
+/// default, like `Thu Apr 7 22:13:13 2005 +0200`.
+pub fn format_date(timestamp: i64, timezone: &str) -> String {
+    let zone = parse_offset(timezone).and_then(FixedOffset::east_opt).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    match zone.timestamp_opt(timestamp, 0).single() {
+        Some(date) => format!("{} {}", date.format("%a %b %-d %H:%M:%S %Y"), timezone),
+        None => format!("{} {}", timestamp, timezone),
+    }
+}
+
+/// How `--date` shows dates.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DateMode {
+    #[default]
+    Default,
+    /// `Thu Apr 7 22:13:13 2005`, in the local zone.
+    Local,
+    Iso,
+    IsoStrict,
+    Rfc,
+    Short,
+    Raw,
+    Unix,
+    Relative,
+    /// `format:<strftime>`.
+    Format(String),
+}
+
+impl DateMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mode = match value {
+            "default" => DateMode::Default,
+            "local" | "default-local" => DateMode::Local,
+            "iso" | "iso8601" => DateMode::Iso,
+            "iso-strict" | "iso8601-strict" => DateMode::IsoStrict,
+            "rfc" | "rfc2822" => DateMode::Rfc,
+            "short" => DateMode::Short,
+            "raw" => DateMode::Raw,
+            "unix" => DateMode::Unix,
+            "relative" => DateMode::Relative,
+            _ => DateMode::Format(value.strip_prefix("format:")?.to_string()),
+        };
+        Some(mode)
+    }
+}
+
+/// Formats a timestamp and its zone in the given mode.
+pub fn format_date_as(timestamp: i64, timezone: &str, mode: &DateMode) -> String {
+    let zone = parse_offset(timezone).and_then(FixedOffset::east_opt).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    let Some(date) = zone.timestamp_opt(timestamp, 0).single() else {
+        return format!("{} {}", timestamp, timezone);
+    };
+    match mode {
+        DateMode::Default => format_date(timestamp, timezone),
+        DateMode::Local => date.with_timezone(&Local).format("%a %b %-d %H:%M:%S %Y").to_string(),
+        DateMode::Iso => format!("{} {}", date.format("%Y-%m-%d %H:%M:%S"), timezone),
+        DateMode::IsoStrict => date.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        DateMode::Rfc => format!("{} {}", date.format("%a, %-d %b %Y %H:%M:%S"), timezone),
+        DateMode::Short => date.format("%Y-%m-%d").to_string(),
+        DateMode::Raw => format!("{} {}", timestamp, timezone),
+        DateMode::Unix => timestamp.to_string(),
+        DateMode::Relative => format_relative(timestamp, Local::now().timestamp()),
+        DateMode::Format(format) => date.format(format).to_string(),
+    }
+}
+
+/// How long before `now` a timestamp is, in git's words: "3 days ago",
+/// "2 years, 1 month ago".
+pub fn format_relative(timestamp: i64, now: i64) -> String {
+    let plural = |n: i64, unit: &str| format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" });
+    let diff = now - timestamp;
+    if diff < 0 {
+        return "in the future".to_string();
+    }
+    if diff < 90 {
+        return format!("{} ago", plural(diff, "second"));
+    }
+    let minutes = (diff + 30) / 60;
+    if minutes < 90 {
+        return format!("{} ago", plural(minutes, "minute"));
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return format!("{} ago", plural(hours, "hour"));
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return format!("{} ago", plural(days, "day"));
+    }
+    if days < 70 {
+        return format!("{} ago", plural((days + 3) / 7, "week"));
+    }
+    if days < 365 {
+        return format!("{} ago", plural((days + 15) / 30, "month"));
+    }
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        if months > 0 {
+            return format!("{}, {} ago", plural(years, "year"), plural(months, "month"));
+        }
+        return format!("{} ago", plural(years, "year"));
+    }
+    format!("{} ago", plural((days + 183) / 365, "year"))
+}
+
+/// Parses the dates `--since` and `--until` take: anything [`parse_date`]
+/// accepts, `now`, `today`, `yesterday`, or `<n> <unit> ago`.
+pub fn parse_approxidate(value: &str) -> io::Result<i64> {
+    let now = Local::now().timestamp();
+    let words: Vec<String> = value.split(|c: char| c.is_whitespace() || c == '.').filter(|w| !w.is_empty()).map(str::to_ascii_lowercase).collect();
+    match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["now"] => return Ok(now),
+        ["today"] => return Ok(Local::now().date_naive().and_hms_opt(0, 0, 0).and_then(|d| Local.from_local_datetime(&d).earliest()).map_or(now, |d| d.timestamp())),
+        ["yesterday"] => return Ok(now - 86400),
+        [count, unit, "ago"] | [count, unit] if count.parse::<i64>().is_ok() => {
+            let seconds = match unit.trim_end_matches('s') {
+                "second" | "sec" => 1,
+                "minute" | "min" => 60,
+                "hour" => 3600,
+                "day" => 86400,
+                "week" => 7 * 86400,
+                "month" => 30 * 86400,
+                "year" => 365 * 86400,
+                _ => 0,
+            };
+            if seconds > 0 {
+                return Ok(now - count.parse::<i64>().unwrap_or(0) * seconds);
+            }
+        }
+        _ => {}
+    }
+    parse_date(value).map(|(timestamp, _)| timestamp)
+}
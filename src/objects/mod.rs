@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod objects;
 pub use objects::*;
 
@@ -5,4 +6,5 @@ pub mod tree;
 pub use tree::*;
 
 pub mod commit_object;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod ident;
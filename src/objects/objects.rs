@@ -8,14 +8,15 @@ use flate2::Compression;
 
 use crate::constants::{GIT_OBJECTS_DIR};
 use crate::hash::GitHash;
-use crate::utils::file_utils::read_file;
+use crate::utils::file_utils::{generate_temp_filename, read_worktree_blob};
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectType {
     Blob,
     Tree,
     Commit,
+    Tag,
     Unknown(u8),
 }
 
@@ -27,6 +28,7 @@ impl ObjectType {
             ObjectType::Blob => "blob",
             ObjectType::Tree => "tree",
             ObjectType::Commit => "commit",
+            ObjectType::Tag => "tag",
             ObjectType::Unknown(_) => "unknown",
         }
     }
@@ -36,6 +38,7 @@ impl ObjectType {
             "blob" => ObjectType::Blob,
             "tree" => ObjectType::Tree,
             "commit" => ObjectType::Commit,
+            "tag" => ObjectType::Tag,
             _ => ObjectType::Unknown(ObjectType::UNKNOWN_FROM_STR_SENTINEL),
         }
     }
@@ -52,6 +55,7 @@ impl ObjectType {
             1 => ObjectType::Commit,
             2 => ObjectType::Tree,
             3 => ObjectType::Blob,
+            4 => ObjectType::Tag,
             other => ObjectType::Unknown(other),
         }
     }
@@ -63,7 +67,7 @@ impl Display for ObjectType {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Person {
     pub name: String,
     pub email: String,
@@ -155,15 +159,62 @@ impl Display for FileMode {
     }
 }
 
-pub fn read_object(hash_str :&str) -> io::Result<(ObjectType, Vec<u8>)> {
-    let hash = GitHash::from_hex(hash_str)?;
+/// Where the loose object for `hash` lives.
+pub fn object_path(hash: &GitHash) -> PathBuf {
     let (dir, file) = hash.to_path_parts();
+    let mut path = PathBuf::from(GIT_OBJECTS_DIR);
+    path.push(dir);
+    path.push(file);
+    path
+}
 
-    let mut object_path = PathBuf::from(GIT_OBJECTS_DIR);
-    object_path.push(dir);
-    object_path.push(file);
+pub fn object_exists(hash: &GitHash) -> bool {
+    object_path(hash).exists()
+}
+
+/// The ids of all loose objects starting with the hex `prefix` (at least two digits).
+pub fn find_objects_by_prefix(prefix: &str) -> io::Result<Vec<GitHash>> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.len() < 2 || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Ok(Vec::new());
+    }
 
-    let file = File::open(&object_path)?;
+    let dir = PathBuf::from(GIT_OBJECTS_DIR).join(&prefix[..2]);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut found = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        let hex = format!("{}{}", &prefix[..2], name);
+        if hex.starts_with(&prefix) {
+            if let Ok(hash) = GitHash::from_hex(&hex) {
+                found.push(hash);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// The shortest prefix of `hash`, at least `min` digits long, that no other
+/// object shares.
+pub fn unique_abbrev(hash: &GitHash, min: usize) -> io::Result<String> {
+    let hex = hash.to_hex();
+    let others = find_objects_by_prefix(&hex[..min.clamp(2, hex.len())])?;
+    let len = others
+        .iter()
+        .filter(|other| *other != hash)
+        .map(|other| other.to_hex().bytes().zip(hex.bytes()).take_while(|(a, b)| a == b).count() + 1)
+        .fold(min, usize::max);
+    Ok(hex[..len.min(hex.len())].to_string())
+}
+
+pub fn read_object(hash_str :&str) -> io::Result<(ObjectType, Vec<u8>)> {
+    let hash = GitHash::from_hex(hash_str)?;
+    let file = File::open(object_path(&hash))?;
     let mut decoder = ZlibDecoder::new(file);
     let mut decompressed = Vec::new();
     _= decoder.read_to_end(&mut decompressed);
@@ -195,7 +246,17 @@ pub fn write_object(object_type: ObjectType, data: &[u8]) -> io::Result<GitHash>
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
         encoder.write_all(&encoded)?;
         let compressed = encoder.finish()?;
-        fs::write(path, compressed)?;
+
+        // write under a temporary name first so concurrent writers and readers
+        // never see a partially written object
+        let temp_path = path.with_file_name(generate_temp_filename(Some("tmp_obj_")));
+        fs::write(&temp_path, compressed)?;
+        if let Err(e) = fs::rename(&temp_path, &path) {
+            let _ = fs::remove_file(&temp_path);
+            if !path.exists() {
+                return Err(e);
+            }
+        }
     }
 
     Ok(hash)
@@ -204,7 +265,7 @@ pub fn write_object(object_type: ObjectType, data: &[u8]) -> io::Result<GitHash>
     write the object to the disk from path and return the calculated hash value of the object
 */
 pub fn write_object_from_path(object_type: ObjectType, file_path: &Path) -> io::Result<GitHash> {
-    let contents = read_file(file_path)?;
+    let contents = read_worktree_blob(file_path)?;
     write_object(object_type, &contents)
 }
 
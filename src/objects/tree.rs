@@ -1,6 +1,6 @@
-use std::io;
+use std::{cmp::Ordering, io};
 
-use crate::{hash::{GitHash, HASH_SIZE_BYTES}, objects::{read_object, FileMode, ObjectType}};
+use crate::{hash::{GitHash, HASH_SIZE_BYTES}, objects::{commit_object::{parse_commit_tree, tag_target}, read_object, FileMode, ObjectType}};
 
 pub struct TreeEntry {
     pub mode: FileMode,
@@ -15,6 +15,16 @@ pub struct Tree {
 
 impl Tree {
     pub fn walk_tree<F: FnMut(&TreeEntry, &str)>(&self, parent_path: &str,callback: &mut F, recursive: bool) -> io::Result<()> {
+        self.walk_tree_with(parent_path, callback, &mut |_, _| recursive)
+    }
+
+    /// Like `walk_tree`, but asks `should_descend` for every subtree instead of
+    /// recursing all or nothing.
+    pub fn walk_tree_with<F, D>(&self, parent_path: &str, callback: &mut F, should_descend: &mut D) -> io::Result<()>
+    where
+        F: FnMut(&TreeEntry, &str),
+        D: FnMut(&TreeEntry, &str) -> bool,
+    {
         for entry in &self.entries {
             let full_path = if parent_path.is_empty() {
                 entry.name.clone()
@@ -24,9 +34,9 @@ impl Tree {
 
             callback(entry, &full_path);
 
-            if recursive && entry.object_type == ObjectType::Tree {
+            if entry.object_type == ObjectType::Tree && should_descend(entry, &full_path) {
                 let sub_tree = Tree::load_tree_from_hash(&entry.hash.to_hex())?;
-                sub_tree.walk_tree(&full_path, callback, recursive)?;
+                sub_tree.walk_tree_with(&full_path, callback, should_descend)?;
             }
         }
 
@@ -75,3 +85,36 @@ impl Tree {
     Ok(Tree { entries })
     }
 }
+
+/// Resolves a tree-ish object id to a tree id, peeling tags and commits.
+pub fn peel_to_tree(hash: &str) -> io::Result<GitHash> {
+    let (object_type, content) = read_object(hash)?;
+    match object_type {
+        ObjectType::Tree => GitHash::from_hex(hash),
+        ObjectType::Commit => parse_commit_tree(&content),
+        ObjectType::Tag => peel_to_tree(&tag_target(&content)?.to_hex()),
+        other => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Object {} is a {}, not a tree-ish", hash, other))),
+    }
+}
+
+/// Git's tree entry order: names compare bytewise, except that a directory
+/// compares as if its name ended in '/', so `foo.txt` sorts before the tree `foo`.
+pub fn tree_entry_cmp(a: &str, a_is_dir: bool, b: &str, b_is_dir: bool) -> Ordering {
+    let terminator = |is_dir| if is_dir { Some(b'/') } else { None };
+    let a_bytes = a.bytes().map(Some).chain(std::iter::once(terminator(a_is_dir)));
+    let b_bytes = b.bytes().map(Some).chain(std::iter::once(terminator(b_is_dir)));
+    a_bytes.cmp(b_bytes)
+}
+
+/// Serializes tree entries, which must already be in `tree_entry_cmp` order.
+pub fn serialize_tree(entries: &[TreeEntry]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for entry in entries {
+        content.extend_from_slice(entry.mode.as_str().as_bytes());
+        content.push(b' ');
+        content.extend_from_slice(entry.name.as_bytes());
+        content.push(0);
+        content.extend_from_slice(entry.hash.as_bytes());
+    }
+    content
+}
@@ -0,0 +1,266 @@
+use std::io;
+
+use crate::utils::{path_utils::to_repo_path, wildmatch::{has_glob_chars, wildmatch, WM_CASEFOLD, WM_PATHNAME}};
+
+/// Magic words that can prefix a pathspec item, either as `:(word,word)path`
+/// or in the short form `:!path` / `:^path` / `:/path`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PathSpecMagic {
+    pub top: bool,
+    pub exclude: bool,
+    pub icase: bool,
+    pub literal: bool,
+    pub glob: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PathSpecItem {
+    /// The item as the user typed it, for error messages.
+    pub original: String,
+    /// Repo-relative pattern with the magic stripped.
+    pub pattern: String,
+    pub magic: PathSpecMagic,
+    /// Length of the leading part of `pattern` without glob characters.
+    literal_len: usize,
+}
+
+impl PathSpecItem {
+    pub fn parse(arg: &str) -> io::Result<Self> {
+        let (magic, rest) = parse_magic(arg)?;
+
+        let is_glob = !magic.literal && has_glob_chars(rest);
+        let pattern = if is_glob {
+            let mut trimmed = rest;
+            while let Some(stripped) = trimmed.strip_prefix("./") {
+                trimmed = stripped;
+            }
+            trimmed.to_string()
+        } else {
+            to_repo_path(rest)?
+        };
+
+        let literal_len = if is_glob {
+            pattern.find(['*', '?', '[', '\\']).unwrap_or(pattern.len())
+        } else {
+            pattern.len()
+        };
+
+        Ok(Self {
+            original: arg.to_string(),
+            pattern,
+            magic,
+            literal_len,
+        })
+    }
+
+    pub fn is_glob(&self) -> bool {
+        self.literal_len < self.pattern.len()
+    }
+
+    /// The directory every match must live under ("" for the whole tree).
+    pub fn base_dir(&self) -> &str {
+        if self.magic.icase {
+            // the on-disk spelling is unknown, so the whole tree has to be searched
+            return "";
+        }
+        if !self.is_glob() {
+            return &self.pattern;
+        }
+        let literal = &self.pattern[..self.literal_len];
+        match literal.rfind('/') {
+            Some(pos) => &literal[..pos],
+            None => "",
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if self.pattern.is_empty() {
+            return true;
+        }
+
+        let (pattern, path) = if self.magic.icase {
+            (self.pattern.to_lowercase(), path.to_lowercase())
+        } else {
+            (self.pattern.clone(), path.to_string())
+        };
+
+        if is_same_or_parent(&pattern, &path) {
+            return true;
+        }
+
+        if !self.is_glob() {
+            return false;
+        }
+
+        let mut flags = 0;
+        if self.magic.glob {
+            flags |= WM_PATHNAME;
+        }
+        if self.magic.icase {
+            flags |= WM_CASEFOLD;
+        }
+        wildmatch(&pattern, &path, flags)
+    }
+
+    /// Whether something below the directory `dir` could match this item.
+    pub fn could_match_under(&self, dir: &str) -> bool {
+        if self.pattern.is_empty() || dir.is_empty() {
+            return true;
+        }
+
+        let (literal, dir) = if self.magic.icase {
+            (self.pattern[..self.literal_len].to_lowercase(), dir.to_lowercase())
+        } else {
+            (self.pattern[..self.literal_len].to_string(), dir.to_string())
+        };
+
+        if self.is_glob() {
+            let dir_prefix = format!("{}/", dir);
+            dir_prefix.starts_with(&literal) || literal.starts_with(&dir_prefix)
+        } else {
+            is_same_or_parent(&literal, &dir) || is_same_or_parent(&dir, &literal)
+        }
+    }
+}
+
+/// A parsed list of pathspec items. An empty list (or one with only
+/// exclusions) matches every path.
+#[derive(Debug, Clone, Default)]
+pub struct PathSpec {
+    items: Vec<PathSpecItem>,
+}
+
+impl PathSpec {
+    pub fn parse(args: &[String]) -> io::Result<Self> {
+        let items = args.iter().map(|a| PathSpecItem::parse(a)).collect::<io::Result<Vec<_>>>()?;
+        Ok(Self { items })
+    }
+
+    pub fn items(&self) -> &[PathSpecItem] {
+        &self.items
+    }
+
+    /// True if no positive items were given, i.e. everything is in scope.
+    pub fn is_unrestricted(&self) -> bool {
+        !self.items.iter().any(|i| !i.magic.exclude)
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.matching_items(path).is_some()
+    }
+
+    /// Returns the indexes of the positive items matching `path`, or `None` if
+    /// the path is out of scope (matches nothing, or is excluded).
+    pub fn matching_items(&self, path: &str) -> Option<Vec<usize>> {
+        if self.items.iter().any(|i| i.magic.exclude && i.matches(path)) {
+            return None;
+        }
+
+        if self.is_unrestricted() {
+            return Some(Vec::new());
+        }
+
+        let matched: Vec<usize> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !item.magic.exclude && item.matches(path))
+            .map(|(i, _)| i)
+            .collect();
+
+        if matched.is_empty() {
+            None
+        } else {
+            Some(matched)
+        }
+    }
+
+    /// Whether a directory needs to be descended into to find matches.
+    pub fn could_match_under(&self, dir: &str) -> bool {
+        if self.is_unrestricted() {
+            return true;
+        }
+        self.items.iter().any(|i| !i.magic.exclude && i.could_match_under(dir))
+    }
+
+    /// The distinct directories to walk to find every possible match,
+    /// with nested ones folded into their parents.
+    pub fn base_dirs(&self) -> Vec<String> {
+        if self.is_unrestricted() {
+            return vec![String::new()];
+        }
+
+        let mut dirs: Vec<String> = self
+            .items
+            .iter()
+            .filter(|i| !i.magic.exclude)
+            .map(|i| i.base_dir().to_string())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        let mut result: Vec<String> = Vec::new();
+        for dir in dirs {
+            if !result.iter().any(|parent| is_same_or_parent(parent, &dir)) {
+                result.push(dir);
+            }
+        }
+        result
+    }
+}
+
+/// `parent` is `path` itself or one of its leading directories.
+fn is_same_or_parent(parent: &str, path: &str) -> bool {
+    parent.is_empty() || path == parent || (path.starts_with(parent) && path.as_bytes().get(parent.len()) == Some(&b'/'))
+}
+
+fn parse_magic(arg: &str) -> io::Result<(PathSpecMagic, &str)> {
+    let mut magic = PathSpecMagic::default();
+    let Some(rest) = arg.strip_prefix(':') else {
+        return Ok((magic, arg));
+    };
+
+    if let Some(long) = rest.strip_prefix('(') {
+        let end = long.find(')').ok_or_else(|| invalid(arg, "missing ')' at the end of pathspec magic"))?;
+        for word in long[..end].split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            match word {
+                "top" => magic.top = true,
+                "exclude" => magic.exclude = true,
+                "icase" => magic.icase = true,
+                "literal" => magic.literal = true,
+                "glob" => magic.glob = true,
+                other => return Err(invalid(arg, &format!("invalid pathspec magic '{}'", other))),
+            }
+        }
+        return check_magic(arg, magic, &long[end + 1..]);
+    }
+
+    let mut consumed = rest.len();
+    for (i, c) in rest.char_indices() {
+        match c {
+            '/' => magic.top = true,
+            '!' | '^' => magic.exclude = true,
+            ':' => {
+                consumed = i + 1;
+                break;
+            }
+            _ => {
+                consumed = i;
+                break;
+            }
+        }
+    }
+
+    check_magic(arg, magic, &rest[consumed..])
+}
+
+fn check_magic<'a>(arg: &str, magic: PathSpecMagic, rest: &'a str) -> io::Result<(PathSpecMagic, &'a str)> {
+    if magic.literal && magic.glob {
+        return Err(invalid(arg, "'literal' and 'glob' are incompatible"));
+    }
+    Ok((magic, rest))
+}
+
+fn invalid(arg: &str, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("{}: '{}'", reason, arg))
+}
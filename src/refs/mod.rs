@@ -0,0 +1,173 @@
+pub mod reflog;
+pub mod revision;
+
+pub use revision::resolve_revision;
+
+use std::{fs, io, path::Path};
+
+use crate::{config::git_config::GitConfig, constants::{GIT_DIR, GIT_HEAD_PATH}, hash::GitHash};
+
+pub const PACKED_REFS_PATH: &str = ".git/packed-refs";
+
+/// Where HEAD points: a branch (by full ref name, which may not exist yet) or a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    Branch(String),
+    Detached(GitHash),
+}
+
+impl Head {
+    /// The branch name without `refs/heads/`, if HEAD is on a branch.
+    pub fn branch_name(&self) -> Option<&str> {
+        match self {
+            Head::Branch(name) => Some(shorten_ref(name)),
+            Head::Detached(_) => None,
+        }
+    }
+}
+
+pub fn read_head() -> io::Result<Head> {
+    let content = fs::read_to_string(GIT_HEAD_PATH)?;
+    let content = content.trim();
+    match content.strip_prefix("ref:") {
+        Some(target) => Ok(Head::Branch(target.trim().to_string())),
+        None => Ok(Head::Detached(GitHash::from_hex(content)?)),
+    }
+}
+
+/// The commit HEAD points at, or `None` on an unborn branch.
+pub fn head_commit() -> io::Result<Option<GitHash>> {
+    resolve_ref("HEAD")
+}
+
+/// Resolves a full ref name (`HEAD`, `refs/heads/main`, ...) through symbolic
+/// refs, loose ref files and `packed-refs`. Returns `None` if the ref doesn't exist.
+pub fn resolve_ref(name: &str) -> io::Result<Option<GitHash>> {
+    let mut name = name.to_string();
+
+    // bounded, like git, so a symref cycle can't loop forever
+    for _ in 0..5 {
+        let path = Path::new(GIT_DIR).join(&name);
+        if !path.is_file() {
+            return Ok(read_packed_refs()?.into_iter().find(|(n, _)| *n == name).map(|(_, hash)| hash));
+        }
+
+        let content = fs::read_to_string(path)?;
+        let content = content.trim();
+        match content.strip_prefix("ref:") {
+            Some(target) => name = target.trim().to_string(),
+            None => return GitHash::from_hex(content).map(Some),
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, format!("symbolic ref loop at '{}'", name)))
+}
+
+/// Points `name`, a full ref like `refs/heads/main`, at `hash` as a loose ref.
+/// The file is written under a lock name first and renamed into place.
+pub fn write_ref(name: &str, hash: &GitHash) -> io::Result<()> {
+    let path = Path::new(GIT_DIR).join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock_path = Path::new(GIT_DIR).join(format!("{}.lock", name));
+    fs::write(&lock_path, format!("{}\n", hash.to_hex()))?;
+    fs::rename(lock_path, path)
+}
+
+/// Points HEAD at a branch, or detaches it at a commit.
+pub fn write_head(head: &Head) -> io::Result<()> {
+    let content = match head {
+        Head::Branch(name) => format!("ref: {}\n", name),
+        Head::Detached(hash) => format!("{}\n", hash.to_hex()),
+    };
+    fs::write(GIT_HEAD_PATH, content)
+}
+
+/// Whether `name` is acceptable as a branch name, following the rules of
+/// `git check-ref-format --branch`.
+pub fn is_valid_branch_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "HEAD"
+        && !name.starts_with('-')
+        && !name.ends_with('/')
+        && !name.ends_with(".lock")
+        && !name.ends_with('.')
+        && !name.contains("..")
+        && !name.contains("@{")
+        && !name.contains("//")
+        && name != "@"
+        && name.split('/').all(|part| !part.is_empty() && !part.starts_with('.'))
+        && !name.chars().any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c))
+}
+
+/// The `(name, id)` pairs in `packed-refs`, skipping comments and peeled lines.
+pub fn read_packed_refs() -> io::Result<Vec<(String, GitHash)>> {
+    let content = match fs::read_to_string(PACKED_REFS_PATH) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut refs = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('#') || line.starts_with('^') || line.is_empty() {
+            continue;
+        }
+        if let Some((hash, name)) = line.split_once(' ') {
+            refs.push((name.trim().to_string(), GitHash::from_hex(hash)?));
+        }
+    }
+    Ok(refs)
+}
+
+/// Every ref under `refs/`, loose and packed, sorted by name. A loose ref
+/// overrides a packed one of the same name.
+pub fn list_refs() -> io::Result<Vec<(String, GitHash)>> {
+    let mut refs: Vec<(String, GitHash)> = Vec::new();
+    let mut dirs = vec!["refs".to_string()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(Path::new(GIT_DIR).join(&dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = format!("{}/{}", dir, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                dirs.push(name);
+            } else if !name.ends_with(".lock") {
+                if let Some(hash) = resolve_ref(&name)? {
+                    refs.push((name, hash));
+                }
+            }
+        }
+    }
+    for (name, hash) in read_packed_refs()? {
+        if !refs.iter().any(|(n, _)| *n == name) {
+            refs.push((name, hash));
+        }
+    }
+    refs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(refs)
+}
+
+/// Drops the `refs/heads/`, `refs/tags/` or `refs/remotes/` prefix for display.
+pub fn shorten_ref(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// The full ref a branch tracks, from `branch.<name>.remote` and `branch.<name>.merge`.
+pub fn upstream_ref(config: &GitConfig, branch: &str) -> Option<String> {
+    let remote = config.get(&format!("branch.{}.remote", branch))?;
+    let merge = config.get(&format!("branch.{}.merge", branch))?;
+    if remote == "." {
+        return Some(merge.to_string());
+    }
+    let merge_branch = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    Some(format!("refs/remotes/{}/{}", remote, merge_branch))
+}
@@ -0,0 +1,78 @@
+//! Reflogs: the history of where a ref pointed, one line per update in
+//! `.git/logs/<ref>`.
+
+use std::{fs, io::{self, Write}, path::{Path, PathBuf}};
+
+use crate::{
+    constants::GIT_DIR,
+    hash::GitHash,
+    objects::{ident::parse_person, Person},
+    refs::{read_head, resolve_ref, write_head, write_ref, Head},
+};
+
+fn reflog_path(name: &str) -> PathBuf {
+    Path::new(GIT_DIR).join("logs").join(name)
+}
+
+/// Appends `old new who<TAB>message` to the reflog of `name`, a full ref
+/// name or `HEAD`. A ref that didn't exist before is logged as moving from
+/// the zero id.
+pub fn append_reflog(name: &str, old: Option<&GitHash>, new: &GitHash, who: &Person, message: &str) -> io::Result<()> {
+    let path = reflog_path(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let old = old.cloned().unwrap_or_else(GitHash::zero);
+    // a log entry is a single line
+    let message = message.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} {} {}\t{}", old.to_hex(), new.to_hex(), who, message)
+}
+
+/// One update recorded in a reflog.
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: GitHash,
+    pub new: GitHash,
+    pub who: Person,
+    pub message: String,
+}
+
+/// The reflog of `name`, oldest entry first; empty if it has none.
+pub fn read_reflog(name: &str) -> io::Result<Vec<ReflogEntry>> {
+    let content = match fs::read_to_string(reflog_path(name)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, format!("malformed reflog for '{}'", name));
+
+    let mut entries = Vec::new();
+    for line in content.lines().filter(|l| !l.is_empty()) {
+        let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = head.splitn(3, ' ');
+        let (Some(old), Some(new), Some(who)) = (parts.next(), parts.next(), parts.next()) else { return Err(malformed()) };
+        entries.push(ReflogEntry {
+            old: GitHash::from_hex(old)?,
+            new: GitHash::from_hex(new)?,
+            who: parse_person(who).ok_or_else(malformed)?,
+            message: message.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Moves whatever HEAD points at to `new`: the current branch, born if it
+/// wasn't yet, or HEAD itself when detached. Both the branch's and HEAD's
+/// reflogs get the entry.
+pub fn update_head(new: &GitHash, who: &Person, message: &str) -> io::Result<()> {
+    let old = resolve_ref("HEAD")?;
+    match read_head()? {
+        Head::Branch(branch) => {
+            write_ref(&branch, new)?;
+            append_reflog(&branch, old.as_ref(), new, who, message)?;
+        }
+        Head::Detached(_) => write_head(&Head::Detached(new.clone()))?,
+    }
+    append_reflog("HEAD", old.as_ref(), new, who, message)
+}
@@ -0,0 +1,386 @@
+//! Git's revision syntax (see gitrevisions): object ids and ref names, with
+//! suffixes to walk to ancestors and peel objects, reflog lookups, and the
+//! paths inside trees and the index.
+
+use std::{fs, io, path::Path};
+
+use regex::Regex;
+
+use crate::{
+    config::git_config::GitConfig,
+    constants::{GIT_DIR, INDEX_PATH},
+    hash::{GitHash, HASH_HEX_LENGTH},
+    index::index::load_index,
+    objects::{commit_object::{peel_to_commit, read_commit, tag_target}, find_objects_by_prefix, ident::{format_date_as, parse_approxidate, DateMode}, peel_to_tree, read_object, ObjectType, Tree},
+    refs::{read_head, reflog::read_reflog, resolve_ref, upstream_ref, Head},
+    revwalk::{all_tips, RevWalk},
+};
+
+/// The full ref names a short name may stand for, in git's order of precedence.
+const DWIM_RULES: [&str; 6] = ["{}", "refs/{}", "refs/tags/{}", "refs/heads/{}", "refs/remotes/{}", "refs/remotes/{}/HEAD"];
+
+fn dwim_ref_names(name: &str) -> Vec<String> {
+    if name.is_empty() || name.contains("..") {
+        return Vec::new();
+    }
+    if name == "HEAD" {
+        return vec![name.to_string()];
+    }
+    DWIM_RULES.iter().map(|rule| rule.replace("{}", name)).collect()
+}
+
+/// Resolves a revision given on the command line. Returns `None` if it
+/// names nothing; malformed or impossible lookups, like a path missing from
+/// a tree or a reflog too short, are errors.
+pub fn resolve_revision(spec: &str) -> io::Result<Option<GitHash>> {
+    if let Some(pattern) = spec.strip_prefix(":/") {
+        return youngest_matching(&all_tips(false)?.into_iter().map(|tip| tip.hash).collect::<Vec<_>>(), pattern);
+    }
+    if let Some(rest) = spec.strip_prefix(':') {
+        return index_entry(rest).map(Some);
+    }
+    match path_separator(spec) {
+        Some(colon) => {
+            let (rev, path) = (&spec[..colon], &spec[colon + 1..]);
+            let Some(hash) = resolve_object(rev)? else { return Ok(None) };
+            tree_entry(&peel_to_tree(&hash.to_hex())?, path, rev).map(Some)
+        }
+        None => resolve_object(spec),
+    }
+}
+
+/// Where `<rev>:<path>` splits: the first colon not inside `@{...}`.
+fn path_separator(spec: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            ':' if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `:<path>` or `:<stage>:<path>`: the blob staged in the index.
+fn index_entry(spec: &str) -> io::Result<GitHash> {
+    let (stage, path) = match spec.split_once(':') {
+        Some((stage, path)) if stage.len() == 1 && stage.as_bytes()[0].is_ascii_digit() => (stage.as_bytes()[0] - b'0', path),
+        _ => (0, spec),
+    };
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let index = load_index(Path::new(INDEX_PATH))?;
+    match index.get(path, stage) {
+        Some(entry) => Ok(entry.hash.clone()),
+        None if stage == 0 => Err(io::Error::new(io::ErrorKind::NotFound, format!("path '{}' does not exist in the index", path))),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, format!("path '{}' is not in the index at stage {}", path, stage))),
+    }
+}
+
+/// The object at `path` in a tree; the tree itself for an empty path.
+fn tree_entry(tree: &GitHash, path: &str, rev: &str) -> io::Result<GitHash> {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let mut hash = tree.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let (object_type, _) = read_object(&hash.to_hex())?;
+        let found = match object_type {
+            ObjectType::Tree => Tree::load_tree_from_hash(&hash.to_hex())?.entries.into_iter().find(|entry| entry.name == name),
+            _ => None,
+        };
+        hash = found
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("path '{}' does not exist in '{}'", path, rev)))?
+            .hash;
+    }
+    Ok(hash)
+}
+
+/// A revision without a path: a name with any `~<n>`, `^<n>` and `^{...}`
+/// suffixes.
+fn resolve_object(spec: &str) -> io::Result<Option<GitHash>> {
+    if spec.ends_with('}') {
+        if let Some(start) = spec.rfind("^{") {
+            let Some(hash) = resolve_object(&spec[..start])? else { return Ok(None) };
+            return peel(&hash, &spec[start + 2..spec.len() - 1], spec);
+        }
+    }
+
+    // a trailing `~<n>` or `^<n>`, where a missing number means 1
+    let digits = spec.len() - spec.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let op_at = spec.len() - digits;
+    if op_at > 0 && matches!(spec.as_bytes()[op_at - 1], b'~' | b'^') {
+        let Some(hash) = resolve_object(&spec[..op_at - 1])? else { return Ok(None) };
+        let n: usize = if digits == 0 { 1 } else { spec[op_at..].parse().map_err(|_| invalid(spec))? };
+        let commit = peel_to_commit(&hash)?;
+        return if spec.as_bytes()[op_at - 1] == b'^' { nth_parent(commit, n) } else { nth_ancestor(commit, n) };
+    }
+
+    resolve_name(spec)
+}
+
+fn invalid(spec: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid revision '{}'", spec))
+}
+
+/// `^<n>`: the n-th parent, or the commit itself for `^0`.
+fn nth_parent(commit: GitHash, n: usize) -> io::Result<Option<GitHash>> {
+    if n == 0 {
+        return Ok(Some(commit));
+    }
+    Ok(read_commit(&commit)?.parents.get(n - 1).cloned())
+}
+
+/// `~<n>`: the n-th first-parent ancestor.
+fn nth_ancestor(mut commit: GitHash, n: usize) -> io::Result<Option<GitHash>> {
+    for _ in 0..n {
+        match read_commit(&commit)?.parents.into_iter().next() {
+            Some(parent) => commit = parent,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(commit))
+}
+
+/// `^{<kind>}`: peels tags (and commits, for a tree) until an object of the
+/// kind is reached; `^{}` peels tags to whatever they point at and
+/// `^{/<regex>}` finds the youngest commit whose message matches.
+fn peel(hash: &GitHash, kind: &str, spec: &str) -> io::Result<Option<GitHash>> {
+    if let Some(pattern) = kind.strip_prefix('/') {
+        return youngest_matching(&[peel_to_commit(hash)?], pattern);
+    }
+    let wrong_type = |found: ObjectType| io::Error::new(io::ErrorKind::InvalidInput, format!("{}: expected {} type, found {}", spec, kind, found));
+    match kind {
+        "object" => Ok(Some(hash.clone())),
+        "commit" => peel_to_commit(hash).map(Some),
+        "tree" => peel_to_tree(&hash.to_hex()).map(Some),
+        "" | "blob" | "tag" => {
+            let mut hash = hash.clone();
+            loop {
+                let (object_type, content) = read_object(&hash.to_hex())?;
+                match (object_type, kind) {
+                    (ObjectType::Tag, "tag") | (ObjectType::Blob, "blob") => return Ok(Some(hash)),
+                    (ObjectType::Tag, _) => hash = tag_target(&content)?,
+                    (_, "") => return Ok(Some(hash)),
+                    (other, _) => return Err(wrong_type(other)),
+                }
+            }
+        }
+        _ => Err(invalid(spec)),
+    }
+}
+
+/// The newest commit reachable from `tips` whose message matches `pattern`.
+fn youngest_matching(tips: &[GitHash], pattern: &str) -> io::Result<Option<GitHash>> {
+    let re = Regex::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid regex '{}': {}", pattern, e)))?;
+    let mut walk = RevWalk::new();
+    for tip in tips {
+        walk.push(tip)?;
+    }
+    for found in walk {
+        let (hash, commit) = found?;
+        if re.is_match(&commit.message) {
+            return Ok(Some(hash));
+        }
+    }
+    Ok(None)
+}
+
+/// A name with no suffixes: `@`, `<ref>@{...}`, a ref in any short form git
+/// accepts, or a full or unique abbreviated object id.
+fn resolve_name(name: &str) -> io::Result<Option<GitHash>> {
+    if name == "@" {
+        return resolve_ref("HEAD");
+    }
+    if let Some((base, at)) = split_at_braces(name) {
+        return resolve_at(base, at);
+    }
+
+    if name.len() == HASH_HEX_LENGTH {
+        if let Ok(hash) = GitHash::from_hex(name) {
+            return Ok(Some(hash));
+        }
+    }
+    for candidate in dwim_ref_names(name) {
+        if let Some(hash) = resolve_ref(&candidate)? {
+            return Ok(Some(hash));
+        }
+    }
+    if name.len() >= 4 && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+        let mut found = find_objects_by_prefix(name)?;
+        if found.len() > 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("short object ID {} is ambiguous", name)));
+        }
+        return Ok(found.pop());
+    }
+    Ok(None)
+}
+
+/// Splits `<base>@{<at>}` into its parts.
+fn split_at_braces(name: &str) -> Option<(&str, &str)> {
+    let start = name.find("@{")?;
+    let inner = name[start + 2..].strip_suffix('}')?;
+    Some((&name[..start], inner))
+}
+
+/// `@{-<n>}`, `@{upstream}`, `@{<n>}` and `@{<date>}`, after an optional ref.
+fn resolve_at(base: &str, at: &str) -> io::Result<Option<GitHash>> {
+    if base.is_empty() {
+        if let Some(n) = at.strip_prefix('-') {
+            let n = n.parse().map_err(|_| invalid(at))?;
+            return match previous_branch(n)? {
+                Some(name) => resolve_name(&name),
+                None => Ok(None),
+            };
+        }
+    }
+    if is_upstream(at) {
+        let full = upstream_of(base)?;
+        return resolve_ref(&full);
+    }
+
+    let Some(log_name) = reflog_name(base)? else { return Ok(None) };
+    let entries = read_reflog(&log_name)?;
+    let short = log_name.strip_prefix("refs/heads/").unwrap_or(&log_name);
+    if entries.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("log for '{}' is empty", short)));
+    }
+
+    // newest first; `@{n}` is where the ref was n updates ago
+    if let Ok(n) = at.parse::<usize>() {
+        return match entries.len().checked_sub(n + 1) {
+            Some(i) => Ok(Some(entries[i].new.clone())),
+            None if n == entries.len() && entries[0].old != GitHash::zero() => Ok(Some(entries[0].old.clone())),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("log for '{}' only has {} entries", short, entries.len()))),
+        };
+    }
+
+    let date = parse_approxidate(at)?;
+    if let Some(entry) = entries.iter().rev().find(|entry| entry.who.timestamp <= date) {
+        return Ok(Some(entry.new.clone()));
+    }
+    let oldest = &entries[0];
+    eprintln!("warning: log for '{}' only goes back to {}", short, format_date_as(oldest.who.timestamp, &oldest.who.timezone, &DateMode::Rfc));
+    Ok(Some(if oldest.old != GitHash::zero() { oldest.old.clone() } else { oldest.new.clone() }))
+}
+
+fn is_upstream(at: &str) -> bool {
+    at.eq_ignore_ascii_case("upstream") || at.eq_ignore_ascii_case("u")
+}
+
+/// The full name of the ref `<branch>@{upstream}` stands for; the current
+/// branch's for an empty name or HEAD.
+fn upstream_of(base: &str) -> io::Result<String> {
+    let branch = match base {
+        "" | "HEAD" => match read_head()? {
+            Head::Branch(name) => name.strip_prefix("refs/heads/").unwrap_or(&name).to_string(),
+            Head::Detached(_) => return Err(io::Error::new(io::ErrorKind::NotFound, "HEAD does not point to a branch")),
+        },
+        name => {
+            if resolve_ref(&format!("refs/heads/{}", name))?.is_none() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("no such branch: '{}'", name)));
+            }
+            name.to_string()
+        }
+    };
+    upstream_ref(&GitConfig::load()?, &branch).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no upstream configured for branch '{}'", branch)))
+}
+
+/// The reflog `<base>@{...}` reads: the current branch's for an empty
+/// name, else the first ref the name may stand for that exists.
+fn reflog_name(base: &str) -> io::Result<Option<String>> {
+    if base.is_empty() {
+        return Ok(Some(match read_head()? {
+            Head::Branch(name) => name,
+            Head::Detached(_) => "HEAD".to_string(),
+        }));
+    }
+    for candidate in dwim_ref_names(base) {
+        if Path::new(GIT_DIR).join("logs").join(&candidate).is_file() || resolve_ref(&candidate)?.is_some() {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// `@{-<n>}`: the branch (or commit) checked out before the n-th last
+/// checkout, from HEAD's reflog.
+fn previous_branch(n: usize) -> io::Result<Option<String>> {
+    if n == 0 {
+        return Ok(None);
+    }
+    let previous = read_reflog("HEAD")?
+        .into_iter()
+        .rev()
+        .filter_map(|entry| entry.message.strip_prefix("checkout: moving from ").and_then(|m| m.split_once(" to ")).map(|(from, _)| from.to_string()))
+        .nth(n - 1);
+    Ok(previous)
+}
+
+/// The full name of the ref a revision names, following symbolic refs, as
+/// `--abbrev-ref` and `--symbolic-full-name` show it. `None` if it isn't a ref.
+pub fn dwim_ref(name: &str) -> io::Result<Option<String>> {
+    if name == "@" {
+        return dwim_ref("HEAD");
+    }
+    if let Some((base, at)) = split_at_braces(name) {
+        if base.is_empty() {
+            if let Some(n) = at.strip_prefix('-') {
+                let n = n.parse().map_err(|_| invalid(at))?;
+                return match previous_branch(n)? {
+                    Some(branch) => dwim_ref(&branch),
+                    None => Ok(None),
+                };
+            }
+        }
+        if is_upstream(at) {
+            return upstream_of(base).map(Some);
+        }
+        return Ok(None);
+    }
+
+    for candidate in dwim_ref_names(name) {
+        if resolve_ref(&candidate)?.is_some() {
+            return follow_symref(&candidate).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// The ref a symbolic ref ends up at.
+fn follow_symref(name: &str) -> io::Result<String> {
+    let mut name = name.to_string();
+    // bounded, like resolve_ref
+    for _ in 0..5 {
+        let Ok(content) = fs::read_to_string(Path::new(GIT_DIR).join(&name)) else { break };
+        match content.trim().strip_prefix("ref:") {
+            Some(target) => name = target.trim().to_string(),
+            None => break,
+        }
+    }
+    Ok(name)
+}
+
+/// The shortest name that still resolves to the full ref `name` and
+/// nothing else, like `main` for `refs/heads/main` unless a tag `main`
+/// exists too.
+pub fn shorten_unambiguous_ref(name: &str) -> io::Result<String> {
+    // the first rule, the name as it is, is never a shortening
+    for (i, rule) in DWIM_RULES.iter().enumerate().skip(1).rev() {
+        let (prefix, suffix) = rule.split_once("{}").expect("rules have a placeholder");
+        let Some(short) = name.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(suffix)) else { continue };
+        if short.is_empty() {
+            continue;
+        }
+        let mut ambiguous = false;
+        for (j, other) in DWIM_RULES.iter().enumerate() {
+            if j != i && resolve_ref(&other.replace("{}", short))?.is_some() {
+                ambiguous = true;
+                break;
+            }
+        }
+        if !ambiguous {
+            return Ok(short.to_string());
+        }
+    }
+    Ok(name.to_string())
+}
@@ -0,0 +1,516 @@
+//! Walking commit history the way `git log` and `git rev-list` do: from
+//! commits to show and commits whose history to leave out, newest commit
+//! date first or in one of the sorted orders.
+
+use std::{cmp::{Ordering, Reverse}, collections::{BinaryHeap, HashMap, HashSet}, io, vec};
+
+use crate::{
+    commands::parse_error::CommandParseError,
+    diff::changes::diff_trees,
+    hash::GitHash,
+    objects::commit_object::{peel_to_commit, read_commit, ParsedCommit},
+    pathspec::PathSpec,
+    refs::{head_commit, list_refs, resolve_revision},
+};
+
+/// A commit waiting to be walked. The heap pops the newest commit first
+/// and, among equal dates, the one queued first.
+struct Queued {
+    hash: GitHash,
+    commit: ParsedCommit,
+    order: u64,
+}
+
+impl Queued {
+    fn date(&self) -> i64 {
+        self.commit.committer.timestamp
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.date().cmp(&other.date()).then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// The order a walk gives commits in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sort {
+    /// Newest commit date first, as the history is read.
+    #[default]
+    Default,
+    /// No commit before all of its children, keeping the commits of a
+    /// line of history together (`--topo-order`).
+    Topo,
+    /// No commit before its children, otherwise newest commit date first
+    /// (`--date-order`).
+    CommitDate,
+    /// Like `CommitDate`, by author date (`--author-date-order`).
+    AuthorDate,
+}
+
+/// The walk options shared by the history commands.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub sort: Sort,
+    /// `--first-parent`: only follow the first parent of merges.
+    pub first_parent: bool,
+    /// `--ancestry-path`: only show commits that are descendants of the
+    /// hidden ones as well as ancestors of the shown ones.
+    pub ancestry_path: bool,
+    /// `--min-parents`/`--max-parents`: how many parents the commits to
+    /// show may have.
+    pub min_parents: usize,
+    pub max_parents: Option<usize>,
+}
+
+impl WalkOptions {
+    /// Handles `arg` if it is a walk option. Returns `false` for anything else.
+    pub fn parse_option(&mut self, arg: &str) -> io::Result<bool> {
+        let count = |value: &str| value.parse::<usize>().map_err(|_| io::Error::from(CommandParseError::InvalidArgument(format!("'{}' is not a number", value))));
+        match arg {
+            "--topo-order" => self.sort = Sort::Topo,
+            "--date-order" => self.sort = Sort::CommitDate,
+            "--author-date-order" => self.sort = Sort::AuthorDate,
+            "--first-parent" => self.first_parent = true,
+            "--ancestry-path" => self.ancestry_path = true,
+            "--merges" => self.min_parents = 2,
+            "--no-merges" => self.max_parents = Some(1),
+            "--no-min-parents" => self.min_parents = 0,
+            "--no-max-parents" => self.max_parents = None,
+            x if x.starts_with("--min-parents=") => self.min_parents = count(&x["--min-parents=".len()..])?,
+            x if x.starts_with("--max-parents=") => self.max_parents = Some(count(&x["--max-parents=".len()..])?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn wants_parents(&self, count: usize) -> bool {
+        count >= self.min_parents && !matches!(self.max_parents, Some(max) if count > max)
+    }
+}
+
+/// A commit to walk from, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tip {
+    pub hash: GitHash,
+    /// `^X`: the history of this commit is left out of the walk.
+    pub hidden: bool,
+}
+
+/// Parses a revision argument: a revision, `^X`, `A..B` (the history of B
+/// without that of A) or `A...B` (the history of either without that of
+/// their merge bases). A missing end of a range is HEAD. After `--not`,
+/// `negated` flips the sense of each. Returns `None` if a name doesn't resolve.
+pub fn parse_revision_arg(arg: &str, negated: bool) -> io::Result<Option<Vec<Tip>>> {
+    let resolve = |name: &str| resolve_revision(if name.is_empty() { "HEAD" } else { name });
+    if let Some((a, b)) = arg.split_once("...") {
+        let (Some(a), Some(b)) = (resolve(a)?, resolve(b)?) else { return Ok(None) };
+        let bases = merge_bases(&peel_to_commit(&a)?, &peel_to_commit(&b)?)?;
+        let mut tips = vec![Tip { hash: a, hidden: negated }, Tip { hash: b, hidden: negated }];
+        tips.extend(bases.into_iter().map(|hash| Tip { hash, hidden: !negated }));
+        return Ok(Some(tips));
+    }
+    if let Some((a, b)) = arg.split_once("..") {
+        let (Some(a), Some(b)) = (resolve(a)?, resolve(b)?) else { return Ok(None) };
+        return Ok(Some(vec![Tip { hash: a, hidden: !negated }, Tip { hash: b, hidden: negated }]));
+    }
+    let (name, hidden) = match arg.strip_prefix('^') {
+        Some(name) => (name, !negated),
+        None => (arg, negated),
+    };
+    Ok(resolve_revision(name)?.map(|hash| vec![Tip { hash, hidden }]))
+}
+
+/// `--all`: every ref, and HEAD.
+pub fn all_tips(hidden: bool) -> io::Result<Vec<Tip>> {
+    let mut tips: Vec<Tip> = list_refs()?.into_iter().map(|(_, hash)| Tip { hash, hidden }).collect();
+    if let Some(hash) = head_commit()? {
+        tips.push(Tip { hash, hidden });
+    }
+    Ok(tips)
+}
+
+/// The best common ancestors of two commits: those common ancestors that
+/// aren't ancestors of another one.
+pub fn merge_bases(a: &GitHash, b: &GitHash) -> io::Result<Vec<GitHash>> {
+    let ancestors_of_a: HashSet<GitHash> = ancestors(a)?.into_iter().map(|(hash, _)| hash).collect();
+    let common: Vec<(GitHash, Vec<GitHash>)> = ancestors(b)?.into_iter().filter(|(hash, _)| ancestors_of_a.contains(hash)).collect();
+    // any ancestor of a common ancestor is the parent of another one
+    let behind: HashSet<&GitHash> = common.iter().flat_map(|(_, parents)| parents).collect();
+    let mut bases: Vec<(i64, GitHash)> = Vec::new();
+    for (hash, _) in &common {
+        if !behind.contains(hash) {
+            bases.push((read_commit(hash)?.committer.timestamp, hash.clone()));
+        }
+    }
+    // newest first, like git
+    bases.sort_by_key(|(date, _)| Reverse(*date));
+    Ok(bases.into_iter().map(|(_, hash)| hash).collect())
+}
+
+/// Every commit reachable from `tip`, with its parents, in the order found.
+fn ancestors(tip: &GitHash) -> io::Result<Vec<(GitHash, Vec<GitHash>)>> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::from([tip.clone()]);
+    let mut stack = vec![tip.clone()];
+    while let Some(hash) = stack.pop() {
+        let parents = read_commit(&hash)?.parents;
+        for parent in &parents {
+            if seen.insert(parent.clone()) {
+                stack.push(parent.clone());
+            }
+        }
+        found.push((hash, parents));
+    }
+    Ok(found)
+}
+
+/// How many more commits a limited walk reads once only hidden commits are
+/// left, in case of clock skew.
+const SLOP: usize = 5;
+
+#[derive(Default)]
+pub struct RevWalk {
+    queue: BinaryHeap<Queued>,
+    seen: HashSet<GitHash>,
+    queued: u64,
+    /// Commits reachable from a hidden one, as far as the walk has got.
+    hidden: HashSet<GitHash>,
+    /// The parents of every commit read, to pass hiding on to them.
+    parents: HashMap<GitHash, Vec<GitHash>>,
+    /// The hidden starting points, where `--ancestry-path` starts from.
+    bottoms: Vec<GitHash>,
+    opts: WalkOptions,
+    /// Paths whose history to show: commits that don't change them are
+    /// skipped, and so are the side branches they came in through.
+    paths: Option<PathSpec>,
+    max_count: Option<usize>,
+    reverse: bool,
+    /// How many commits a walk read as it goes has given.
+    emitted: usize,
+    /// Every commit of a walk that has to be read in full first.
+    sorted: Option<vec::IntoIter<(GitHash, ParsedCommit)>>,
+}
+
+impl RevWalk {
+    pub fn new() -> Self {
+        RevWalk::default()
+    }
+
+    /// Starts the walk at `hash`, a commit or a tag pointing at one.
+    pub fn push(&mut self, hash: &GitHash) -> io::Result<()> {
+        let hash = peel_to_commit(hash)?;
+        self.enqueue(hash)
+    }
+
+    /// Leaves the history of `hash` out of the walk.
+    pub fn hide(&mut self, hash: &GitHash) -> io::Result<()> {
+        let hash = peel_to_commit(hash)?;
+        self.bottoms.push(hash.clone());
+        self.mark_hidden(&hash);
+        self.enqueue(hash)
+    }
+
+    pub fn add(&mut self, tip: &Tip) -> io::Result<()> {
+        if tip.hidden { self.hide(&tip.hash) } else { self.push(&tip.hash) }
+    }
+
+    pub fn set_options(&mut self, opts: WalkOptions) {
+        self.opts = opts;
+    }
+
+    /// Limits the walk to commits touching `paths`, with git's default
+    /// history simplification.
+    pub fn limit_to_paths(&mut self, paths: PathSpec) {
+        self.paths = Some(paths);
+    }
+
+    /// Stops after `max` commits.
+    pub fn set_max_count(&mut self, max: Option<usize>) {
+        self.max_count = max;
+    }
+
+    /// Gives the commits in reverse, applied after `max_count`.
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.reverse = reverse;
+    }
+
+    /// Whether the walk has found `hash` to be in the history left out.
+    pub fn is_hidden(&self, hash: &GitHash) -> bool {
+        self.hidden.contains(hash)
+    }
+
+    /// The parents the walk follows.
+    fn followed<'a>(&self, parents: &'a [GitHash]) -> &'a [GitHash] {
+        if self.opts.first_parent { &parents[..parents.len().min(1)] } else { parents }
+    }
+
+    fn enqueue(&mut self, hash: GitHash) -> io::Result<()> {
+        if !self.seen.insert(hash.clone()) {
+            return Ok(());
+        }
+        let commit = read_commit(&hash)?;
+        self.parents.insert(hash.clone(), commit.parents.clone());
+        self.queued += 1;
+        self.queue.push(Queued { hash, commit, order: self.queued });
+        Ok(())
+    }
+
+    /// Hides a commit and, if it has been read already, its history.
+    fn mark_hidden(&mut self, hash: &GitHash) {
+        let mut stack = vec![hash.clone()];
+        while let Some(hash) = stack.pop() {
+            if !self.hidden.insert(hash.clone()) {
+                continue;
+            }
+            if let Some(parents) = self.parents.get(&hash) {
+                stack.extend(parents.iter().cloned());
+            }
+        }
+    }
+
+    /// Takes the newest queued commit and queues the parents worth following.
+    /// The flag says whether the commit is to be shown.
+    fn step(&mut self) -> io::Result<Option<(GitHash, ParsedCommit, bool)>> {
+        let Some(Queued { hash, commit, .. }) = self.queue.pop() else { return Ok(None) };
+        let parents = self.followed(&commit.parents).to_vec();
+
+        // hiding goes down every parent, even with `--first-parent`
+        if self.hidden.contains(&hash) {
+            for parent in commit.parents.iter().cloned() {
+                self.mark_hidden(&parent);
+                self.enqueue(parent)?;
+            }
+            return Ok(Some((hash, commit, false)));
+        }
+
+        let Some(paths) = &self.paths else {
+            for parent in parents.iter().cloned() {
+                self.enqueue(parent)?;
+            }
+            return Ok(Some((hash, commit, true)));
+        };
+
+        // a commit that leaves the paths as one of its parents had them is
+        // skipped, and history is only followed through that parent; a
+        // hidden parent doesn't count, so the other lines aren't lost
+        if parents.is_empty() {
+            let shown = !diff_trees(None, Some(&commit.tree), paths, true)?.is_empty();
+            return Ok(Some((hash, commit, shown)));
+        }
+        let mut treesame = None;
+        let mut changed = false;
+        for parent in &parents {
+            let parent_tree = read_commit(parent)?.tree;
+            if parent_tree != commit.tree && !diff_trees(Some(&parent_tree), Some(&commit.tree), paths, true)?.is_empty() {
+                changed = true;
+            } else if !self.hidden.contains(parent) {
+                treesame = Some(parent.clone());
+                break;
+            }
+        }
+        let shown = treesame.is_none() && changed;
+        match treesame {
+            Some(parent) => self.enqueue(parent)?,
+            None => {
+                for parent in parents {
+                    self.enqueue(parent)?;
+                }
+            }
+        }
+        Ok(Some((hash, commit, shown)))
+    }
+
+    /// Whether the walk can give commits as it reads them, rather than
+    /// having to read it all first.
+    fn is_streaming(&self) -> bool {
+        self.bottoms.is_empty() && self.opts.sort == Sort::Default && !self.opts.ancestry_path && !self.reverse
+    }
+
+    /// The next commit to show in date order.
+    fn next_by_date(&mut self) -> io::Result<Option<(GitHash, ParsedCommit)>> {
+        while let Some((hash, commit, shown)) = self.step()? {
+            if shown && self.opts.wants_parents(commit.parents.len()) {
+                return Ok(Some((hash, commit)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the walk until only hidden commits are left, then sorts and
+    /// filters what it found.
+    fn read_all(&mut self) -> io::Result<Vec<(GitHash, ParsedCommit)>> {
+        if self.opts.ancestry_path && self.bottoms.is_empty() {
+            return Err(CommandParseError::InvalidArgument("--ancestry-path given but there are no bottom commits".to_string()).into());
+        }
+
+        let mut list = Vec::new();
+        let mut skipped = HashSet::new();
+        let mut slop = SLOP;
+        let mut date = i64::MAX;
+        while let Some((hash, commit, shown)) = self.step()? {
+            if self.hidden.contains(&hash) {
+                slop = self.still_interesting(date, slop);
+                if slop == 0 {
+                    break;
+                }
+                continue;
+            }
+            date = commit.committer.timestamp;
+            if !shown {
+                skipped.insert(hash.clone());
+            }
+            list.push((hash, commit));
+        }
+
+        if self.opts.ancestry_path {
+            self.limit_to_ancestry(&list);
+        }
+        let list = match self.opts.sort {
+            Sort::Default => list,
+            sort => topo_sort(list, sort),
+        };
+        let mut list: Vec<_> = list
+            .into_iter()
+            .filter(|(hash, commit)| !self.hidden.contains(hash) && !skipped.contains(hash) && self.opts.wants_parents(commit.parents.len()))
+            .take(self.max_count.unwrap_or(usize::MAX))
+            .collect();
+        if self.reverse {
+            list.reverse();
+        }
+        Ok(list)
+    }
+
+    /// How much longer a limited walk should go on after reading a hidden
+    /// commit: while shown commits may still be found, `SLOP` more, else
+    /// one fewer than before.
+    fn still_interesting(&self, date: i64, slop: usize) -> usize {
+        let Some(newest) = self.queue.peek() else { return 0 };
+        if date <= newest.date() || self.queue.iter().any(|q| !self.hidden.contains(&q.hash)) {
+            return SLOP;
+        }
+        slop - 1
+    }
+
+    /// Hides the commits of `list` that don't descend from a hidden tip.
+    fn limit_to_ancestry(&mut self, list: &[(GitHash, ParsedCommit)]) {
+        let mut on_path: HashSet<GitHash> = self.bottoms.iter().cloned().collect();
+        // oldest first, so one pass marks every descendant; more are only
+        // needed where skewed dates put a parent before its child
+        loop {
+            let mut progress = false;
+            for (hash, commit) in list.iter().rev() {
+                if on_path.contains(hash) || self.hidden.contains(hash) {
+                    continue;
+                }
+                if commit.parents.iter().any(|p| on_path.contains(p)) {
+                    on_path.insert(hash.clone());
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        for (hash, _) in list {
+            if !on_path.contains(hash) {
+                self.hidden.insert(hash.clone());
+            }
+        }
+    }
+}
+
+/// Orders commits so each comes after all of its children, like git. In
+/// topological order the tips come in walk order and a merge's last parent
+/// line first; in the date orders the newest commit that is ready goes next.
+fn topo_sort(commits: Vec<(GitHash, ParsedCommit)>, sort: Sort) -> Vec<(GitHash, ParsedCommit)> {
+    // one more than the number of children in the list
+    let mut indegree: HashMap<GitHash, usize> = commits.iter().map(|(hash, _)| (hash.clone(), 1)).collect();
+    for (_, commit) in &commits {
+        for parent in &commit.parents {
+            if let Some(degree) = indegree.get_mut(parent) {
+                *degree += 1;
+            }
+        }
+    }
+
+    let mut by_hash: HashMap<GitHash, ParsedCommit> = HashMap::new();
+    let mut tips = Vec::new();
+    for (hash, commit) in commits {
+        if indegree[&hash] == 1 {
+            tips.push(hash.clone());
+        }
+        by_hash.insert(hash, commit);
+    }
+
+    // the topological order is a stack: the last commit ready goes first
+    let mut inserted = 0u64;
+    let mut ready = BinaryHeap::new();
+    let mut put = |ready: &mut BinaryHeap<(i64, u64, GitHash)>, hash: GitHash, commit: &ParsedCommit| {
+        inserted += 1;
+        let entry = match sort {
+            Sort::CommitDate => (commit.committer.timestamp, u64::MAX - inserted, hash),
+            Sort::AuthorDate => (commit.author.timestamp, u64::MAX - inserted, hash),
+            _ => (0, inserted, hash),
+        };
+        ready.push(entry);
+    };
+    if sort == Sort::Topo {
+        tips.reverse();
+    }
+    for hash in tips {
+        put(&mut ready, hash.clone(), &by_hash[&hash]);
+    }
+
+    let mut sorted = Vec::with_capacity(by_hash.len());
+    while let Some((_, _, hash)) = ready.pop() {
+        let commit = by_hash.remove(&hash).expect("sorted commit listed once");
+        for parent in &commit.parents {
+            if let Some(degree) = indegree.get_mut(parent) {
+                *degree -= 1;
+                if *degree == 1 {
+                    put(&mut ready, parent.clone(), &by_hash[parent]);
+                }
+            }
+        }
+        sorted.push((hash, commit));
+    }
+    sorted
+}
+
+impl Iterator for RevWalk {
+    type Item = io::Result<(GitHash, ParsedCommit)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_streaming() {
+            if self.max_count.is_some_and(|max| self.emitted >= max) {
+                return None;
+            }
+            let next = self.next_by_date().transpose();
+            self.emitted += 1;
+            return next;
+        }
+        if self.sorted.is_none() {
+            match self.read_all() {
+                Ok(commits) => self.sorted = Some(commits.into_iter()),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.sorted.as_mut()?.next().map(Ok)
+    }
+}
@@ -0,0 +1,45 @@
+use std::{env, io, path::Path, process::Command};
+
+use crate::config::git_config::GitConfig;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+/// The editor to use, looked up like git: `GIT_EDITOR`, `core.editor`,
+/// `VISUAL` (unless the terminal is dumb), `EDITOR`, then `vi`.
+pub fn editor_command(config: &GitConfig) -> io::Result<String> {
+    let dumb_terminal = env::var("TERM").map_or(true, |term| term == "dumb");
+    // an empty setting falls through to the next one
+    let var = |name| env::var(name).ok().filter(|e: &String| !e.is_empty());
+    let editor = var("GIT_EDITOR")
+        .or_else(|| config.get("core.editor").filter(|e| !e.is_empty()).map(str::to_string))
+        .or_else(|| var("VISUAL").filter(|_| !dumb_terminal))
+        .or_else(|| var("EDITOR"));
+
+    match editor {
+        Some(editor) => Ok(editor),
+        None if dumb_terminal => Err(io::Error::new(io::ErrorKind::NotFound, "Terminal is dumb, but EDITOR unset")),
+        None => Ok(DEFAULT_EDITOR.to_string()),
+    }
+}
+
+/// Opens `path` in the user's editor and waits for it to exit. The editor
+/// is run by the shell, so it may carry its own arguments.
+pub fn launch_editor(path: &Path, config: &GitConfig) -> io::Result<()> {
+    let editor = editor_command(config)?;
+    // `:` is how scripts ask for no editing at all
+    if editor == ":" {
+        return Ok(());
+    }
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("unable to start editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(io::Error::other(format!("There was a problem with the editor '{}'.", editor)));
+    }
+    Ok(())
+}
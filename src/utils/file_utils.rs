@@ -30,6 +30,22 @@ pub fn read_file(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
     Ok(contents)
 }
 
+/// Reads a working tree file the way git stores it as a blob: a symlink's
+/// content is its target path, not the file it points to.
+pub fn read_worktree_blob(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            return Ok(std::fs::read_link(path)?.as_os_str().as_bytes().to_vec());
+        }
+        #[cfg(not(unix))]
+        return Ok(std::fs::read_link(path)?.to_string_lossy().into_owned().into_bytes());
+    }
+    read_file(path)
+}
+
 pub fn dump_bytes_as_hex(bytes: &Bytes, file_path: &str) -> io::Result<()> {
     let mut file = File::create(file_path)?;
 
@@ -1,4 +1,8 @@
 pub mod file_utils;
+pub mod path_utils;
 pub mod print_utils;
 pub mod streamer;
-pub mod signal;
\ No newline at end of file
+pub mod wildmatch;
+pub mod parallel;
+pub mod signal;
+pub mod editor;
@@ -0,0 +1,54 @@
+use std::{num::NonZeroUsize, sync::atomic::{AtomicUsize, Ordering}, thread};
+
+use crate::config::git_config::GitConfig;
+
+/// How many threads per-file work such as hashing may use for `jobs` items:
+/// one per core, or a single thread when `core.preloadIndex` is turned off.
+pub fn worker_count(config: &GitConfig, jobs: usize) -> usize {
+    if config.get_bool("core.preloadIndex") == Some(false) {
+        return 1;
+    }
+    let cores = thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1);
+    cores.min(jobs).max(1)
+}
+
+/// Applies `f` to every item on up to `workers` threads. Results come back in
+/// input order, so callers can apply them deterministically.
+pub fn parallel_map<T, R, F>(items: &[T], workers: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if workers <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut slots: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let (next, f) = (&next, &f);
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else { break };
+                        done.push((i, f(item)));
+                    }
+                    done
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for (i, result) in handle.join().expect("worker thread panicked") {
+                slots[i] = Some(result);
+            }
+        }
+    });
+
+    slots.into_iter().map(|r| r.expect("every item is processed")).collect()
+}
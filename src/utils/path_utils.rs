@@ -0,0 +1,85 @@
+use std::{io, path::Path};
+
+/// Lexically normalizes a user supplied path into the repo-relative, `/` separated
+/// form stored in the index. The path does not need to exist, which is what
+/// commands like `mv` need for their destinations and `add` for deleted files.
+pub fn to_repo_path(path: &str) -> io::Result<String> {
+    let relative = if Path::new(path).is_absolute() {
+        let cwd = std::env::current_dir()?;
+        Path::new(path)
+            .strip_prefix(&cwd)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is outside repository", path)))?
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        path.to_string()
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for component in relative.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is outside repository", path)));
+                }
+            }
+            other => parts.push(other),
+        }
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Joins two repo-relative paths, treating an empty base as the repository root.
+pub fn join_repo_path(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+/// Returns the last component of a repo-relative path.
+pub fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Quotes a path the way git prints it (`core.quotePath`): paths with control
+/// characters, quotes, backslashes or non-ASCII bytes are wrapped in double
+/// quotes with C-style escapes; anything else is returned unchanged.
+pub fn quote_path(path: &str) -> String {
+    quote_path_with(path, false)
+}
+
+/// Like `quote_path`, but also quotes paths containing spaces, as git's short
+/// status formats do so their lines can be split on whitespace.
+pub fn quote_path_with_spaces(path: &str) -> String {
+    quote_path_with(path, true)
+}
+
+fn quote_path_with(path: &str, quote_spaces: bool) -> String {
+    let needs_quoting = path.bytes().any(|b| b < 0x20 || b == b'"' || b == b'\\' || b >= 0x7f || (quote_spaces && b == b' '));
+    if !needs_quoting {
+        return path.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for b in path.bytes() {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b if !(0x20..0x7f).contains(&b) => quoted.push_str(&format!("\\{:03o}", b)),
+            b => quoted.push(b as char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -21,7 +21,7 @@ where
         event_name.as_str()
     );
     match event_name {
-        _ctrl_c => {
+        EventName::CtrlC => {
             ctrlc::set_handler(move || {
                 handler();
             }).expect("Error setting Ctrl-C handler");
@@ -0,0 +1,213 @@
+//! Port of git's `wildmatch`, the glob matcher behind ignore rules and pathspecs.
+
+/// `*` and `?` do not match `/`, and `**` spans directories only as a full path component.
+pub const WM_PATHNAME: u32 = 1;
+/// Match case-insensitively.
+pub const WM_CASEFOLD: u32 = 2;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum MatchResult {
+    Match,
+    NoMatch,
+    AbortAll,
+    AbortToStarStar,
+}
+
+pub fn wildmatch(pattern: &str, text: &str, flags: u32) -> bool {
+    let fold = |s: &str| -> Vec<char> {
+        if flags & WM_CASEFOLD != 0 {
+            s.chars().flat_map(char::to_lowercase).collect()
+        } else {
+            s.chars().collect()
+        }
+    };
+    let pattern = fold(pattern);
+    let text = fold(text);
+    dowild(&pattern, &text, flags & WM_PATHNAME != 0) == MatchResult::Match
+}
+
+/// Returns true if the pattern contains characters with glob meaning.
+pub fn has_glob_chars(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '\\'])
+}
+
+fn dowild(p: &[char], t: &[char], pathname: bool) -> MatchResult {
+    let mut pi = 0;
+    let mut ti = 0;
+
+    while pi < p.len() {
+        let p_ch = p[pi];
+        if ti >= t.len() && p_ch != '*' {
+            return MatchResult::AbortAll;
+        }
+
+        match p_ch {
+            '\\' => {
+                pi += 1;
+                if pi >= p.len() || t[ti] != p[pi] {
+                    return MatchResult::NoMatch;
+                }
+            }
+            '?' => {
+                if pathname && t[ti] == '/' {
+                    return MatchResult::NoMatch;
+                }
+            }
+            '*' => {
+                pi += 1;
+                let match_slash;
+                if pi < p.len() && p[pi] == '*' {
+                    let starts_component = pi < 2 || p[pi - 2] == '/';
+                    while pi < p.len() && p[pi] == '*' {
+                        pi += 1;
+                    }
+                    if !pathname {
+                        match_slash = true;
+                    } else if starts_component && (pi == p.len() || p[pi] == '/') {
+                        // "**/" may also match zero leading directories
+                        if pi < p.len() && dowild(&p[pi + 1..], &t[ti..], pathname) == MatchResult::Match {
+                            return MatchResult::Match;
+                        }
+                        match_slash = true;
+                    } else {
+                        match_slash = false;
+                    }
+                } else {
+                    match_slash = !pathname;
+                }
+
+                if pi == p.len() {
+                    // trailing "**" matches everything, a trailing "*" only within one component
+                    if !match_slash && t[ti..].contains(&'/') {
+                        return MatchResult::NoMatch;
+                    }
+                    return MatchResult::Match;
+                } else if !match_slash && p[pi] == '/' {
+                    // "*/" matches the rest of the current component; the slash is consumed below
+                    match t[ti..].iter().position(|&c| c == '/') {
+                        Some(offset) => ti += offset,
+                        None => return MatchResult::NoMatch,
+                    }
+                } else {
+                    loop {
+                        if ti >= t.len() {
+                            break;
+                        }
+                        let matched = dowild(&p[pi..], &t[ti..], pathname);
+                        if matched != MatchResult::NoMatch {
+                            if !match_slash || matched != MatchResult::AbortToStarStar {
+                                return matched;
+                            }
+                        } else if !match_slash && t[ti] == '/' {
+                            return MatchResult::AbortToStarStar;
+                        }
+                        ti += 1;
+                    }
+                    return MatchResult::AbortAll;
+                }
+            }
+            '[' => {
+                let (matched, end) = match match_class(p, pi, t[ti]) {
+                    Some(result) => result,
+                    None => return MatchResult::AbortAll,
+                };
+                if !matched || (pathname && t[ti] == '/') {
+                    return MatchResult::NoMatch;
+                }
+                pi = end;
+            }
+            c => {
+                if t[ti] != c {
+                    return MatchResult::NoMatch;
+                }
+            }
+        }
+
+        pi += 1;
+        ti += 1;
+    }
+
+    if ti == t.len() {
+        MatchResult::Match
+    } else {
+        MatchResult::NoMatch
+    }
+}
+
+/// Matches `t_ch` against the bracket expression starting at `p[start] == '['`.
+/// Returns whether it matched and the index of the closing `]`, or `None` if unterminated.
+fn match_class(p: &[char], start: usize, t_ch: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let mut negated = false;
+    if i < p.len() && (p[i] == '!' || p[i] == '^') {
+        negated = true;
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut prev: Option<char> = None;
+    let mut first = true;
+
+    loop {
+        let c = *p.get(i)?;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+
+        if c == '\\' {
+            i += 1;
+            let escaped = *p.get(i)?;
+            if t_ch == escaped {
+                matched = true;
+            }
+            prev = Some(escaped);
+        } else if c == '-' && prev.is_some() && p.get(i + 1).is_some_and(|&n| n != ']') {
+            i += 1;
+            let mut upper = p[i];
+            if upper == '\\' {
+                i += 1;
+                upper = *p.get(i)?;
+            }
+            if prev.is_some_and(|lower| lower <= t_ch && t_ch <= upper) {
+                matched = true;
+            }
+            prev = None;
+        } else if c == '[' && p.get(i + 1) == Some(&':') {
+            let rest: String = p[i + 2..].iter().collect();
+            let close = rest.find(":]")?;
+            let class = &rest[..close];
+            if matches_named_class(class, t_ch)? {
+                matched = true;
+            }
+            i += 2 + class.chars().count() + 1;
+            prev = None;
+        } else {
+            if t_ch == c {
+                matched = true;
+            }
+            prev = Some(c);
+        }
+        i += 1;
+    }
+
+    Some((matched != negated, i))
+}
+
+fn matches_named_class(class: &str, c: char) -> Option<bool> {
+    Some(match class {
+        "alnum" => c.is_ascii_alphanumeric(),
+        "alpha" => c.is_ascii_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_ascii_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => c.is_ascii_graphic(),
+        "lower" => c.is_ascii_lowercase(),
+        "print" => c.is_ascii_graphic() || c == ' ',
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_ascii_whitespace(),
+        "upper" => c.is_ascii_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => return None,
+    })
+}
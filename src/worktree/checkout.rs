@@ -0,0 +1,282 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, io, path::Path};
+
+use crate::{
+    constants::INDEX_PATH,
+    hash::GitHash,
+    ignore::matcher::IgnoreMatcher,
+    index::{index::{entries_from_tree, load_index, write_index, Index}, index_entry::IndexEntry},
+    objects::{read_object, FileMode, ObjectType},
+    worktree::{file_state::{check_entry, FileState}, walker::WorktreeWalker},
+};
+
+/// Paths a checkout refused to touch, grouped the way git reports them.
+#[derive(Debug, Default)]
+pub struct Conflicts {
+    /// Tracked files whose local changes would be lost.
+    pub local_changes: Vec<String>,
+    /// Untracked files that would be overwritten by a file of the new tree.
+    pub untracked_overwritten: Vec<String>,
+    /// Untracked files in a directory that would be replaced by a file.
+    pub untracked_removed: Vec<String>,
+}
+
+impl Conflicts {
+    pub fn is_empty(&self) -> bool {
+        self.local_changes.is_empty() && self.untracked_overwritten.is_empty() && self.untracked_removed.is_empty()
+    }
+
+    /// Prints git's explanation of why the checkout was aborted.
+    pub fn report(&self) {
+        let groups = [
+            (&self.local_changes, "Your local changes to the following files would be overwritten by checkout:", "Please commit your changes or stash them before you switch branches."),
+            (&self.untracked_overwritten, "The following untracked working tree files would be overwritten by checkout:", "Please move or remove them before you switch branches."),
+            (&self.untracked_removed, "The following untracked working tree files would be removed by checkout:", "Please move or remove them before you switch branches."),
+        ];
+        for (paths, headline, advice) in groups {
+            if paths.is_empty() {
+                continue;
+            }
+            eprintln!("error: {}", headline);
+            for path in paths {
+                eprintln!("\t{}", path);
+            }
+            eprintln!("{}", advice);
+        }
+        eprintln!("Aborting");
+    }
+}
+
+/// Moves the working tree and the index from tree `old` (what HEAD has now,
+/// `None` for no commit yet) to tree `new`, like `git checkout <commit>`.
+///
+/// Files that differ between the trees are rewritten and files the new tree
+/// lacks are removed. Local changes to paths the trees agree on are carried
+/// over; changes that would be lost and untracked files in the way abort the
+/// whole checkout before anything is touched, unless `force` is set.
+pub fn checkout_tree(old: Option<&GitHash>, new: &GitHash, force: bool) -> io::Result<()> {
+    let index = load_index(Path::new(INDEX_PATH))?;
+    if !force && index.has_unmerged() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "you need to resolve your current index first"));
+    }
+
+    let head = match old {
+        Some(tree) => entries_from_tree(tree, "")?,
+        None => Index::default(),
+    };
+    let target = entries_from_tree(new, "")?;
+    let plan = plan_checkout(&index, &head, &target, force)?;
+    if !plan.conflicts.is_empty() {
+        plan.conflicts.report();
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "checkout would overwrite local changes"));
+    }
+
+    for path in &plan.removals {
+        remove_worktree_file(path)?;
+    }
+    for entry in &plan.updates {
+        write_worktree_entry(entry)?;
+    }
+    write_index(Path::new(INDEX_PATH), &Index::from_entries(plan.result))
+}
+
+/// What a checkout does: the new index, the files to write and the files to remove.
+struct CheckoutPlan {
+    result: Vec<IndexEntry>,
+    updates: Vec<IndexEntry>,
+    removals: Vec<String>,
+    conflicts: Conflicts,
+}
+
+fn by_path(entries: &Index) -> BTreeMap<&str, &IndexEntry> {
+    entries.iter().filter(|e| !e.is_unmerged()).map(|e| (e.path.as_str(), e)).collect()
+}
+
+fn same(a: Option<&&IndexEntry>, b: Option<&&IndexEntry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.mode == b.mode && a.hash == b.hash && !a.intent_to_add && !b.intent_to_add,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Decides every path with git's two-way merge rules: a path the trees agree
+/// on keeps its index entry, a path whose index matches the old tree moves to
+/// the new one, and anything else is a local change in the way.
+fn plan_checkout(index: &Index, head: &Index, target: &Index, force: bool) -> io::Result<CheckoutPlan> {
+    let tracked: BTreeSet<&str> = index.iter().map(|e| e.path.as_str()).collect();
+    let (index_map, head_map, target_map) = (by_path(index), by_path(head), by_path(target));
+    let paths: BTreeSet<&str> = tracked.iter().copied().chain(head_map.keys().copied()).chain(target_map.keys().copied()).collect();
+
+    let mut plan = CheckoutPlan { result: Vec::new(), updates: Vec::new(), removals: Vec::new(), conflicts: Conflicts::default() };
+    for path in paths {
+        let (i, h, m) = (index_map.get(path), head_map.get(path), target_map.get(path));
+
+        if force {
+            match m {
+                Some(entry) => {
+                    if !same(i, m) || check_entry(entry)? != FileState::Unchanged {
+                        plan.updates.push((*entry).clone());
+                    }
+                    plan.result.push((*entry).clone());
+                }
+                None => plan.removals.push(path.to_string()),
+            }
+            continue;
+        }
+
+        if same(h, m) || same(i, m) {
+            if let Some(entry) = i {
+                plan.result.push((*entry).clone());
+            }
+        } else if same(i, h) {
+            if let Some(entry) = i {
+                if check_entry(entry)? == FileState::Modified {
+                    plan.conflicts.local_changes.push(path.to_string());
+                    continue;
+                }
+            }
+            match m {
+                Some(entry) => {
+                    plan.updates.push((*entry).clone());
+                    plan.result.push((*entry).clone());
+                }
+                None => plan.removals.push(path.to_string()),
+            }
+        } else {
+            plan.conflicts.local_changes.push(path.to_string());
+        }
+    }
+
+    if !force {
+        find_untracked_in_the_way(&plan.updates, &tracked, &mut plan.conflicts)?;
+    }
+    Ok(plan)
+}
+
+/// Records untracked, not ignored files that writing `updates` would destroy:
+/// a file at a path to write, a file where a directory is needed, or files
+/// inside a directory that a file replaces.
+fn find_untracked_in_the_way(updates: &[IndexEntry], tracked: &BTreeSet<&str>, conflicts: &mut Conflicts) -> io::Result<()> {
+    let mut walker = WorktreeWalker::new(Some(IgnoreMatcher::load()?));
+    let mut reported = BTreeSet::new();
+
+    for entry in updates {
+        for (pos, _) in entry.path.match_indices('/') {
+            let dir = &entry.path[..pos];
+            let Ok(meta) = fs::symlink_metadata(dir) else { break };
+            if !meta.is_dir() && !tracked.contains(dir) && !is_ignored(&mut walker, dir, false)? && reported.insert(dir.to_string()) {
+                conflicts.untracked_overwritten.push(dir.to_string());
+            }
+        }
+
+        if tracked.contains(entry.path.as_str()) {
+            continue;
+        }
+        let Ok(meta) = fs::symlink_metadata(&entry.path) else { continue };
+        if meta.is_dir() {
+            for file in walker.files_under(&entry.path)? {
+                if !tracked.contains(file.as_str()) && reported.insert(file.clone()) {
+                    conflicts.untracked_removed.push(file);
+                }
+            }
+        } else if !is_ignored(&mut walker, &entry.path, false)? && reported.insert(entry.path.clone()) {
+            conflicts.untracked_overwritten.push(entry.path.clone());
+        }
+    }
+    Ok(())
+}
+
+fn is_ignored(walker: &mut WorktreeWalker, path: &str, is_dir: bool) -> io::Result<bool> {
+    match walker.ignore_matcher() {
+        Some(matcher) => matcher.is_excluded(path, is_dir),
+        None => Ok(false),
+    }
+}
+
+/// Deletes a file and any directories above it that become empty.
+pub fn remove_worktree_file(path: &str) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => return Ok(()),
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut dir = Path::new(path).parent();
+    while let Some(d) = dir.filter(|d| !d.as_os_str().is_empty()) {
+        // stops at the first directory that still has something in it
+        if fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(())
+}
+
+/// Writes the blob of an index entry to its path: a symlink for
+/// `FileMode::Symlink`, otherwise a file with the executable bits the mode
+/// asks for. Whatever is in the way is replaced.
+pub fn write_worktree_entry(entry: &IndexEntry) -> io::Result<()> {
+    let (object_type, content) = read_object(&entry.hash.to_hex())?;
+    if object_type != ObjectType::Blob {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a blob", entry.hash.to_hex())));
+    }
+    let path = Path::new(&entry.path);
+    prepare_parent_dirs(path)?;
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+
+    if entry.mode == FileMode::Symlink {
+        return write_symlink(&content, path);
+    }
+    write_file(path, &content, entry.mode == FileMode::Executable)
+}
+
+/// Creates the directories above `path`, removing files that stand where one is needed.
+fn prepare_parent_dirs(path: &Path) -> io::Result<()> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else { return Ok(()) };
+    for dir in parent.ancestors().collect::<Vec<_>>().into_iter().rev() {
+        if dir.as_os_str().is_empty() {
+            continue;
+        }
+        match fs::symlink_metadata(dir) {
+            Ok(meta) if meta.is_dir() => continue,
+            Ok(_) => fs::remove_file(dir)?,
+            Err(_) => {}
+        }
+        fs::create_dir(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_file(path: &Path, content: &[u8], executable: bool) -> io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    // like git, the umask decides the final permissions
+    let mode = if executable { 0o777 } else { 0o666 };
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(mode).open(path)?;
+    file.write_all(content)
+}
+
+#[cfg(not(unix))]
+fn write_file(path: &Path, content: &[u8], _executable: bool) -> io::Result<()> {
+    fs::write(path, content)
+}
+
+#[cfg(unix)]
+fn write_symlink(target: &[u8], path: &Path) -> io::Result<()> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    std::os::unix::fs::symlink(OsStr::from_bytes(target), path)
+}
+
+/// Without symlink support the link is checked out as a file holding its
+/// target, as git does with `core.symlinks=false`.
+#[cfg(not(unix))]
+fn write_symlink(target: &[u8], path: &Path) -> io::Result<()> {
+    fs::write(path, target)
+}
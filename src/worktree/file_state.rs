@@ -0,0 +1,39 @@
+use std::{fs, io, path::Path};
+
+use crate::{index::index_entry::IndexEntry, objects::{hash_object, FileMode, ObjectType}, utils::file_utils::read_worktree_blob};
+
+/// How a tracked file in the working tree compares to its index entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    Unchanged,
+    Modified,
+    Deleted,
+}
+
+pub fn check_entry(entry: &IndexEntry) -> io::Result<FileState> {
+    let path = Path::new(&entry.path);
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileState::Deleted),
+        // a file where one of its directories used to be
+        Err(_) if path.parent().is_some_and(|p| !p.as_os_str().is_empty() && !p.is_dir()) => return Ok(FileState::Deleted),
+        Err(e) => return Err(e),
+    };
+
+    // a directory where a file used to be means the file is gone
+    if meta.is_dir() {
+        return Ok(FileState::Deleted);
+    }
+
+    // an unmerged path needs resolving whatever its content
+    if entry.is_unmerged() || entry.intent_to_add || FileMode::from_path(path)? != entry.mode {
+        return Ok(FileState::Modified);
+    }
+
+    let (hash, _) = hash_object(ObjectType::Blob, &read_worktree_blob(path)?);
+    if hash == entry.hash {
+        Ok(FileState::Unchanged)
+    } else {
+        Ok(FileState::Modified)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod walker;
+pub mod file_state;
+pub mod status;
+pub mod checkout;
@@ -0,0 +1,326 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use crate::{config::git_config::GitConfig, diff::{changes::{ChangeStatus, DiffSide, FileChange}, rename::{detect_renames, RenameOptions}}, hash::GitHash, ignore::matcher::IgnoreMatcher, index::{index::{entries_from_tree, load_index, Index}, index_entry::IndexEntry}, constants::INDEX_PATH, objects::{commit_object::read_commit, FileMode}, pathspec::PathSpec, refs::{head_commit, read_head, resolve_ref, shorten_ref, upstream_ref, Head}, revwalk::RevWalk, utils::parallel::{parallel_map, worker_count}, worktree::{file_state::{check_entry, FileState}, walker::WorktreeWalker}};
+
+/// How one side of a path changed, as the status letters git uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    TypeChanged,
+    /// Staged only: the path was moved from [`ChangedPath::orig_path`].
+    Renamed,
+    /// Staged only: the path was copied from [`ChangedPath::orig_path`].
+    Copied,
+}
+
+impl ChangeKind {
+    pub fn code(self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+            ChangeKind::TypeChanged => 'T',
+            ChangeKind::Renamed => 'R',
+            ChangeKind::Copied => 'C',
+        }
+    }
+
+    /// The label used by the long format, e.g. `new file`.
+    pub fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "new file",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::TypeChanged => "typechange",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::Copied => "copied",
+        }
+    }
+}
+
+/// A tracked path that differs between HEAD and the index, or the index and the working tree.
+#[derive(Debug, Clone)]
+pub struct ChangedPath {
+    pub path: String,
+    /// The HEAD path a staged rename or copy came from.
+    pub orig_path: Option<String>,
+    /// How similar a rename or copy is to its source, in percent.
+    pub similarity: u32,
+    /// HEAD → index.
+    pub staged: Option<ChangeKind>,
+    /// Index → working tree.
+    pub unstaged: Option<ChangeKind>,
+    pub head: Option<IndexEntry>,
+    pub index: Option<IndexEntry>,
+    pub worktree_mode: Option<FileMode>,
+}
+
+/// A path with conflict stages in the index.
+#[derive(Debug, Clone)]
+pub struct UnmergedPath {
+    pub path: String,
+    /// Stages 1 (base), 2 (ours) and 3 (theirs).
+    pub stages: [Option<IndexEntry>; 3],
+    pub worktree_mode: Option<FileMode>,
+}
+
+impl UnmergedPath {
+    /// Git's two letter conflict code, e.g. `UU` for both modified.
+    pub fn code(&self) -> &'static str {
+        match self.stages.each_ref().map(Option::is_some) {
+            [true, false, false] => "DD",
+            [false, true, false] => "AU",
+            [true, true, false] => "UD",
+            [false, false, true] => "UA",
+            [true, false, true] => "DU",
+            [false, true, true] => "AA",
+            _ => "UU",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self.code() {
+            "DD" => "both deleted",
+            "AU" => "added by us",
+            "UD" => "deleted by them",
+            "UA" => "added by them",
+            "DU" => "deleted by us",
+            "AA" => "both added",
+            _ => "both modified",
+        }
+    }
+}
+
+/// The branch's configured upstream and how far apart the two are.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    /// Short name, e.g. `origin/main`.
+    pub name: String,
+    /// Commits only on the branch and only on the upstream; `None` if the upstream ref is gone.
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntrackedMode {
+    No,
+    /// Untracked directories are shown as a single `dir/` entry.
+    Normal,
+    All,
+}
+
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub head: Head,
+    pub head_commit: Option<GitHash>,
+    pub upstream: Option<Upstream>,
+    /// Sorted by path.
+    pub changes: Vec<ChangedPath>,
+    pub unmerged: Vec<UnmergedPath>,
+    pub untracked: Vec<String>,
+    /// How untracked files were looked for; with `No`, `untracked` is empty.
+    pub untracked_mode: UntrackedMode,
+}
+
+impl Status {
+    pub fn has_staged(&self) -> bool {
+        self.changes.iter().any(|c| c.staged.is_some())
+    }
+
+    pub fn has_unstaged(&self) -> bool {
+        self.changes.iter().any(|c| c.unstaged.is_some())
+    }
+}
+
+pub fn collect_status(pathspec: &PathSpec, untracked_mode: UntrackedMode, config: &GitConfig) -> io::Result<Status> {
+    let head = read_head()?;
+    let head_commit = head_commit()?;
+    let head_tree = match &head_commit {
+        Some(commit) => entries_from_tree(&read_commit(commit)?.tree, "")?,
+        None => Index::default(),
+    };
+    let index = load_index(Path::new(INDEX_PATH))?;
+
+    let upstream = match head.branch_name() {
+        Some(branch) => find_upstream(config, branch, head_commit.as_ref())?,
+        None => None,
+    };
+
+    let (changes, unmerged) = compare(&head_tree, &index, pathspec, config)?;
+    let untracked = match untracked_mode {
+        UntrackedMode::No => Vec::new(),
+        mode => list_untracked(&index, pathspec, mode)?,
+    };
+
+    Ok(Status { head, head_commit, upstream, changes, unmerged, untracked, untracked_mode })
+}
+
+fn find_upstream(config: &GitConfig, branch: &str, local: Option<&GitHash>) -> io::Result<Option<Upstream>> {
+    let Some(upstream_name) = upstream_ref(config, branch) else {
+        return Ok(None);
+    };
+
+    let ahead_behind = match (local, resolve_ref(&upstream_name)?) {
+        (Some(local), Some(remote)) => Some(ahead_behind(local, &remote)?),
+        (None, Some(remote)) => Some((0, count_commits(&remote, None)?)),
+        (_, None) => None,
+    };
+
+    Ok(Some(Upstream {
+        name: shorten_ref(&upstream_name).to_string(),
+        ahead_behind,
+    }))
+}
+
+/// Counts the commits reachable only from `local` and only from `upstream`.
+/// Each count is a walk with the other tip hidden, so it stops at the merge base.
+pub fn ahead_behind(local: &GitHash, upstream: &GitHash) -> io::Result<(usize, usize)> {
+    Ok((count_commits(local, Some(upstream))?, count_commits(upstream, Some(local))?))
+}
+
+fn count_commits(tip: &GitHash, hidden: Option<&GitHash>) -> io::Result<usize> {
+    let mut walk = RevWalk::new();
+    walk.push(tip)?;
+    if let Some(hidden) = hidden {
+        walk.hide(hidden)?;
+    }
+    walk.try_fold(0, |count, commit| commit.map(|_| count + 1))
+}
+
+fn compare(head_tree: &Index, index: &Index, pathspec: &PathSpec, config: &GitConfig) -> io::Result<(Vec<ChangedPath>, Vec<UnmergedPath>)> {
+    let mut paths: Vec<&str> = head_tree.iter().chain(index.iter()).map(|e| e.path.as_str()).filter(|p| pathspec.matches(p)).collect();
+    paths.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    paths.dedup();
+
+    let mut changes = Vec::new();
+    let mut unmerged = Vec::new();
+    for path in paths {
+        let stages = index.stages(path);
+        if stages.iter().any(IndexEntry::is_unmerged) {
+            let mut by_stage: [Option<IndexEntry>; 3] = Default::default();
+            for entry in stages {
+                by_stage[entry.stage as usize - 1] = Some(entry.clone());
+            }
+            unmerged.push(UnmergedPath { path: path.to_string(), stages: by_stage, worktree_mode: worktree_mode(path) });
+            continue;
+        }
+
+        let head = head_tree.get(path, 0);
+        let entry = index.get(path, 0);
+        let staged = match (head, entry) {
+            (_, Some(e)) if e.intent_to_add => None,
+            (None, Some(_)) => Some(ChangeKind::Added),
+            (Some(_), None) => Some(ChangeKind::Deleted),
+            (Some(h), Some(e)) if is_symlink(&h.mode) != is_symlink(&e.mode) => Some(ChangeKind::TypeChanged),
+            (Some(h), Some(e)) if h.mode != e.mode || h.hash != e.hash => Some(ChangeKind::Modified),
+            _ => None,
+        };
+
+        changes.push(ChangedPath {
+            path: path.to_string(),
+            orig_path: None,
+            similarity: 0,
+            staged,
+            unstaged: None,
+            head: head.cloned(),
+            index: entry.cloned(),
+            worktree_mode: None,
+        });
+    }
+
+    find_staged_renames(&mut changes, head_tree, config)?;
+
+    // the working tree side needs file hashing, so it runs on the worker pool
+    let states = parallel_map(&changes, worker_count(config, changes.len()), |change| match &change.index {
+        Some(entry) => check_entry(entry).map(Some),
+        None => Ok(None),
+    });
+    for (change, state) in changes.iter_mut().zip(states) {
+        let Some(entry) = &change.index else { continue };
+        change.worktree_mode = worktree_mode(&change.path);
+        change.unstaged = match state? {
+            Some(FileState::Deleted) => Some(ChangeKind::Deleted),
+            _ if entry.intent_to_add => Some(ChangeKind::Added),
+            Some(FileState::Modified) if change.worktree_mode.as_ref().is_some_and(|m| is_symlink(m) != is_symlink(&entry.mode)) => Some(ChangeKind::TypeChanged),
+            Some(FileState::Modified) => Some(ChangeKind::Modified),
+            _ => None,
+        };
+    }
+    changes.retain(|c| c.staged.is_some() || c.unstaged.is_some());
+
+    Ok((changes, unmerged))
+}
+
+/// Pairs staged deletions with staged additions, as `diff --cached` does.
+/// A rename's old path is dropped; its HEAD entry moves to the new path.
+fn find_staged_renames(changes: &mut Vec<ChangedPath>, head_tree: &Index, config: &GitConfig) -> io::Result<()> {
+    let staged: Vec<FileChange> = changes
+        .iter()
+        .filter(|c| c.staged.is_some())
+        .map(|c| FileChange::new(c.path.clone(), c.head.as_ref().map(DiffSide::of_entry), c.index.as_ref().map(DiffSide::of_entry)))
+        .collect();
+    let head_files = || Ok(head_tree.iter().map(|e| (e.path.clone(), DiffSide::of_entry(e))).collect());
+
+    let mut renamed_away = HashSet::new();
+    for found in detect_renames(staged, &RenameOptions::from_config(config), head_files)? {
+        let Some(orig_path) = found.old_path else { continue };
+        let Some(change) = changes.iter_mut().find(|c| c.path == found.path) else { continue };
+        change.staged = Some(if found.status == ChangeStatus::Copied { ChangeKind::Copied } else { ChangeKind::Renamed });
+        change.head = head_tree.get(&orig_path, 0).cloned();
+        change.similarity = found.similarity;
+        if found.status == ChangeStatus::Renamed {
+            renamed_away.insert(orig_path.clone());
+        }
+        change.orig_path = Some(orig_path);
+    }
+    changes.retain(|c| !(c.staged == Some(ChangeKind::Deleted) && renamed_away.contains(&c.path)));
+    Ok(())
+}
+
+fn is_symlink(mode: &FileMode) -> bool {
+    *mode == FileMode::Symlink
+}
+
+fn worktree_mode(path: &str) -> Option<FileMode> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if !meta.is_dir() => FileMode::from_path(Path::new(path)).ok(),
+        _ => None,
+    }
+}
+
+/// Untracked, non-ignored files in scope. In `Normal` mode a directory holding
+/// no tracked files is reported once, as `dir/`.
+fn list_untracked(index: &Index, pathspec: &PathSpec, mode: UntrackedMode) -> io::Result<Vec<String>> {
+    let mut walker = WorktreeWalker::new(Some(IgnoreMatcher::load()?));
+    let mut untracked = Vec::new();
+
+    for base in pathspec.base_dirs() {
+        for path in walker.files_under(&base)? {
+            if index.contains(&path) || !pathspec.matches(&path) {
+                continue;
+            }
+            let shown = match mode {
+                UntrackedMode::Normal => collapse_untracked_dir(index, &path),
+                _ => path,
+            };
+            untracked.push(shown);
+        }
+    }
+
+    untracked.sort();
+    untracked.dedup();
+    Ok(untracked)
+}
+
+fn collapse_untracked_dir(index: &Index, path: &str) -> String {
+    let mut end = 0;
+    while let Some(pos) = path[end..].find('/') {
+        let dir = &path[..end + pos];
+        if index.entries_under(dir).is_empty() {
+            return format!("{}/", dir);
+        }
+        end += pos + 1;
+    }
+    path.to_string()
+}
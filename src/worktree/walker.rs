@@ -0,0 +1,77 @@
+use std::{fs, io};
+
+use crate::{constants::GIT_DIR, ignore::matcher::IgnoreMatcher, utils::path_utils::join_repo_path};
+
+/// Walks the working tree yielding repo-relative file paths, skipping `.git`
+/// and, when an ignore matcher is set, ignored files and directories.
+pub struct WorktreeWalker {
+    ignore: Option<IgnoreMatcher>,
+}
+
+impl WorktreeWalker {
+    pub fn new(ignore: Option<IgnoreMatcher>) -> Self {
+        Self { ignore }
+    }
+
+    pub fn ignore_matcher(&mut self) -> Option<&mut IgnoreMatcher> {
+        self.ignore.as_mut()
+    }
+
+    /// Lists the files at or below a repo-relative path ("" is the whole tree).
+    /// A path naming a file is returned as is, ignored or not; ignore rules only
+    /// prune what is discovered while descending into directories.
+    pub fn files_under(&mut self, scope: &str) -> io::Result<Vec<String>> {
+        if is_git_dir(scope) {
+            return Ok(Vec::new());
+        }
+
+        let disk_path = if scope.is_empty() { "." } else { scope };
+        let meta = match fs::symlink_metadata(disk_path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        if !meta.is_dir() {
+            return Ok(vec![scope.to_string()]);
+        }
+
+        let mut result = Vec::new();
+        self.walk_dir(scope, &mut result)?;
+        Ok(result)
+    }
+
+    fn walk_dir(&mut self, dir: &str, result: &mut Vec<String>) -> io::Result<()> {
+        let disk_path = if dir.is_empty() { "." } else { dir };
+        let mut children = fs::read_dir(disk_path)?.collect::<io::Result<Vec<_>>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+
+        for entry in children {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == GIT_DIR {
+                continue;
+            }
+
+            let path = join_repo_path(dir, &name);
+            let is_dir = entry.file_type()?.is_dir();
+
+            if let Some(ignore) = self.ignore.as_mut() {
+                if ignore.is_excluded(&path, is_dir)? {
+                    continue;
+                }
+            }
+
+            if is_dir {
+                self.walk_dir(&path, result)?;
+            } else {
+                result.push(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_git_dir(path: &str) -> bool {
+    path == GIT_DIR || path.starts_with(&format!("{}/", GIT_DIR))
+}